@@ -262,6 +262,95 @@ export namespace matchmaking {
     user: bigint
   }
 }
+export namespace matchmakingServers {
+  export const enum ServerListKind {
+    Internet = 0,
+    Lan = 1,
+    Friends = 2,
+    Favorites = 3,
+    History = 4
+  }
+  export interface ServerFilter {
+    /** e.g. `gametagsand`, `gamedataand`, `map`, `notfull`, `region` */
+    key: string
+    value: string
+  }
+  export interface GameServer {
+    ip: number
+    connectionPort: number
+    queryPort: number
+    /** Current ping time in milliseconds */
+    ping: number
+    hadSuccessfulResponse: boolean
+    doNotRefresh: boolean
+    gameDir: string
+    map: string
+    gameDescription: string
+    appId: number
+    /** Number of players currently on the server, including bots */
+    players: number
+    maxPlayers: number
+    botPlayers: number
+    password: boolean
+    secure: boolean
+    /** Unix time when this server was last played on (favorites and history only) */
+    lastPlayed: number
+    serverVersion: number
+    name: string
+    gameTags: Array<string>
+    /** Use with `SteamClient.setSteamIdGameServer` to connect, 0 if the server is not logged on */
+    steamId: bigint
+  }
+  export interface ServerPlayer {
+    name: string
+    score: number
+    /** Seconds the player has been connected */
+    timePlayed: number
+  }
+  /**
+   * Requests a list of game servers for the current app
+   *
+   * `onServer` is called for every server as it responds, `filters` are ignored for LAN lists
+   */
+  export function requestServerList(kind: ServerListKind, filters: Array<ServerFilter> | undefined | null, onServer: (server: GameServer) => void): ServerListQuery
+  /** Queries the details of a single server by its ip and query port */
+  export function pingServer(ip: number, queryPort: number): ServerPingQuery
+  /** Queries the players on a single server by its ip and query port */
+  export function getServerPlayers(ip: number, queryPort: number): ServerPlayersQuery
+  /** Queries the rules of a single server by its ip and query port */
+  export function getServerRules(ip: number, queryPort: number): ServerRulesQuery
+  /** A running server list request */
+  export class ServerListQuery {
+    /** Stops the query, `wait()` resolves with the servers found so far */
+    cancel(): void
+    isRefreshing(): boolean
+    /** Number of servers found so far */
+    getServerCount(): number
+    /** Resolves with all the servers found once the list is complete or the query is cancelled */
+    wait(): Promise<Array<GameServer>>
+  }
+  /** A running query of a single server, cancelled when the object is garbage collected */
+  export class ServerPingQuery {
+    /** Stops the query, `wait()` rejects */
+    cancel(): void
+    /** Resolves with the server details, rejects if the server failed to respond or the query is cancelled */
+    wait(): Promise<GameServer>
+  }
+  /** A running query of the players on a single server, cancelled when the object is garbage collected */
+  export class ServerPlayersQuery {
+    /** Stops the query, `wait()` rejects */
+    cancel(): void
+    /** Resolves with the players, rejects if the server failed to respond or the query is cancelled */
+    wait(): Promise<Array<ServerPlayer>>
+  }
+  /** A running query of the rules of a single server, cancelled when the object is garbage collected */
+  export class ServerRulesQuery {
+    /** Stops the query, `wait()` rejects */
+    cancel(): void
+    /** Resolves with the rules, rejects if the server failed to respond or the query is cancelled */
+    wait(): Promise<Record<string, string>>
+  }
+}
 export namespace networking {
   export interface P2PPacket {
     data: Buffer
@@ -320,13 +409,54 @@ export namespace overlay {
   export function activateToStore(appId: number, flag: StoreFlag): void
 }
 export namespace steamp2p {
-  export function createAsyncClient(): SteamClientManager
+  /** `codec` 为空时使用 MessagePack,需要和服务器一致 */
+  export function createAsyncClient(codec?: ECodec | undefined | null): SteamClientManager
+  /**
+   * 索引中每条输入数据占用的 `u32` 个数
+   *
+   * 依次为 steam id 低 32 位、高 32 位、玩家位置、`types`、在 `buffer` 中的偏移、长度
+   */
+  export const FRAME_INDEX_STRIDE: number
+  /** 发送方式,覆盖消息默认的发送方式 */
+  export const enum ESendMode {
+    /** 不可靠,可能丢失或乱序,不能超过1200字节 */
+    KEsendUnreliable = 0,
+    /** 不可靠,连接没有准备好时直接丢弃 */
+    KEsendUnreliableNoDelay = 1,
+    /** 可靠,立即发送 */
+    KEsendReliable = 2,
+    /** 可靠,合并小消息后发送 */
+    KEsendReliableWithBuffering = 3
+  }
+  /** 发送通道,同一通道内的可靠消息按顺序到达 */
+  export const enum ELane {
+    KElaneControl = 0,
+    KElaneFrames = 1,
+    KElaneBroadcast = 2,
+    KElaneBlob = 3
+  }
+  /** 消息体的编码方式,服务器和客户端必须一致 */
+  export const enum ECodec {
+    KEcodecMessagePack = 0,
+    KEcodecBincode = 1,
+    /** 帧数据按固定字节布局读写,其他消息使用 MessagePack */
+    KEcodecRaw = 2
+  }
   export const enum EServerMode {
     EServerModeInvalid = 0,
     EServerModeNoAuthentication = 1,
     EServerModeAuthentication = 2,
     EServerModeAuthenticationAndSecure = 3
   }
+  /** 服务器的生命周期状态 */
+  export const enum EServerState {
+    KEserverStateIdle = 0,
+    KEserverStateInitializing = 1,
+    KEserverStateLoggingOn = 2,
+    KEserverStateListening = 3,
+    KEserverStateInGame = 4,
+    KEserverStateShuttingDown = 5
+  }
   export const enum EServerGameState {
     KEserverWaitingForPlayers = 0,
     KEserverActive = 1,
@@ -334,40 +464,162 @@ export namespace steamp2p {
     KEserverWinner = 3,
     KEserverExiting = 4
   }
-  export function createAsyncServer(): SteamServerManager
+  /** 目标帧已经下发(或超出提前范围)的操作如何处理 */
+  export const enum ELateInputPolicy {
+    /** 丢弃 */
+    KElateInputDrop = 0,
+    /** 放到最近一个可以接受的帧 */
+    KElateInputClamp = 1
+  }
+  /** `codec` 为空时使用 MessagePack,需要和客户端一致 */
+  export function createAsyncServer(codec?: ECodec | undefined | null): SteamServerManager
   export class SteamReceiveUpdate {
     buffer: Buffer
+    /** 每条输入数据 `FRAME_INDEX_STRIDE` 个数:steam id 低位、高位、玩家位置、`types`、偏移、长度 */
+    index: Uint32Array
     frameId: number
     count: number
   }
   export class GameStart {
     buffer: Buffer
+    /** 与 `SteamReceiveUpdate::index` 相同 */
+    index: Uint32Array
     count: number
   }
   export class BroadcastData {
     buffer: Buffer
     steamId: bigint
   }
+  export class BlobReceived {
+    steamId: bigint
+    transferId: number
+    meta: string
+    buffer: Buffer
+  }
+  export class BlobProgress {
+    steamId: bigint
+    transferId: number
+    transferred: number
+    total: number
+    /** 由我们发送 */
+    outgoing: boolean
+  }
+  export class BlobFailed {
+    steamId: bigint
+    transferId: number
+    error: string
+    outgoing: boolean
+  }
+  export class SteamResumeGame {
+    playerPosition: number
+    fromFrameId: number
+    toFrameId: number
+  }
+  export class SteamVersionMismatch {
+    /** 对方的steam id */
+    steamId: bigint
+    /** 对方的协议版本 */
+    protocolVersion: number
+    /** 对方的应用版本号 */
+    build: string
+    /** 对方的编码方式,见 `ECodec` */
+    codec: number
+  }
+  export class SteamPauseEvent {
+    /** 请求暂停(恢复)的玩家,为0时表示服务器 */
+    steamId: bigint
+    /** 暂停时为最后下发的一帧,恢复时下一帧为 `frame_id + 1` */
+    frameId: number
+  }
   export class SteamClientManager {
     setpStart(): void
+    /** 请求服务器暂停游戏,是否暂停由服务器决定,通过 `onSteamPaused` 通知 */
     setpPause(): void
+    /** 请求服务器恢复游戏,通过 `onSteamResumed` 通知 */
     setpResume(): void
     setpClose(): void
+    isPaused(): boolean
     initialize(): void
     onSteamConnectedSuccess(callback: (count:number) => void): void
     onSteamAllReadyToGo(callback: () => void): void
-    onSteamOnReceiveUpdate(callback: ({buffer,frameID,count}:{buffer:ArrayBuffer,frameID:number,count:number}) => void): void
-    gameStartDataCallback(callback: ({buffer,count}:{buffer:ArrayBuffer,count:number}) => void): void
+    onSteamOnReceiveUpdate(callback: ({buffer,index,frameID,count}:{buffer:ArrayBuffer,index:Uint32Array,frameID:number,count:number}) => void): void
+    gameStartDataCallback(callback: ({buffer,index,count}:{buffer:ArrayBuffer,index:Uint32Array,count:number}) => void): void
     broadcastCallback(callback: ({buffer,steamID}:{buffer:ArrayBuffer,steamID:bigint}) => void): void
+    onSteamResumeGame(callback: ({playerPosition,fromFrameId,toFrameId}:{playerPosition:number,fromFrameId:number,toFrameId:number}) => void): void
+    /** 和服务器的协议版本或应用版本号不一致,连接已经断开 */
+    onSteamVersionMismatch(callback: ({steamId,protocolVersion,build}:{steamId:bigint,protocolVersion:number,build:string}) => void): void
+    /** 收到完整并且校验通过的大数据 */
+    onBlobReceived(callback: ({steamId,transferId,meta,buffer}:{steamId:bigint,transferId:number,meta:string,buffer:Buffer}) => void): void
+    /** 大数据的发送或接收进度 */
+    onBlobProgress(callback: ({steamId,transferId,transferred,total,outgoing}:{steamId:bigint,transferId:number,transferred:number,total:number,outgoing:boolean}) => void): void
+    /** 大数据传输失败,例如校验不一致或者对方不在线 */
+    onBlobFailed(callback: ({steamId,transferId,error,outgoing}:{steamId:bigint,transferId:number,error:string,outgoing:boolean}) => void): void
+    /** 游戏暂停,`frameId` 为暂停前下发的最后一帧 */
+    onSteamPaused(callback: ({steamId,frameId}:{steamId:bigint,frameId:number}) => void): void
+    /** 游戏恢复,下一帧为 `frameId + 1` */
+    onSteamResumed(callback: ({steamId,frameId}:{steamId:bigint,frameId:number}) => void): void
+    /** 服务器长时间没有响应,连接已经断开,可以调用 `reconnect` 重新连接 */
+    onServerTimedOut(callback: () => void): void
+    /** 服务器检测到玩家之间同一帧的状态校验值不一致 */
+    onSteamDesync(callback: (frameId:number, checksums:Record<string,bigint>) => void): void
     setGameStartData(callback: () => void): void
     isConnectedToServer(): boolean
     loadReadyToGo(): void
+    /** 掉线后重新连接游戏服务器,服务器会补发错过的帧 */
+    reconnect(): void
+    /** 请求服务器重新发送 `from..=to` 之间的帧,通过 `onSteamOnReceiveUpdate` 返回,服务器每秒最多补发600帧 */
+    requestFrames(from: number, to: number): void
+    /** 上报某一帧模拟状态的校验值,用于检测不同步 */
+    sendChecksum(frameId: number, checksum: bigint): void
     runCallback(isConnectedToServer: boolean, policyResponseCallback: boolean): void
+    /** 设置服务器超过多久(毫秒)没有发送任何数据视为超时,为0时不检测 */
+    setServerTimeout(timeout: number): void
+    /** 设置向服务器发送心跳的间隔(毫秒) */
+    setHeartbeatInterval(interval: number): void
+    /** 设置要加入的大厅ID,握手时发给服务器用来找到对应的房间 */
     setLobbyId(lobbyId: bigint): void
     setSteamIdGameServer(lobbyId: bigint): void
-    sendFrameData(types: number, buffer: Buffer): void
+    /** 设置应用版本号,和服务器不一致时不能加入,为空时只比较协议版本 */
+    setBuildVersion(build: string): void
+    /**
+     * 以观战者身份连接服务器,需要在连接之前设置
+     *
+     * 观战者只接收帧数据,不能发送操作
+     */
+    setSpectator(spectator: boolean): void
+    isSpectator(): boolean
+    /**
+     * 设置输入延迟的帧数,`send_frame_data` 没有指定目标帧时
+     * 操作会放到收到的最新一帧之后的第 `frames + 1` 帧
+     */
+    setInputDelay(frames: number): void
+    /**
+     * 设置不可靠发送操作时附带之前的操作数量(最多8条,默认3条),服务器会过滤重复的操作
+     *
+     * 为0时没有指定 `mode` 的操作改为可靠发送
+     */
+    setInputRedundancy(count: number): void
+    /** 设置超过多少字节的消息压缩后发送,为0时不压缩 */
+    setCompressionThreshold(bytes: number): void
+    /** 收到的最新一帧 */
+    getFrameId(): number
+    /**
+     * 发送操作,`frame_id` 为操作生效的目标帧
+     *
+     * `mode` 和 `lane` 为空时使用帧数据通道,开启冗余时不可靠发送,否则可靠发送
+     */
+    sendFrameData(types: number, buffer: Buffer, frameId?: number | undefined | null, mode?: ESendMode | undefined | null, lane?: ELane | undefined | null): void
     setGameData(buffer: Buffer): void
-    broadcast(buffer: Buffer): void
+    /** 广播数据,`mode` 和 `lane` 只影响发往服务器的这一段 */
+    broadcast(buffer: Buffer, mode?: ESendMode | undefined | null, lane?: ELane | undefined | null): void
+    /**
+     * 向服务器或其他玩家发送大数据(地图、存档等),返回传输ID
+     *
+     * 在单独的通道上按带宽限制发送,不会阻塞帧数据,断线重连后从断点继续
+     */
+    sendBlob(steamId: bigint, buffer: Buffer, meta: string): number
+    /** 设置大数据每秒最多发送的字节数,为0时不限制 */
+    setBlobBandwidth(bytesPerSecond: number): void
   }
   export type JsSteamClient = SteamClient
     export class SteamClient {
@@ -403,11 +655,63 @@ export namespace steamp2p {
     export class GsPolicyResponseCallback {
     secure: number
   }
+  export class PlayerConnectionEvent {
+    steamId: bigint
+    /** 玩家在服务器中的槽位 */
+    playerPosition: number
+    /** 事件发生时服务器的逻辑帧 */
+    frameId: number
+  }
+  /** 玩家操作没有通过校验的原因 */
+  export const enum EInputViolation {
+    /** 严格模式下没有注册规则的操作类型 */
+    KEinputUnknownType = 0,
+    /** 数据超过限制大小 */
+    KEinputTooLarge = 1,
+    /** 提交过于频繁 */
+    KEinputRateLimited = 2,
+    /** 被校验函数拒绝 */
+    KEinputRejected = 3
+  }
+  export class InputViolationEvent {
+    steamId: bigint
+    types: number
+    violation: EInputViolation
+    /** 该玩家累计的违规次数 */
+    count: number
+  }
+  export class DecodeErrorEvent {
+    steamId: bigint
+    error: string
+    /** 该连接累计的错误次数 */
+    count: number
+  }
+  export class FrameAdvantage {
+    steamId: bigint
+    playerPosition: number
+    /** 最近一次操作的目标帧比服务器下一帧提前的帧数,负数表示晚到 */
+    advantage: number
+    /** 上报周期内的平均提前帧数 */
+    averageAdvantage: number
+    /** 上报周期内晚到的操作数量 */
+    lateInputs: number
+    /** 上报周期内被丢弃的操作数量 */
+    droppedInputs: number
+  }
   export type JsSteamServer = SteamServer
     export class SteamServer {
     constructor()
     runCallbacks(): void
     dispatchMessage(): void
+    /**
+     * 把服务器名称、规则、标签和玩家等信息推送到服务器浏览器
+     *
+     * 多个房间共用一个游戏服务器时,和其他房间推送的信息合并后显示:
+     * 人数相加,规则、标签和玩家取并集,同名的规则以后打开的房间为准,
+     * 名称、地图等其他信息使用最先打开的房间
+     *
+     * 没有设置最大玩家数量,或者名称、规则等字符串中含有空字符时抛出错误
+     */
     sendUpdatedServerDetailsToSteam(): void
     isConnectedToSteam(): boolean
     /** 设置应用ID */
@@ -422,14 +726,99 @@ export namespace steamp2p {
     setServerName(name: string): void
     /** 设置机器人的数量 */
     setBotPlayerCount(bot: number): void
+    /** 设置服务器浏览器中是否显示需要密码 */
+    setPasswordProtected(protected: boolean): void
+    /** 设置服务器规则,`value` 为空时删除 */
+    setServerRule(key: string, value?: string | undefined | null): void
+    /** 清空服务器规则 */
+    clearServerRules(): void
+    /** 设置游戏标签,服务器列表可以按标签过滤,合计最多128字节 */
+    setGameTags(tags: Array<string>): void
+    /** 设置游戏数据,服务器列表可以按游戏数据过滤,最多2048字节 */
+    setGameData(data: string): void
+    /** 设置服务器所在的地区 */
+    setRegion(region: string): void
+    /** 设置观战服务器的端口和名称,端口为0时不显示 */
+    setSpectatorServer(port: number, name: string): void
+    /** 设置服务器浏览器中显示的玩家名称和分数,玩家没有通过验证时抛出错误 */
+    setBrowserPlayer(steamId: bigint, name: string, score: number): void
+    /** 删除服务器浏览器中显示的玩家 */
+    removeBrowserPlayer(steamId: bigint): void
     /** 设置FPS */
     setInterval(interval: number): void
     /** 获取游戏服务器的steam 唯一ID */
     getServerSteamId(): bigint
-    /** 设置当前服务器的大厅唯一ID */
+    /** 设置当前服务器的大厅唯一ID,也是客户端加入时用来找到房间的ID */
     setLobbyId(lobbyId: bigint): void
     /**  获取大厅唯一ID */
     getLobbyId(): bigint
+    /** 设置断线重连的等待时间(毫秒),为0时玩家掉线后直接移除 */
+    setReconnectTimeout(timeout: number): void
+    /** 指定玩家的槽位,玩家加入时如果槽位空闲就使用这个槽位 */
+    setPlayerSlot(steamId: bigint, slot: number): void
+    /** 从大厅成员数据的 `key` 读取玩家指定的槽位,为空时不读取,不能占用其他玩家保留的槽位 */
+    setSlotLobbyKey(key?: string | undefined | null): void
+    /** 设置服务器缓存的历史帧数量,需要覆盖断线重连的等待时间 */
+    setFrameHistorySize(size: number): void
+    /** 可以同时观战的最大人数,观战者不占用玩家槽位 */
+    setMaxSpectators(max: number): void
+    /** 设置观战者延迟接收的帧数,超过服务器缓存的历史帧数量时取历史帧数量减一 */
+    setSpectatorDelay(frames: number): void
+    /** 获取当前观战人数 */
+    getSpectatorCount(): number
+    /** 设置目标帧已经下发的操作如何处理,默认丢弃 */
+    setLateInputPolicy(policy: ELateInputPolicy): void
+    /** 设置客户端超过多久(毫秒)没有发送任何数据视为超时,为0时不检测(默认不检测) */
+    setClientTimeout(timeout: number): void
+    /** 设置等待验证的客户端超过多久(毫秒)没有完成验证就断开,为0时不检测(默认不检测) */
+    setAuthTimeout(timeout: number): void
+    /** 设置向客户端发送心跳的间隔(毫秒) */
+    setHeartbeatInterval(interval: number): void
+    /** 设置应用版本号,和客户端不一致时拒绝加入,为空时只比较协议版本 */
+    setBuildVersion(build: string): void
+    /**
+     * 封禁玩家,`duration_secs` 为0时永久封禁,不会断开已有的连接
+     *
+     * 封禁列表由同一进程中的所有房间共用,被封禁的玩家在连接被接受之前就会被拒绝
+     */
+    ban(steamId: bigint, durationSecs: number): void
+    unban(steamId: bigint): void
+    /** 禁言玩家,该玩家的广播消息会被丢弃 */
+    mute(steamId: bigint): void
+    unmute(steamId: bigint): void
+    /** 设置某一类操作的最大字节数和每秒最多提交次数,为0时不限制 */
+    setInputRule(types: number, maxSize: number, maxPerSecond: number): void
+    removeInputRule(types: number): void
+    /** 为true时拒绝没有注册规则的操作类型 */
+    setInputRulesStrict(strict: boolean): void
+    /** 玩家操作违规达到多少次后踢出,为0时不踢出 */
+    setMaxInputViolations(count: number): void
+    /** 设置一个连接最多发送多少条无法解析的消息,超过后断开,为0时不断开 */
+    setMaxDecodeErrors(count: number): void
+    /** 设置超过多少字节的消息压缩后发送,为0时不压缩 */
+    setCompressionThreshold(bytes: number): void
+    /**
+     * 向玩家或观战者发送大数据(地图、存档等),返回传输ID
+     *
+     * 在单独的通道上按带宽限制发送,不会阻塞帧数据,断线重连后从断点继续
+     */
+    sendBlob(steamId: bigint, buffer: Buffer, meta: string): number
+    /** 设置大数据每秒最多发送的字节数,为0时不限制 */
+    setBlobBandwidth(bytesPerSecond: number): void
+    /** 设置客户端最多可以提前多少帧提交操作 */
+    setMaxInputLead(frames: number): void
+    /** 设置每隔多少帧上报一次操作提前量,为0时不上报 */
+    setFrameAdvantageInterval(frames: number): void
+    /**
+     * 设置每一帧附带之前的帧数(默认3帧),帧数据不可靠发送,丢包时由后面的帧补上
+     *
+     * 为0时不附带之前的帧,帧数据改为可靠发送
+     */
+    setFrameRedundancy(frames: number): void
+    /** 开始录像,记录开局数据、每一帧、广播以及玩家进出 */
+    startRecording(path: string): void
+    /** 停止录像并写入文件 */
+    stopRecording(): void
     /**
      * 初始化参数
      *
@@ -449,7 +838,20 @@ export namespace steamp2p {
      *
      */
     initialize(pchGameDir: string, unIp: number, usSteamPort: number, usGamePort: number, usQueryPort: number, serverMode: EServerMode, pchVersionString: string): void
+    /**
+     * 打开服务器,失败时错误信息以错误码开头,例如 `LobbyNotSet: lobby id is not set`
+     *
+     * 同一进程中的多个服务器共用一个游戏服务器和监听,按握手中的大厅ID分配连接
+     */
     open(): void
+    /** 获取服务器的生命周期状态 */
+    getState(): EServerState
+    /**
+     * 关闭服务器,断开所有客户端并退出 steam,之后可以重新 `open()`
+     *
+     * 返回 `false` 表示服务器没有打开
+     */
+    shutdown(reason?: string | undefined | null): boolean
     onServersConnected(callback: () => void): Handle
     onServersConnectFailure(callback: ({reason,stillRetrying}:{reason:number,stillRetrying:boolean}) => void): Handle
     onServersDisconnected(callback: ({reason}:{reason:number}) => void): Handle
@@ -462,12 +864,55 @@ export namespace steamp2p {
     onServersDisconnected(callback: ({reason}:{reason:number}) => void): void
     onAllReadyToGo(callback: (count:number) => void): void
     onGspolicyResponseCallback(callback: () => void): void
+    /** 玩家在游戏中掉线,进入重连等待 */
+    onPlayerDisconnected(callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void): void
+    /** 掉线玩家重连成功,错过的帧已经补发 */
+    onPlayerReconnected(callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void): void
+    /** 玩家离开服务器(包括重连超时) */
+    onPlayerLeft(callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void): void
+    /** 观战者验证成功并加入服务器 */
+    onSpectatorJoined(callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void): void
+    /** 观战者离开服务器 */
+    onSpectatorLeft(callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void): void
+    /** 定期上报每个玩家的操作提前量,用于调整输入延迟 */
+    onFrameAdvantage(callback: (players:{steamId:bigint,playerPosition:number,advantage:number,averageAdvantage:number,lateInputs:number,droppedInputs:number}[]) => void): void
+    /** 玩家之间同一帧的状态校验值不一致 */
+    onDesync(callback: (frameId:number, checksums:Record<string,bigint>) => void): void
+    /** 收到完整并且校验通过的大数据 */
+    onBlobReceived(callback: ({steamId,transferId,meta,buffer}:{steamId:bigint,transferId:number,meta:string,buffer:Buffer}) => void): void
+    /** 大数据的发送或接收进度,客户端之间转发的传输不会通知 */
+    onBlobProgress(callback: ({steamId,transferId,transferred,total,outgoing}:{steamId:bigint,transferId:number,transferred:number,total:number,outgoing:boolean}) => void): void
+    /** 大数据传输失败,例如校验不一致 */
+    onBlobFailed(callback: ({steamId,transferId,error,outgoing}:{steamId:bigint,transferId:number,error:string,outgoing:boolean}) => void): void
+    /** 收到无法解析的消息,`count` 为该连接累计的错误次数 */
+    onDecodeError(callback: ({steamId,error,count}:{steamId:bigint,error:string,count:number}) => void): void
+    /** 客户端的协议版本或应用版本号不一致,已经拒绝加入 */
+    onVersionMismatch(callback: ({steamId,protocolVersion,build}:{steamId:bigint,protocolVersion:number,build:string}) => void): void
+    /** 游戏暂停,`frameId` 为暂停前下发的最后一帧 */
+    onPaused(callback: ({steamId,frameId}:{steamId:bigint,frameId:number}) => void): void
+    /** 游戏恢复,下一帧为 `frameId + 1` */
+    onResumed(callback: ({steamId,frameId}:{steamId:bigint,frameId:number}) => void): void
+    /** 玩家操作没有通过校验 */
+    onInputViolation(callback: ({steamId,types,violation,count}:{steamId:bigint,types:number,violation:EInputViolation,count:number}) => void): void
+    /**
+     * 设置操作校验函数,在操作进入帧之前同步调用
+     *
+     * 返回 `false` 拒绝该操作,返回 `Buffer` 替换操作数据,其它返回值表示接受
+     */
+    setInputValidator(validator?: ((steamId:bigint, types:number, data:Buffer, frameId:number) => boolean | Buffer | undefined) | null): void
     receiveNetworkData(): void
     runCallbacks(dt: number): void
     setpStart(): void
+    /** 暂停游戏并通知所有客户端 */
     setpPause(): void
+    /** 恢复游戏并通知所有客户端,可以恢复任何玩家发起的暂停 */
     setpResume(): void
     setpClose(): void
+    isPaused(): boolean
+    /** 设置每个玩家每局可以暂停的次数,为0时客户端不能暂停 */
+    setPauseBudget(count: number): void
+    /** 设置玩家暂停超过多久(毫秒)自动恢复,为0时不自动恢复,发起暂停的玩家离开后总是恢复 */
+    setMaxPauseTime(time: number): void
     isConnectedToSteam(): boolean
     isPolicyResponseCallback(): boolean
     /** 设置应用ID */
@@ -482,14 +927,117 @@ export namespace steamp2p {
     setServerName(name: string): void
     /** 设置机器人的数量 */
     setBotPlayerCount(bot: number): void
+    /** 设置服务器浏览器中是否显示需要密码 */
+    setPasswordProtected(protected: boolean): void
+    /** 设置服务器规则,`value` 为空时删除 */
+    setServerRule(key: string, value?: string | undefined | null): void
+    /** 清空服务器规则 */
+    clearServerRules(): void
+    /** 设置游戏标签,服务器列表可以按标签过滤,合计最多128字节 */
+    setGameTags(tags: Array<string>): void
+    /** 设置游戏数据,服务器列表可以按游戏数据过滤,最多2048字节 */
+    setGameData(data: string): void
+    /** 设置服务器所在的地区 */
+    setRegion(region: string): void
+    /** 设置观战服务器的端口和名称,端口为0时不显示 */
+    setSpectatorServer(port: number, name: string): void
+    /** 设置服务器浏览器中显示的玩家名称和分数,玩家没有通过验证时抛出错误 */
+    setBrowserPlayer(steamId: bigint, name: string, score: number): void
+    /** 删除服务器浏览器中显示的玩家 */
+    removeBrowserPlayer(steamId: bigint): void
+    /**
+     * 把服务器名称、规则、标签和玩家等信息推送到服务器浏览器
+     *
+     * 多个房间共用一个游戏服务器时,和其他房间推送的信息合并后显示:
+     * 人数相加,规则、标签和玩家取并集,同名的规则以后打开的房间为准,
+     * 名称、地图等其他信息使用最先打开的房间
+     *
+     * 没有设置最大玩家数量,或者名称、规则等字符串中含有空字符时抛出错误
+     */
+    sendUpdatedServerDetailsToSteam(): void
     /** 设置FPS */
     setInterval(interval: number): void
     /** 获取游戏服务器的steam 唯一ID */
     getServerSteamId(): bigint
-    /** 设置当前服务器的大厅唯一ID */
+    /** 设置当前服务器的大厅唯一ID,也是客户端加入时用来找到房间的ID */
     setLobbyId(lobbyId: bigint): void
     /**  获取大厅唯一ID */
     getLobbyId(): bigint
+    /** 设置断线重连的等待时间(毫秒),为0时玩家掉线后直接移除 */
+    setReconnectTimeout(timeout: number): void
+    /** 指定玩家的槽位,玩家加入时如果槽位空闲就使用这个槽位 */
+    setPlayerSlot(steamId: bigint, slot: number): void
+    /** 从大厅成员数据的 `key` 读取玩家指定的槽位,为空时不读取,不能占用其他玩家保留的槽位 */
+    setSlotLobbyKey(key?: string | undefined | null): void
+    /** 设置服务器缓存的历史帧数量,需要覆盖断线重连的等待时间 */
+    setFrameHistorySize(size: number): void
+    /** 可以同时观战的最大人数,观战者不占用玩家槽位 */
+    setMaxSpectators(max: number): void
+    /** 设置观战者延迟接收的帧数,超过服务器缓存的历史帧数量时取历史帧数量减一 */
+    setSpectatorDelay(frames: number): void
+    /** 获取当前观战人数 */
+    getSpectatorCount(): number
+    /** 设置目标帧已经下发的操作如何处理,默认丢弃 */
+    setLateInputPolicy(policy: ELateInputPolicy): void
+    /** 设置客户端超过多久(毫秒)没有发送任何数据视为超时,为0时不检测(默认不检测) */
+    setClientTimeout(timeout: number): void
+    /** 设置等待验证的客户端超过多久(毫秒)没有完成验证就断开,为0时不检测(默认不检测) */
+    setAuthTimeout(timeout: number): void
+    /** 设置向客户端发送心跳的间隔(毫秒) */
+    setHeartbeatInterval(interval: number): void
+    /** 设置应用版本号,和客户端不一致时拒绝加入,为空时只比较协议版本 */
+    setBuildVersion(build: string): void
+    /**
+     * 踢出玩家(或观战者),不保留重连槽位
+     *
+     * 返回 `false` 表示该玩家不在服务器中
+     */
+    kick(steamId: bigint, reason?: string | undefined | null): boolean
+    /**
+     * 封禁玩家并踢出本房间,`duration_secs` 为0时永久封禁
+     *
+     * 封禁列表由同一进程中的所有房间共用,被封禁的玩家在连接被接受之前就会被拒绝,
+     * 但不会断开其他房间中已有的连接
+     */
+    ban(steamId: bigint, durationSecs: number): void
+    unban(steamId: bigint): void
+    isBanned(steamId: bigint): boolean
+    /** 禁言玩家,该玩家的广播消息会被丢弃 */
+    mute(steamId: bigint): void
+    unmute(steamId: bigint): void
+    /** 设置某一类操作的最大字节数和每秒最多提交次数,为0时不限制 */
+    setInputRule(types: number, maxSize: number, maxPerSecond: number): void
+    removeInputRule(types: number): void
+    /** 为true时拒绝没有注册规则的操作类型 */
+    setInputRulesStrict(strict: boolean): void
+    /** 玩家操作违规达到多少次后踢出,为0时不踢出 */
+    setMaxInputViolations(count: number): void
+    /** 设置一个连接最多发送多少条无法解析的消息,超过后断开,为0时不断开 */
+    setMaxDecodeErrors(count: number): void
+    /** 设置超过多少字节的消息压缩后发送,为0时不压缩 */
+    setCompressionThreshold(bytes: number): void
+    /**
+     * 向玩家或观战者发送大数据(地图、存档等),返回传输ID
+     *
+     * 在单独的通道上按带宽限制发送,不会阻塞帧数据,断线重连后从断点继续
+     */
+    sendBlob(steamId: bigint, buffer: Buffer, meta: string): number
+    /** 设置大数据每秒最多发送的字节数,为0时不限制 */
+    setBlobBandwidth(bytesPerSecond: number): void
+    /** 设置客户端最多可以提前多少帧提交操作 */
+    setMaxInputLead(frames: number): void
+    /** 设置每隔多少帧上报一次操作提前量,为0时不上报 */
+    setFrameAdvantageInterval(frames: number): void
+    /**
+     * 设置每一帧附带之前的帧数(默认3帧),帧数据不可靠发送,丢包时由后面的帧补上
+     *
+     * 为0时不附带之前的帧,帧数据改为可靠发送
+     */
+    setFrameRedundancy(frames: number): void
+    /** 开始录像,记录开局数据、每一帧、广播以及玩家进出 */
+    startRecording(path: string): void
+    /** 停止录像并写入文件 */
+    stopRecording(): void
     /**
      * 初始化参数
      *
@@ -509,7 +1057,51 @@ export namespace steamp2p {
      *
      */
     initialize(pchGameDir: string, unIp: number, usSteamPort: number, usGamePort: number, usQueryPort: number, serverMode: EServerMode, pchVersionString: string): void
+    /**
+     * 打开服务器,失败时错误信息以错误码开头,例如 `LobbyNotSet: lobby id is not set`
+     *
+     * 同一进程中的多个服务器共用一个游戏服务器和监听,按握手中的大厅ID分配连接
+     */
     open(): void
+    /** 获取服务器的生命周期状态 */
+    getState(): EServerState
+    onStateChanged(callback: (state:EServerState) => void): void
+    /**
+     * 关闭服务器,断开所有客户端并退出 steam,之后可以重新 `open()`
+     *
+     * 返回 `false` 表示服务器没有打开
+     */
+    shutdown(reason?: string | undefined | null): boolean
+    onShutdown(callback: (reason:string) => void): void
+  }
+  export class ReplayPlayerChanged {
+    steamId: bigint
+    playerPosition: number
+    frameId: number
+  }
+  /**
+   * 回放 `SteamServerManager.startRecording` 录制的文件
+   *
+   * 回调的参数与 `SteamClientManager` 相同,可以直接复用游戏中的帧处理逻辑
+   */
+  export class ReplayPlayer {
+    constructor(path: string)
+    onSteamOnReceiveUpdate(callback: ({buffer,index,frameID,count}:{buffer:ArrayBuffer,index:Uint32Array,frameID:number,count:number}) => void): void
+    gameStartDataCallback(callback: ({buffer,index,count}:{buffer:ArrayBuffer,index:Uint32Array,count:number}) => void): void
+    broadcastCallback(callback: ({buffer,steamID}:{buffer:ArrayBuffer,steamID:bigint}) => void): void
+    onPlayerJoined(callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void): void
+    onPlayerLeft(callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void): void
+    /**
+     * 播放到下一帧为止(包括之前的开局数据、广播和玩家进出事件)
+     *
+     * 返回 `false` 表示录像已经播放完毕
+     */
+    step(): boolean
+    /** 回到录像开头 */
+    rewind(): void
+    isFinished(): boolean
+    /** 录像中的总帧数 */
+    getFrameCount(): number
   }
 }
 export namespace stats {
@@ -0,0 +1,343 @@
+use napi_derive::napi;
+
+#[napi]
+pub mod matchmaking_servers {
+    use napi::bindgen_prelude::{BigInt, Error};
+    use napi::threadsafe_function::{
+        ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+    };
+    use napi::{Env, JsFunction, JsObject};
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+    use std::sync::{Arc, Mutex};
+    use steamworks::{ClientManager, GameServerItem, ServerListRequest, ServerQuery};
+    use tokio::sync::oneshot;
+
+    #[napi]
+    pub enum ServerListKind {
+        Internet,
+        Lan,
+        Friends,
+        Favorites,
+        History,
+    }
+
+    #[napi(object)]
+    pub struct ServerFilter {
+        /// e.g. `gametagsand`, `gamedataand`, `map`, `notfull`, `region`
+        pub key: String,
+        pub value: String,
+    }
+
+    #[napi(object)]
+    pub struct GameServer {
+        pub ip: u32,
+        pub connection_port: u16,
+        pub query_port: u16,
+        /// Current ping time in milliseconds
+        pub ping: i32,
+        pub had_successful_response: bool,
+        pub do_not_refresh: bool,
+        pub game_dir: String,
+        pub map: String,
+        pub game_description: String,
+        pub app_id: u32,
+        /// Number of players currently on the server, including bots
+        pub players: i32,
+        pub max_players: i32,
+        pub bot_players: i32,
+        pub password: bool,
+        pub secure: bool,
+        /// Unix time when this server was last played on (favorites and history only)
+        pub last_played: u32,
+        pub server_version: i32,
+        pub name: String,
+        pub game_tags: Vec<String>,
+        /// Use with `SteamClient.setSteamIdGameServer` to connect, 0 if the server is not logged on
+        pub steam_id: BigInt,
+    }
+
+    impl From<GameServerItem> for GameServer {
+        fn from(item: GameServerItem) -> Self {
+            GameServer {
+                ip: u32::from(item.addr),
+                connection_port: item.connection_port,
+                query_port: item.query_port,
+                ping: item.ping,
+                had_successful_response: item.had_successful_response,
+                do_not_refresh: item.do_not_refresh,
+                game_dir: item.game_dir,
+                map: item.map,
+                game_description: item.game_description,
+                app_id: item.app_id.0,
+                players: item.players,
+                max_players: item.max_players,
+                bot_players: item.bot_players,
+                password: item.password,
+                secure: item.secure,
+                last_played: item.last_played,
+                server_version: item.server_version,
+                name: item.server_name,
+                game_tags: item
+                    .game_tags
+                    .split(',')
+                    .filter(|tag| !tag.is_empty())
+                    .map(|tag| tag.to_string())
+                    .collect(),
+                steam_id: BigInt::from(item.steam_id.raw()),
+            }
+        }
+    }
+
+    #[napi(object)]
+    pub struct ServerPlayer {
+        pub name: String,
+        pub score: i32,
+        /// Seconds the player has been connected
+        pub time_played: f64,
+    }
+
+    /// A running server list request
+    #[napi]
+    pub struct ServerListQuery {
+        request: Option<ServerListRequest<ClientManager>>,
+        servers: Arc<Mutex<Vec<GameServer>>>,
+        done: Option<oneshot::Receiver<()>>,
+    }
+
+    #[napi]
+    impl ServerListQuery {
+        /// Stops the query, `wait()` resolves with the servers found so far
+        #[napi]
+        pub fn cancel(&mut self) {
+            self.request = None;
+        }
+
+        #[napi]
+        pub fn is_refreshing(&self) -> bool {
+            self.request
+                .as_ref()
+                .is_some_and(|request| request.is_refreshing())
+        }
+
+        /// Number of servers found so far
+        #[napi]
+        pub fn get_server_count(&self) -> u32 {
+            self.servers.lock().unwrap().len() as u32
+        }
+
+        /// Resolves with all the servers found once the list is complete or the query is cancelled
+        #[napi(ts_return_type = "Promise<Array<GameServer>>")]
+        pub fn wait(&mut self, env: Env) -> Result<JsObject, Error> {
+            let done = self.done.take();
+            let servers = self.servers.clone();
+
+            env.spawn_future(async move {
+                if let Some(done) = done {
+                    // a cancelled query drops the sender without sending
+                    let _ = done.await;
+                }
+                let servers = std::mem::take(&mut *servers.lock().unwrap());
+                Ok(servers)
+            })
+        }
+    }
+
+    /// Requests a list of game servers for the current app
+    ///
+    /// `onServer` is called for every server as it responds, `filters` are ignored for LAN lists
+    #[napi(
+        ts_args_type = "kind: ServerListKind, filters: Array<ServerFilter> | undefined | null, onServer: (server: GameServer) => void"
+    )]
+    pub fn request_server_list(
+        kind: ServerListKind,
+        filters: Option<Vec<ServerFilter>>,
+        on_server: JsFunction,
+    ) -> ServerListQuery {
+        let client = crate::client::get_client();
+
+        let threadsafe_handler: ThreadsafeFunction<GameServer, ErrorStrategy::Fatal> = on_server
+            .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+            .unwrap();
+
+        let filters = filters.unwrap_or_default();
+        let filters: Vec<(&str, &str)> = filters
+            .iter()
+            .map(|filter| (filter.key.as_str(), filter.value.as_str()))
+            .collect();
+
+        let servers = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = oneshot::channel();
+
+        let found = servers.clone();
+        let request = client.matchmaking_servers().request_server_list(
+            match kind {
+                ServerListKind::Internet => steamworks::ServerListKind::Internet,
+                ServerListKind::Lan => steamworks::ServerListKind::Lan,
+                ServerListKind::Friends => steamworks::ServerListKind::Friends,
+                ServerListKind::Favorites => steamworks::ServerListKind::Favorites,
+                ServerListKind::History => steamworks::ServerListKind::History,
+            },
+            client.utils().app_id(),
+            &filters,
+            move |item| {
+                found.lock().unwrap().push(GameServer::from(item.clone()));
+                threadsafe_handler
+                    .call(GameServer::from(item), ThreadsafeFunctionCallMode::Blocking);
+            },
+            move |_| {
+                let _ = tx.send(());
+            },
+        );
+
+        ServerListQuery {
+            request: Some(request),
+            servers,
+            done: Some(rx),
+        }
+    }
+
+    /// Waits for the result of a single server query
+    async fn query_result<T>(result: Option<oneshot::Receiver<Option<T>>>) -> Result<T, Error> {
+        let Some(result) = result else {
+            return Err(Error::from_reason("Query result already taken"));
+        };
+
+        match result.await {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => Err(Error::from_reason("Server failed to respond")),
+            // the callback is dropped without being called when the query is cancelled
+            Err(_) => Err(Error::from_reason("Query cancelled")),
+        }
+    }
+
+    /// A running query of a single server, cancelled when the object is garbage collected
+    #[napi]
+    pub struct ServerPingQuery {
+        query: Option<ServerQuery<ClientManager>>,
+        result: Option<oneshot::Receiver<Option<GameServerItem>>>,
+    }
+
+    #[napi]
+    impl ServerPingQuery {
+        /// Stops the query, `wait()` rejects
+        #[napi]
+        pub fn cancel(&mut self) {
+            self.query = None;
+        }
+
+        /// Resolves with the server details, rejects if the server failed to respond or the query is cancelled
+        #[napi(ts_return_type = "Promise<GameServer>")]
+        pub fn wait(&mut self, env: Env) -> Result<JsObject, Error> {
+            let result = self.result.take();
+            env.spawn_future(async move { query_result(result).await.map(GameServer::from) })
+        }
+    }
+
+    /// A running query of the players on a single server, cancelled when the object is garbage collected
+    #[napi]
+    pub struct ServerPlayersQuery {
+        query: Option<ServerQuery<ClientManager>>,
+        result: Option<oneshot::Receiver<Option<Vec<steamworks::ServerPlayer>>>>,
+    }
+
+    #[napi]
+    impl ServerPlayersQuery {
+        /// Stops the query, `wait()` rejects
+        #[napi]
+        pub fn cancel(&mut self) {
+            self.query = None;
+        }
+
+        /// Resolves with the players, rejects if the server failed to respond or the query is cancelled
+        #[napi(ts_return_type = "Promise<Array<ServerPlayer>>")]
+        pub fn wait(&mut self, env: Env) -> Result<JsObject, Error> {
+            let result = self.result.take();
+            env.spawn_future(async move {
+                let players = query_result(result).await?;
+                Ok(players
+                    .into_iter()
+                    .map(|player| ServerPlayer {
+                        name: player.name,
+                        score: player.score,
+                        time_played: f64::from(player.time_played),
+                    })
+                    .collect::<Vec<_>>())
+            })
+        }
+    }
+
+    /// A running query of the rules of a single server, cancelled when the object is garbage collected
+    #[napi]
+    pub struct ServerRulesQuery {
+        query: Option<ServerQuery<ClientManager>>,
+        result: Option<oneshot::Receiver<Option<HashMap<String, String>>>>,
+    }
+
+    #[napi]
+    impl ServerRulesQuery {
+        /// Stops the query, `wait()` rejects
+        #[napi]
+        pub fn cancel(&mut self) {
+            self.query = None;
+        }
+
+        /// Resolves with the rules, rejects if the server failed to respond or the query is cancelled
+        #[napi(ts_return_type = "Promise<Record<string, string>>")]
+        pub fn wait(&mut self, env: Env) -> Result<JsObject, Error> {
+            let result = self.result.take();
+            env.spawn_future(async move { query_result(result).await })
+        }
+    }
+
+    /// Queries the details of a single server by its ip and query port
+    #[napi]
+    pub fn ping_server(ip: u32, query_port: u16) -> ServerPingQuery {
+        let (tx, rx) = oneshot::channel();
+
+        let query = crate::client::get_client()
+            .matchmaking_servers()
+            .ping_server(Ipv4Addr::from(ip), query_port, move |item| {
+                let _ = tx.send(item);
+            });
+
+        ServerPingQuery {
+            query: Some(query),
+            result: Some(rx),
+        }
+    }
+
+    /// Queries the players on a single server by its ip and query port
+    #[napi]
+    pub fn get_server_players(ip: u32, query_port: u16) -> ServerPlayersQuery {
+        let (tx, rx) = oneshot::channel();
+
+        let query = crate::client::get_client()
+            .matchmaking_servers()
+            .player_details(Ipv4Addr::from(ip), query_port, move |players| {
+                let _ = tx.send(players);
+            });
+
+        ServerPlayersQuery {
+            query: Some(query),
+            result: Some(rx),
+        }
+    }
+
+    /// Queries the rules of a single server by its ip and query port
+    #[napi]
+    pub fn get_server_rules(ip: u32, query_port: u16) -> ServerRulesQuery {
+        let (tx, rx) = oneshot::channel();
+
+        let query = crate::client::get_client()
+            .matchmaking_servers()
+            .server_rules(Ipv4Addr::from(ip), query_port, move |rules| {
+                let _ = tx.send(rules);
+            });
+
+        ServerRulesQuery {
+            query: Some(query),
+            result: Some(rx),
+        }
+    }
+}
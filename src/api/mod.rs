@@ -6,6 +6,7 @@ pub mod cloud;
 pub mod input;
 pub mod localplayer;
 pub mod matchmaking;
+pub mod matchmaking_servers;
 pub mod networking;
 pub mod overlay;
 pub mod p2p;
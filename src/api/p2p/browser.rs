@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+
+/// 游戏标签的最大字节数
+pub const MAX_GAME_TAGS: usize = 128;
+/// 游戏数据的最大字节数
+pub const MAX_GAME_DATA: usize = 2048;
+
+/// 服务器浏览器中显示的玩家
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct BrowserPlayer {
+    pub name: String,
+    pub score: u32,
+}
+
+/// 服务器浏览器中显示的信息,由 `send_updated_server_details_to_steam` 一次全部推送
+#[derive(Default, Clone)]
+pub struct BrowserDetails {
+    pub password_protected: bool,
+    pub game_data: String,
+    pub region: String,
+    /// 为0时不显示观战服务器
+    pub spectator_port: u16,
+    pub spectator_name: String,
+    /// 规则查询返回的键值对
+    rules: BTreeMap<String, String>,
+    tags: Vec<String>,
+    /// steam id -> 玩家
+    players: BTreeMap<u64, BrowserPlayer>,
+}
+
+impl BrowserDetails {
+    /// 设置一条规则,`value` 为空时删除
+    pub fn set_rule(&mut self, key: String, value: Option<String>) {
+        match value {
+            Some(value) => {
+                self.rules.insert(key, value);
+            }
+            None => {
+                self.rules.remove(&key);
+            }
+        }
+    }
+
+    pub fn clear_rules(&mut self) {
+        self.rules.clear();
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.rules.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// 设置游戏标签,标签中不能有逗号,重复和空白的标签会被忽略
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags.clear();
+        for tag in tags {
+            let tag = tag.replace(',', "").trim().to_string();
+            if !tag.is_empty() && !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+    }
+
+    /// 逗号分隔的游戏标签,超出长度的标签被丢弃
+    pub fn game_tags(&self) -> String {
+        let mut joined = String::new();
+        for tag in self.tags.iter() {
+            let len = joined.len() + tag.len() + usize::from(!joined.is_empty());
+            if len > MAX_GAME_TAGS {
+                break;
+            }
+            if !joined.is_empty() {
+                joined.push(',');
+            }
+            joined.push_str(tag);
+        }
+        joined
+    }
+
+    /// 超出长度时在字符边界截断
+    pub fn game_data(&self) -> &str {
+        truncate(&self.game_data, MAX_GAME_DATA)
+    }
+
+    pub fn set_player(&mut self, steam_id: u64, name: String, score: u32) {
+        self.players.insert(steam_id, BrowserPlayer { name, score });
+    }
+
+    pub fn remove_player(&mut self, steam_id: u64) {
+        self.players.remove(&steam_id);
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = (u64, &BrowserPlayer)> {
+        self.players.iter().map(|(id, player)| (*id, player))
+    }
+}
+
+/// 一个房间推送到服务器浏览器的全部信息
+#[derive(Default, Clone)]
+pub struct ServerDetails {
+    pub server_name: String,
+    pub map_name: String,
+    pub max_players: u32,
+    pub bot_players: u32,
+    pub browser: BrowserDetails,
+}
+
+impl ServerDetails {
+    /// 合并共用一个游戏服务器的所有房间的信息,`rooms` 按房间打开的顺序
+    ///
+    /// 人数相加,规则、标签和玩家取并集,同名的规则以后打开的房间为准,
+    /// 名称、地图等其他信息使用最先打开的房间
+    pub fn merge<'a>(rooms: impl IntoIterator<Item = &'a ServerDetails>) -> ServerDetails {
+        let mut rooms = rooms.into_iter();
+        let Some(first) = rooms.next() else {
+            return ServerDetails::default();
+        };
+
+        let mut merged = first.clone();
+        for room in rooms {
+            merged.max_players += room.max_players;
+            merged.bot_players += room.bot_players;
+
+            let browser = &mut merged.browser;
+            browser.password_protected |= room.browser.password_protected;
+            browser.rules.extend(room.browser.rules.clone());
+            for tag in room.browser.tags.iter() {
+                if !browser.tags.contains(tag) {
+                    browser.tags.push(tag.clone());
+                }
+            }
+            browser.players.extend(room.browser.players.clone());
+        }
+
+        merged
+    }
+}
+
+fn truncate(value: &str, max: usize) -> &str {
+    if value.len() <= max {
+        return value;
+    }
+    let end = (0..=max)
+        .rev()
+        .find(|i| value.is_char_boundary(*i))
+        .unwrap();
+    &value[..end]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BrowserDetails, ServerDetails, MAX_GAME_DATA, MAX_GAME_TAGS};
+
+    #[test]
+    fn limits_tags_and_game_data() {
+        let mut details = BrowserDetails::default();
+        details.set_tags(vec![
+            "ranked".to_string(),
+            " 4v4 ".to_string(),
+            "ranked".to_string(),
+            "".to_string(),
+            "eu,west".to_string(),
+        ]);
+        assert_eq!(details.game_tags(), "ranked,4v4,euwest");
+
+        details.set_tags((0..100).map(|i| format!("tag{}", i)).collect());
+        let tags = details.game_tags();
+        assert!(tags.len() <= MAX_GAME_TAGS);
+        assert!(tags.ends_with(|c: char| c.is_ascii_digit()));
+
+        details.game_data = "数".repeat(MAX_GAME_DATA);
+        assert!(details.game_data().len() <= MAX_GAME_DATA);
+        assert!(details.game_data().chars().all(|c| c == '数'));
+    }
+
+    #[test]
+    fn merges_rooms() {
+        let mut first = ServerDetails {
+            server_name: "first".to_string(),
+            max_players: 4,
+            ..Default::default()
+        };
+        first
+            .browser
+            .set_rule("mode".to_string(), Some("ffa".to_string()));
+        first.browser.set_tags(vec!["ranked".to_string()]);
+        first.browser.set_player(1, "a".to_string(), 10);
+
+        let mut second = ServerDetails {
+            server_name: "second".to_string(),
+            max_players: 2,
+            bot_players: 1,
+            ..Default::default()
+        };
+        second.browser.password_protected = true;
+        second
+            .browser
+            .set_rule("mode".to_string(), Some("duel".to_string()));
+        second
+            .browser
+            .set_rule("map".to_string(), Some("arena".to_string()));
+        second
+            .browser
+            .set_tags(vec!["ranked".to_string(), "1v1".to_string()]);
+        second.browser.set_player(2, "b".to_string(), 20);
+
+        let merged = ServerDetails::merge([&first, &second]);
+        assert_eq!(merged.server_name, "first");
+        assert_eq!((merged.max_players, merged.bot_players), (6, 1));
+        assert!(merged.browser.password_protected);
+        assert_eq!(
+            merged.browser.rules().collect::<Vec<_>>(),
+            vec![("map", "arena"), ("mode", "duel")]
+        );
+        assert_eq!(merged.browser.game_tags(), "ranked,1v1");
+        assert_eq!(merged.browser.players().count(), 2);
+
+        assert_eq!(ServerDetails::merge([]).max_players, 0);
+    }
+}
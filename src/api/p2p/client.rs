@@ -3,17 +3,28 @@ use napi_derive::napi;
 #[napi]
 pub mod steamp2p {
     use crate::api::callback::callback::Handle;
+    use crate::api::p2p::codec::CodecKind;
+    use crate::api::p2p::desync::desync_js_args;
     use crate::api::p2p::message::*;
+    use crate::api::p2p::packet::{
+        decode_packet, encode_packet, ChunkAssembler, DecodeError, DEFAULT_COMPRESSION_THRESHOLD,
+        SUPPORTED_FEATURES,
+    };
+    use crate::api::p2p::redundancy::{
+        FrameSequencer, DEFAULT_INPUT_REDUNDANCY, MAX_INPUT_REDUNDANCY,
+    };
+    use crate::api::p2p::transfer::{BlobEvent, BlobMessage, BlobTransfers};
     use crate::client::now;
-    use bytebuffer::ByteBuffer;
-    use bytebuffer::Endian;
     use napi::bindgen_prelude::BigInt;
     use napi::bindgen_prelude::Buffer;
+    use napi::bindgen_prelude::Uint32Array;
     use napi::threadsafe_function::ErrorStrategy;
+    use napi::threadsafe_function::ThreadSafeCallContext;
     use napi::threadsafe_function::ThreadsafeFunction;
     use napi::threadsafe_function::ThreadsafeFunctionCallMode;
     use napi::JsFunction;
     use std::collections::HashSet;
+    use std::collections::VecDeque;
     use std::sync::mpsc::channel;
     use std::sync::mpsc::Receiver;
     use std::sync::mpsc::Sender;
@@ -50,6 +61,69 @@ pub mod steamp2p {
         KEclientConnectedAndAuthenticated, // 最后阶段，服务器已经授权给我们，我们实际上可以在上面玩了
     }
 
+    /// 发送方式,覆盖消息默认的发送方式
+    #[napi]
+    pub enum ESendMode {
+        /// 不可靠,可能丢失或乱序,不能超过1200字节
+        KEsendUnreliable,
+        /// 不可靠,连接没有准备好时直接丢弃
+        KEsendUnreliableNoDelay,
+        /// 可靠,立即发送
+        KEsendReliable,
+        /// 可靠,合并小消息后发送
+        KEsendReliableWithBuffering,
+    }
+
+    impl From<ESendMode> for SendFlags {
+        fn from(mode: ESendMode) -> Self {
+            match mode {
+                ESendMode::KEsendUnreliable => SendFlags::UNRELIABLE_NO_NAGLE,
+                ESendMode::KEsendUnreliableNoDelay => SendFlags::UNRELIABLE_NO_DELAY,
+                ESendMode::KEsendReliable => SendFlags::RELIABLE_NO_NAGLE,
+                ESendMode::KEsendReliableWithBuffering => SendFlags::RELIABLE,
+            }
+        }
+    }
+
+    /// 发送通道,同一通道内的可靠消息按顺序到达
+    #[napi]
+    pub enum ELane {
+        KElaneControl,
+        KElaneFrames,
+        KElaneBroadcast,
+        KElaneBlob,
+    }
+
+    impl From<ELane> for u16 {
+        fn from(lane: ELane) -> Self {
+            match lane {
+                ELane::KElaneControl => LANE_CONTROL,
+                ELane::KElaneFrames => LANE_FRAMES,
+                ELane::KElaneBroadcast => LANE_BROADCAST,
+                ELane::KElaneBlob => LANE_BLOB,
+            }
+        }
+    }
+
+    /// 消息体的编码方式,服务器和客户端必须一致
+    #[napi]
+    pub enum ECodec {
+        KEcodecMessagePack,
+        KEcodecBincode,
+        /// 帧数据按固定字节布局读写,其他消息使用 MessagePack
+        KEcodecRaw,
+    }
+
+    impl From<ECodec> for CodecKind {
+        fn from(codec: ECodec) -> Self {
+            match codec {
+                ECodec::KEcodecMessagePack => CodecKind::MessagePack,
+                ECodec::KEcodecBincode => CodecKind::Bincode,
+                ECodec::KEcodecRaw => CodecKind::Raw,
+            }
+        }
+    }
+
     enum SteamClientEvent {
         LobbyGameCreated(LobbyGameCreated),
     }
@@ -57,6 +131,8 @@ pub mod steamp2p {
     #[napi]
     pub struct SteamReceiveUpdate {
         pub buffer: Buffer,
+        /// 每条输入数据 `FRAME_INDEX_STRIDE` 个数:steam id 低位、高位、玩家位置、`types`、偏移、长度
+        pub index: Uint32Array,
         pub frame_id: u32,
         pub count: u32,
     }
@@ -64,6 +140,8 @@ pub mod steamp2p {
     #[napi]
     pub struct GameStart {
         pub buffer: Buffer,
+        /// 与 `SteamReceiveUpdate::index` 相同
+        pub index: Uint32Array,
         pub count: u32,
     }
 
@@ -73,6 +151,206 @@ pub mod steamp2p {
         pub steam_id: BigInt,
     }
 
+    #[napi]
+    pub struct BlobReceived {
+        pub steam_id: BigInt,
+        pub transfer_id: u32,
+        pub meta: String,
+        pub buffer: Buffer,
+    }
+
+    #[napi]
+    pub struct BlobProgress {
+        pub steam_id: BigInt,
+        pub transfer_id: u32,
+        pub transferred: u32,
+        pub total: u32,
+        /// 由我们发送
+        pub outgoing: bool,
+    }
+
+    #[napi]
+    pub struct BlobFailed {
+        pub steam_id: BigInt,
+        pub transfer_id: u32,
+        pub error: String,
+        pub outgoing: bool,
+    }
+
+    /// 大数据传输的回调,客户端和服务器共用
+    #[derive(Default)]
+    pub struct BlobCallbacks {
+        pub received: Option<ThreadsafeFunction<BlobReceived, ErrorStrategy::Fatal>>,
+        pub progress: Option<ThreadsafeFunction<BlobProgress, ErrorStrategy::Fatal>>,
+        pub failed: Option<ThreadsafeFunction<BlobFailed, ErrorStrategy::Fatal>>,
+    }
+
+    impl BlobCallbacks {
+        pub fn notify(&self, events: Vec<BlobEvent>) {
+            for event in events {
+                match event {
+                    BlobEvent::Received {
+                        steam_id,
+                        transfer_id,
+                        meta,
+                        data,
+                    } => {
+                        if let Some(fun) = self.received.as_ref() {
+                            let event = BlobReceived {
+                                steam_id: BigInt::from(steam_id),
+                                transfer_id,
+                                meta,
+                                buffer: data.into(),
+                            };
+                            fun.call(event, ThreadsafeFunctionCallMode::Blocking);
+                        }
+                    }
+                    BlobEvent::Progress {
+                        steam_id,
+                        transfer_id,
+                        transferred,
+                        total,
+                        outgoing,
+                    } => {
+                        if let Some(fun) = self.progress.as_ref() {
+                            let event = BlobProgress {
+                                steam_id: BigInt::from(steam_id),
+                                transfer_id,
+                                transferred,
+                                total,
+                                outgoing,
+                            };
+                            fun.call(event, ThreadsafeFunctionCallMode::Blocking);
+                        }
+                    }
+                    BlobEvent::Failed {
+                        steam_id,
+                        transfer_id,
+                        error,
+                        outgoing,
+                    } => {
+                        if let Some(fun) = self.failed.as_ref() {
+                            let event = BlobFailed {
+                                steam_id: BigInt::from(steam_id),
+                                transfer_id,
+                                error,
+                                outgoing,
+                            };
+                            fun.call(event, ThreadsafeFunctionCallMode::Blocking);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 索引中每条输入数据占用的 `u32` 个数
+    ///
+    /// 依次为 steam id 低 32 位、高 32 位、玩家位置、`types`、在 `buffer` 中的偏移、长度
+    #[napi]
+    pub const FRAME_INDEX_STRIDE: u32 = 6;
+
+    /// 生成每条输入数据在合并后的缓冲区中的索引,同时返回缓冲区总长度
+    fn frame_index(game_data: &[MsgServerFrameData]) -> (Vec<u32>, usize) {
+        let mut index = Vec::with_capacity(game_data.len() * FRAME_INDEX_STRIDE as usize);
+        let mut offset = 0;
+
+        for frame in game_data.iter() {
+            index.extend_from_slice(&[
+                frame.local_steam_id as u32,
+                (frame.local_steam_id >> 32) as u32,
+                frame.player_position,
+                frame.types,
+                offset as u32,
+                frame.data.len() as u32,
+            ]);
+            offset += frame.data.len();
+        }
+
+        (index, offset)
+    }
+
+    /// 把所有输入数据按顺序直接拼进同一个缓冲区,并附带索引
+    ///
+    /// 只有一条输入数据时直接移交内存,不再复制
+    fn pack_frame_data(mut game_data: Vec<MsgServerFrameData>) -> (Buffer, Uint32Array, u32) {
+        let (index, size) = frame_index(&game_data);
+        let count = game_data.len() as u32;
+
+        let buffer = if game_data.len() == 1 {
+            game_data.pop().unwrap().data
+        } else {
+            let mut buffer = Vec::with_capacity(size);
+            game_data
+                .iter()
+                .for_each(|frame| buffer.extend_from_slice(&frame.data));
+            buffer
+        };
+
+        (Buffer::from(buffer), Uint32Array::new(index), count)
+    }
+
+    impl From<MsgServerFramesData> for SteamReceiveUpdate {
+        fn from(data: MsgServerFramesData) -> Self {
+            let (buffer, index, count) = pack_frame_data(data.game_data);
+
+            SteamReceiveUpdate {
+                buffer,
+                index,
+                frame_id: data.frame_id,
+                count,
+            }
+        }
+    }
+
+    impl From<MsgServerGameStart> for GameStart {
+        fn from(data: MsgServerGameStart) -> Self {
+            let (buffer, index, count) = pack_frame_data(data.game_data);
+
+            GameStart {
+                buffer,
+                index,
+                count,
+            }
+        }
+    }
+
+    impl From<&MsgServerDataBroadcast> for BroadcastData {
+        fn from(data: &MsgServerDataBroadcast) -> Self {
+            BroadcastData {
+                buffer: Buffer::from(data.data.as_slice()),
+                steam_id: BigInt::from(data.local_steam_id),
+            }
+        }
+    }
+
+    #[napi]
+    pub struct SteamResumeGame {
+        pub player_position: u32,
+        pub from_frame_id: u32,
+        pub to_frame_id: u32,
+    }
+
+    #[napi]
+    pub struct SteamVersionMismatch {
+        /// 对方的steam id
+        pub steam_id: BigInt,
+        /// 对方的协议版本
+        pub protocol_version: u32,
+        /// 对方的应用版本号
+        pub build: String,
+        /// 对方的编码方式,见 `ECodec`
+        pub codec: u32,
+    }
+
+    #[napi]
+    pub struct SteamPauseEvent {
+        /// 请求暂停(恢复)的玩家,为0时表示服务器
+        pub steam_id: BigInt,
+        /// 暂停时为最后下发的一帧,恢复时下一帧为 `frame_id + 1`
+        pub frame_id: u32,
+    }
+
     #[napi]
     pub struct SteamClientManager {
         rx: Receiver<SteamClientEvent>,
@@ -107,72 +385,15 @@ pub mod steamp2p {
                             continue;
                         }
 
-                        if data.len() < 4 {
-                            println!("got garbage on client socket, too short");
-
-                            drop(message); // drop call SteamAPI_SteamNetworkingMessage_t_Release
-                            continue;
-                        }
-
-                        let header: EMessage = data[0..4].to_vec().into();
-                        let body = &data[4..];
-
-                        if header == EMessage::Error {
+                        let result = client.chunks.push(0, data).and_then(|packet| match packet {
+                            Some(packet) => decode_packet(&packet)
+                                .and_then(|(header, body)| client.on_message(header, &body)),
+                            // 等待剩下的分片
+                            None => Ok(()),
+                        });
+                        if let Err(_err) = result {
                             #[cfg(feature = "dev")]
-                            dbg!("SteamClientManager::receive EMessage::Error");
-
-                            drop(message); // drop call SteamAPI_SteamNetworkingMessage_t_Release
-                            continue;
-                        }
-
-                        match header {
-                            EMessage::KEmsgServerSendInfo => {
-                                if let Ok(msg) = rmps::from_slice::<MsgServerSendInfo>(body) {
-                                    client.on_receive_server_info(msg);
-                                }
-                            }
-                            EMessage::KEmsgServerFailAuthentication => {
-                                if let Ok(_) = rmps::from_slice::<MsgServerFailAuthentication>(body)
-                                {
-                                    client.on_receive_server_authentication_response(false, 0);
-                                }
-                            }
-                            EMessage::KEmsgServerPassAuthentication => {
-                                if let Ok(msg) =
-                                    rmps::from_slice::<MsgServerPassAuthentication>(body)
-                                {
-                                    client.on_receive_server_authentication_response(
-                                        true,
-                                        msg.player_position,
-                                    );
-                                }
-                            }
-                            EMessage::KEmsgServerAllReadyToGo => {
-                                if let Some(fun) = client.steam_all_ready_to_go.as_ref() {
-                                    fun.call((), ThreadsafeFunctionCallMode::Blocking);
-                                }
-                            }
-                            EMessage::KEmsgServerFramesData => {
-                                if let Ok(msg) = rmps::from_slice::<MsgServerFramesData>(body) {
-                                    client.on_receive_update(msg);
-                                }
-                            }
-                            EMessage::KEmsgServerGameStart => {
-                                if let Ok(msg) = rmps::from_slice::<MsgServerGameStart>(body) {
-                                    client.on_game_start(msg);
-                                }
-                            }
-                            EMessage::KEmsgServerSetGameStartDataComplete => {
-                                if let Some(fun) = client.set_game_start_data.as_ref() {
-                                    fun.call((), ThreadsafeFunctionCallMode::Blocking);
-                                }
-                            }
-                            EMessage::KEmsgServerBroadcast => {
-                                if let Ok(msg) = rmps::from_slice::<MsgServerDataBroadcast>(body) {
-                                    client.on_broadcast_update(msg);
-                                }
-                            }
-                            _ => panic!("error message,{:?}", header),
+                            dbg!("dropping bad message from server", _err.to_string());
                         }
 
                         drop(message); // drop call SteamAPI_SteamNetworkingMessage_t_Release
@@ -210,11 +431,22 @@ pub mod steamp2p {
         #[napi]
         pub fn setp_start(&self) {}
 
+        /// 请求服务器暂停游戏,是否暂停由服务器决定,通过 `onSteamPaused` 通知
+        #[napi]
+        pub fn setp_pause(&self) {
+            self.raw.send_message(MsgClientRequestPause);
+        }
+
+        /// 请求服务器恢复游戏,通过 `onSteamResumed` 通知
         #[napi]
-        pub fn setp_pause(&self) {}
+        pub fn setp_resume(&self) {
+            self.raw.send_message(MsgClientRequestResume);
+        }
 
         #[napi]
-        pub fn setp_resume(&self) {}
+        pub fn is_paused(&self) -> bool {
+            self.raw.paused
+        }
 
         #[napi]
         pub fn setp_close(&self) {}
@@ -247,7 +479,7 @@ pub mod steamp2p {
         }
 
         #[napi(
-            ts_args_type = "callback: ({buffer,frameID,count}:{buffer:ArrayBuffer,frameID:number,count:number}) => void"
+            ts_args_type = "callback: ({buffer,index,frameID,count}:{buffer:ArrayBuffer,index:Uint32Array,frameID:number,count:number}) => void"
         )]
         pub fn on_steam_on_receive_update(&mut self, handler: JsFunction) {
             let threadsafe_handler: ThreadsafeFunction<SteamReceiveUpdate, ErrorStrategy::Fatal> =
@@ -261,7 +493,7 @@ pub mod steamp2p {
         }
 
         #[napi(
-            ts_args_type = "callback: ({buffer,count}:{buffer:ArrayBuffer,count:number}) => void"
+            ts_args_type = "callback: ({buffer,index,count}:{buffer:ArrayBuffer,index:Uint32Array,count:number}) => void"
         )]
         pub fn game_start_data_callback(&mut self, handler: JsFunction) {
             let threadsafe_handler: ThreadsafeFunction<GameStart, ErrorStrategy::Fatal> = handler
@@ -287,6 +519,138 @@ pub mod steamp2p {
             dbg!("broadcast_callback");
         }
 
+        #[napi(
+            ts_args_type = "callback: ({playerPosition,fromFrameId,toFrameId}:{playerPosition:number,fromFrameId:number,toFrameId:number}) => void"
+        )]
+        pub fn on_steam_resume_game(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<SteamResumeGame, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.raw.resume_game_cb = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_steam_resume_game");
+        }
+
+        /// 和服务器的协议版本或应用版本号不一致,连接已经断开
+        #[napi(
+            ts_args_type = "callback: ({steamId,protocolVersion,build}:{steamId:bigint,protocolVersion:number,build:string}) => void"
+        )]
+        pub fn on_steam_version_mismatch(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<SteamVersionMismatch, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.raw.version_mismatch_cb = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_steam_version_mismatch");
+        }
+
+        /// 收到完整并且校验通过的大数据
+        #[napi(
+            ts_args_type = "callback: ({steamId,transferId,meta,buffer}:{steamId:bigint,transferId:number,meta:string,buffer:Buffer}) => void"
+        )]
+        pub fn on_blob_received(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<BlobReceived, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.raw.blob_callbacks.received = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_blob_received");
+        }
+
+        /// 大数据的发送或接收进度
+        #[napi(
+            ts_args_type = "callback: ({steamId,transferId,transferred,total,outgoing}:{steamId:bigint,transferId:number,transferred:number,total:number,outgoing:boolean}) => void"
+        )]
+        pub fn on_blob_progress(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<BlobProgress, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.raw.blob_callbacks.progress = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_blob_progress");
+        }
+
+        /// 大数据传输失败,例如校验不一致或者对方不在线
+        #[napi(
+            ts_args_type = "callback: ({steamId,transferId,error,outgoing}:{steamId:bigint,transferId:number,error:string,outgoing:boolean}) => void"
+        )]
+        pub fn on_blob_failed(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<BlobFailed, ErrorStrategy::Fatal> = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
+            self.raw.blob_callbacks.failed = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_blob_failed");
+        }
+
+        /// 游戏暂停,`frameId` 为暂停前下发的最后一帧
+        #[napi(
+            ts_args_type = "callback: ({steamId,frameId}:{steamId:bigint,frameId:number}) => void"
+        )]
+        pub fn on_steam_paused(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<SteamPauseEvent, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.raw.paused_cb = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_steam_paused");
+        }
+
+        /// 游戏恢复,下一帧为 `frameId + 1`
+        #[napi(
+            ts_args_type = "callback: ({steamId,frameId}:{steamId:bigint,frameId:number}) => void"
+        )]
+        pub fn on_steam_resumed(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<SteamPauseEvent, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.raw.resumed_cb = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_steam_resumed");
+        }
+
+        /// 服务器长时间没有响应,连接已经断开,可以调用 `reconnect` 重新连接
+        #[napi(ts_args_type = "callback: () => void")]
+        pub fn on_server_timed_out(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<(), ErrorStrategy::Fatal> = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
+            self.raw.server_timed_out = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_server_timed_out");
+        }
+
+        /// 服务器检测到玩家之间同一帧的状态校验值不一致
+        #[napi(
+            ts_args_type = "callback: (frameId:number, checksums:Record<string,bigint>) => void"
+        )]
+        pub fn on_steam_desync(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<MsgServerDesync, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<MsgServerDesync>| {
+                        desync_js_args(&ctx.env, &ctx.value)
+                    })
+                    .unwrap();
+            self.raw.desync_cb = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_steam_desync");
+        }
+
         #[napi(ts_args_type = "callback: () => void")]
         pub fn set_game_start_data(&mut self, handler: JsFunction) {
             let threadsafe_handler: ThreadsafeFunction<(), ErrorStrategy::Fatal> = handler
@@ -308,6 +672,35 @@ pub mod steamp2p {
             self.raw.send_message(MsgClientLoadComplete);
         }
 
+        /// 掉线后重新连接游戏服务器,服务器会补发错过的帧
+        #[napi]
+        pub fn reconnect(&mut self) {
+            self.raw.reconnect_to_server();
+        }
+
+        /// 请求服务器重新发送 `from..=to` 之间的帧,通过 `onSteamOnReceiveUpdate` 返回,服务器每秒最多补发600帧
+        #[napi]
+        pub fn request_frames(&self, from: u32, to: u32) {
+            if from > to {
+                return;
+            }
+
+            self.raw.send_message(MsgClientRequestFrames { from, to });
+        }
+
+        /// 上报某一帧模拟状态的校验值,用于检测不同步
+        #[napi]
+        pub fn send_checksum(&self, frame_id: u32, checksum: BigInt) {
+            if self.raw.spectator {
+                return;
+            }
+
+            self.raw.send_message(MsgClientChecksum {
+                frame_id,
+                checksum: checksum.get_u64().1,
+            });
+        }
+
         #[napi]
         pub fn run_callback(
             &mut self,
@@ -345,8 +738,24 @@ pub mod steamp2p {
             }
 
             self.receive();
+            self.raw.check_server_timeout();
+            self.raw.send_heartbeat();
+            self.raw.send_blobs();
+        }
+
+        /// 设置服务器超过多久(毫秒)没有发送任何数据视为超时,为0时不检测
+        #[napi]
+        pub fn set_server_timeout(&mut self, timeout: u32) {
+            self.raw.server_timeout = timeout as i64;
         }
 
+        /// 设置向服务器发送心跳的间隔(毫秒)
+        #[napi]
+        pub fn set_heartbeat_interval(&mut self, interval: u32) {
+            self.raw.heartbeat_interval = interval as i64;
+        }
+
+        /// 设置要加入的大厅ID,握手时发给服务器用来找到对应的房间
         #[napi]
         pub fn set_lobby_id(&mut self, lobby_id: BigInt) {
             self.raw.lobby_id = Some(SteamId::from_raw(lobby_id.get_u64().1));
@@ -363,9 +772,66 @@ pub mod steamp2p {
             dbg!("set_steam_id_game_server", self.raw.steam_id_game_server);
         }
 
+        /// 设置应用版本号,和服务器不一致时不能加入,为空时只比较协议版本
         #[napi]
-        pub fn send_frame_data(&self, types: u32, buffer: Buffer) {
-            if buffer.len() == 0 {
+        pub fn set_build_version(&mut self, build: String) {
+            self.raw.build = build;
+        }
+
+        /// 以观战者身份连接服务器,需要在连接之前设置
+        ///
+        /// 观战者只接收帧数据,不能发送操作
+        #[napi]
+        pub fn set_spectator(&mut self, spectator: bool) {
+            self.raw.spectator = spectator;
+        }
+
+        #[napi]
+        pub fn is_spectator(&self) -> bool {
+            self.raw.spectator
+        }
+
+        /// 设置输入延迟的帧数,`send_frame_data` 没有指定目标帧时
+        /// 操作会放到收到的最新一帧之后的第 `frames + 1` 帧
+        #[napi]
+        pub fn set_input_delay(&mut self, frames: u32) {
+            self.raw.input_delay = frames;
+        }
+
+        /// 设置不可靠发送操作时附带之前的操作数量(最多8条,默认3条),服务器会过滤重复的操作
+        ///
+        /// 为0时没有指定 `mode` 的操作改为可靠发送
+        #[napi]
+        pub fn set_input_redundancy(&mut self, count: u32) {
+            self.raw.input_redundancy = count.min(MAX_INPUT_REDUNDANCY as u32);
+            self.raw.recent_inputs.clear();
+        }
+
+        /// 设置超过多少字节的消息压缩后发送,为0时不压缩
+        #[napi]
+        pub fn set_compression_threshold(&mut self, bytes: u32) {
+            self.raw.compression_threshold = bytes;
+        }
+
+        /// 收到的最新一帧
+        #[napi]
+        pub fn get_frame_id(&self) -> u32 {
+            self.raw.last_frame_id
+        }
+
+        /// 发送操作,`frame_id` 为操作生效的目标帧
+        ///
+        /// `mode` 和 `lane` 为空时使用帧数据通道,开启冗余时不可靠发送,否则可靠发送
+        #[napi]
+        pub fn send_frame_data(
+            &mut self,
+            types: u32,
+            buffer: Buffer,
+            frame_id: Option<u32>,
+            mode: Option<ESendMode>,
+            lane: Option<ELane>,
+        ) {
+            if buffer.is_empty() || self.raw.spectator {
                 return;
             }
 
@@ -373,41 +839,87 @@ pub mod steamp2p {
                 return;
             }
 
-            self.raw.send_message(MsgClientFrameData {
-                data: buffer.to_vec(),
-                types,
+            let frame_id = frame_id.unwrap_or_else(|| {
+                if self.raw.input_delay > 0 {
+                    self.raw.last_frame_id + 1 + self.raw.input_delay
+                } else {
+                    0
+                }
             });
+
+            self.raw.send_input(
+                MsgClientFrameData {
+                    data: buffer.to_vec(),
+                    types,
+                    frame_id,
+                    seq: 0,
+                    redundant: vec![],
+                },
+                mode.map_or_else(|| self.raw.input_send_flags(), SendFlags::from),
+                lane.map_or(MsgClientFrameData::LANE, u16::from),
+            );
         }
 
         #[napi]
         pub fn set_game_data(&self, buffer: Buffer) {
-            if buffer.len() == 0 {
+            if buffer.is_empty() || self.raw.spectator {
                 return;
             }
 
-            self.raw.send_message(MsgClientFrameData {
-                data: buffer.to_vec(),
-                types: 0,
-            });
+            // 和 `load_ready_to_go` 使用同一个通道,保证开局数据先到达
+            self.raw.send_message_with(
+                MsgClientFrameData {
+                    data: buffer.to_vec(),
+                    types: 0,
+                    frame_id: 0,
+                    seq: 0,
+                    redundant: vec![],
+                },
+                SendFlags::RELIABLE_NO_NAGLE,
+                LANE_CONTROL,
+            );
         }
 
+        /// 广播数据,`mode` 和 `lane` 只影响发往服务器的这一段
         #[napi]
-        pub fn broadcast(&self, buffer: Buffer) {
+        pub fn broadcast(&self, buffer: Buffer, mode: Option<ESendMode>, lane: Option<ELane>) {
             if buffer.len() == 0 {
                 return;
             }
 
-            self.raw.send_message(MsgClientDataBroadcast {
-                data: buffer.to_vec(),
-                types: 0,
-                local_steam_id: 0,
-            });
+            self.raw.send_message_with(
+                MsgClientDataBroadcast {
+                    data: buffer.to_vec(),
+                    types: 0,
+                    local_steam_id: 0,
+                },
+                mode.map_or(MsgClientDataBroadcast::SEND_FLAGS, SendFlags::from),
+                lane.map_or(MsgClientDataBroadcast::LANE, u16::from),
+            );
+        }
+
+        /// 向服务器或其他玩家发送大数据(地图、存档等),返回传输ID
+        ///
+        /// 在单独的通道上按带宽限制发送,不会阻塞帧数据,断线重连后从断点继续
+        #[napi]
+        pub fn send_blob(&mut self, steam_id: BigInt, buffer: Buffer, meta: String) -> u32 {
+            self.raw
+                .blobs
+                .send(steam_id.get_u64().1, buffer.to_vec(), meta)
+        }
+
+        /// 设置大数据每秒最多发送的字节数,为0时不限制
+        #[napi]
+        pub fn set_blob_bandwidth(&mut self, bytes_per_second: u32) {
+            self.raw.blobs.set_bandwidth(bytes_per_second);
         }
     }
 
+    /// `codec` 为空时使用 MessagePack,需要和服务器一致
     #[napi]
-    pub fn create_async_client() -> SteamClientManager {
+    pub fn create_async_client(codec: Option<ECodec>) -> SteamClientManager {
         let mut client = JsSteamClient::new();
+        client.codec = codec.map_or(CodecKind::default(), CodecKind::from);
         let (tx, rx) = channel();
 
         client.send = Some(tx);
@@ -429,6 +941,35 @@ pub mod steamp2p {
 
         // 玩家在服务器中的槽位
         player_index: u32,
+        /// 以观战者身份加入
+        spectator: bool,
+        /// 应用版本号,和服务器不一致时不能加入
+        build: String,
+        /// 消息体的编码方式,和服务器不一致时不能加入
+        codec: CodecKind,
+        /// 收到的最新一帧
+        last_frame_id: u32,
+        /// 服务器已经暂停游戏
+        paused: bool,
+        /// 输入延迟的帧数,为0时操作放到服务器的下一帧
+        input_delay: u32,
+        /// 最后一条操作的序号
+        input_seq: u32,
+        /// 不可靠发送操作时附带之前的操作数量
+        input_redundancy: u32,
+        /// 最近发送的操作
+        recent_inputs: VecDeque<MsgClientFrameData>,
+        /// 冗余打包的帧去重排序
+        frames: FrameSequencer,
+        /// 大数据传输
+        blobs: BlobTransfers,
+        blob_callbacks: BlobCallbacks,
+        /// 服务器支持的可选功能
+        server_features: u32,
+        /// 超过多少字节的消息压缩后发送,为0时不压缩
+        compression_threshold: u32,
+        /// 拼接服务器发来的分片
+        chunks: ChunkAssembler,
         state: SteamClientState,
         connected_status: EClientConnectionState,
         conn_server: Option<NetConnection<ClientManager>>,
@@ -442,6 +983,11 @@ pub mod steamp2p {
         client_socket: Option<NetworkingSockets<ClientManager>>,
         // 我们最后一次从服务器获得数据的时间
         last_network_data_received_time: i64,
+        /// 服务器超过多久(毫秒)没有发送任何数据视为超时,为0时不检测
+        server_timeout: i64,
+        /// 向服务器发送心跳的间隔(毫秒)
+        heartbeat_interval: i64,
+        last_heartbeat_sent_time: i64,
         steam_id_game_server: Option<SteamId>,
         user: User<ClientManager>,
         matchmaking: Matchmaking<ClientManager>,
@@ -453,6 +999,12 @@ pub mod steamp2p {
         game_start_data_cb: Option<ThreadsafeFunction<GameStart, ErrorStrategy::Fatal>>,
         set_game_start_data: Option<ThreadsafeFunction<(), ErrorStrategy::Fatal>>,
         broadcast_cb: Option<ThreadsafeFunction<BroadcastData, ErrorStrategy::Fatal>>,
+        resume_game_cb: Option<ThreadsafeFunction<SteamResumeGame, ErrorStrategy::Fatal>>,
+        desync_cb: Option<ThreadsafeFunction<MsgServerDesync, ErrorStrategy::Fatal>>,
+        server_timed_out: Option<ThreadsafeFunction<(), ErrorStrategy::Fatal>>,
+        paused_cb: Option<ThreadsafeFunction<SteamPauseEvent, ErrorStrategy::Fatal>>,
+        version_mismatch_cb: Option<ThreadsafeFunction<SteamVersionMismatch, ErrorStrategy::Fatal>>,
+        resumed_cb: Option<ThreadsafeFunction<SteamPauseEvent, ErrorStrategy::Fatal>>,
     }
 
     #[napi]
@@ -466,6 +1018,21 @@ pub mod steamp2p {
                 un_server_ip: 0,
                 us_server_port: 0,
                 player_index: 0,
+                spectator: false,
+                build: String::new(),
+                codec: CodecKind::default(),
+                last_frame_id: 0,
+                paused: false,
+                input_delay: 0,
+                input_seq: 0,
+                input_redundancy: DEFAULT_INPUT_REDUNDANCY,
+                recent_inputs: VecDeque::new(),
+                frames: FrameSequencer::default(),
+                blobs: BlobTransfers::default(),
+                blob_callbacks: BlobCallbacks::default(),
+                server_features: 0,
+                compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+                chunks: ChunkAssembler::default(),
                 state: SteamClientState::KEclientFree,
                 connected_status: EClientConnectionState::KEclientNotConnected,
                 conn_server: None,
@@ -478,6 +1045,9 @@ pub mod steamp2p {
                 client_raw: None,
                 client_socket: None,
                 last_network_data_received_time: 0,
+                server_timeout: 10_000,
+                heartbeat_interval: 1_000,
+                last_heartbeat_sent_time: 0,
                 steam_id_game_server: None,
                 user: client.user(),
                 matchmaking: client.matchmaking(),
@@ -488,6 +1058,12 @@ pub mod steamp2p {
                 game_start_data_cb: None,
                 broadcast_cb: None,
                 set_game_start_data: None,
+                resume_game_cb: None,
+                desync_cb: None,
+                server_timed_out: None,
+                paused_cb: None,
+                version_mismatch_cb: None,
+                resumed_cb: None,
             }
         }
 
@@ -498,6 +1074,7 @@ pub mod steamp2p {
 
             let client = crate::client::get_client();
             self.local_id = Some(self.user.steam_id());
+            self.blobs.set_local_id(self.user.steam_id().raw());
 
             self.checkout = true;
             self.state = SteamClientState::KEclientInLobby;
@@ -538,6 +1115,9 @@ pub mod steamp2p {
                 .connect_p2p(identity, 0, vec![]);
 
             if let Ok(p2p) = p2p {
+                if let Err(e) = p2p.configure_lanes(&LANE_PRIORITIES, &LANE_WEIGHTS) {
+                    println!("failed to configure connection lanes: {:?}", e);
+                }
                 self.conn_server = Some(p2p);
 
                 #[cfg(feature = "dev")]
@@ -578,105 +1158,158 @@ pub mod steamp2p {
         }
 
         pub fn on_broadcast_update(&mut self, data: MsgServerDataBroadcast) {
-            let mut buffer = ByteBuffer::new();
-            buffer.set_endian(Endian::LittleEndian);
-
-            let count = data.data.len();
-
-            if count != 0 {
-                buffer.write_bytes(&data.data);
-            }
-
             if let Some(fun) = self.broadcast_cb.as_ref() {
                 fun.call(
-                    BroadcastData {
-                        buffer: Buffer::from(buffer.into_vec()),
-                        steam_id: BigInt::from(data.local_steam_id),
-                    },
+                    BroadcastData::from(&data),
                     ThreadsafeFunctionCallMode::Blocking,
                 );
             }
         }
 
         pub fn on_game_start(&mut self, data: MsgServerGameStart) {
-            let mut buffer = ByteBuffer::new();
-            buffer.set_endian(Endian::LittleEndian);
+            if let Some(fun) = self.game_start_data_cb.as_ref() {
+                fun.call(GameStart::from(data), ThreadsafeFunctionCallMode::Blocking);
+            }
+        }
 
-            let mut count = data.game_data.len();
+        pub fn on_receive_update(&mut self, data: MsgServerFramesData) {
+            let frames = if data.frame_id == 0 {
+                // 补发或者重连时打包的旧帧
+                data.redundant
+                    .into_iter()
+                    .flat_map(|f| self.frames.push(f).unwrap_or_else(|f| vec![f]))
+                    .collect()
+            } else if data.redundant.is_empty() {
+                match self.frames.push(data) {
+                    Ok(frames) => frames,
+                    // 补发或者重连时请求的旧帧
+                    Err(frame) => vec![frame],
+                }
+            } else {
+                self.frames.push_packed(data, self.last_frame_id)
+            };
 
-            if count != 0 {
-                let u16size = std::mem::size_of::<u16>();
-                let size = data.buffer_size as usize + u16size * count;
-                buffer.resize(size);
+            // 丢失的帧超出了冗余范围
+            if let Some((from, to)) = self.frames.missing() {
+                self.send_message(MsgClientRequestFrames { from, to });
+            }
 
-                let mut offset = 0;
-                let frame_data = &data.game_data;
+            for data in frames {
+                self.last_frame_id = self.last_frame_id.max(data.frame_id);
 
-                for frame in frame_data.iter() {
-                    buffer.write_u16(frame.data.len() as u16);
-                    offset += u16size;
-                    buffer.write_bytes(&frame.data);
-                    offset += frame.data.len();
+                if let Some(fun) = self.steam_on_receive_update.as_ref() {
+                    fun.call(
+                        SteamReceiveUpdate::from(data),
+                        ThreadsafeFunctionCallMode::Blocking,
+                    );
                 }
+            }
+        }
+
+        /// 没有指定发送方式时的操作发送方式,关闭冗余时可靠发送
+        fn input_send_flags(&self) -> SendFlags {
+            if self.input_redundancy == 0 {
+                SendFlags::RELIABLE_NO_NAGLE
+            } else {
+                MsgClientFrameData::SEND_FLAGS
+            }
+        }
 
-                if offset != size {
-                    buffer.clear();
-                    buffer.resize(0);
-                    count = 0;
+        /// 发送一条操作,不可靠发送时附带最近的几条操作
+        pub fn send_input(
+            &mut self,
+            mut msg: MsgClientFrameData,
+            send_flags: SendFlags,
+            lane: u16,
+        ) {
+            self.input_seq += 1;
+            msg.seq = self.input_seq;
+
+            if self.input_redundancy > 0 {
+                let sent = msg.clone();
+                if !send_flags.contains(SendFlags::RELIABLE) {
+                    msg.redundant = self.recent_inputs.iter().cloned().collect();
+                }
+
+                self.recent_inputs.push_back(sent);
+                while self.recent_inputs.len() > self.input_redundancy as usize {
+                    self.recent_inputs.pop_front();
                 }
             }
 
-            if let Some(fun) = self.game_start_data_cb.as_ref() {
+            self.send_message_with(msg, send_flags, lane);
+        }
+
+        pub fn on_paused(&mut self, data: MsgServerPaused) {
+            self.paused = true;
+
+            if let Some(fun) = self.paused_cb.as_ref() {
                 fun.call(
-                    GameStart {
-                        buffer: Buffer::from(buffer.into_vec()),
-                        count: count.try_into().unwrap(),
+                    SteamPauseEvent {
+                        steam_id: BigInt::from(data.steam_id),
+                        frame_id: data.frame_id,
                     },
                     ThreadsafeFunctionCallMode::Blocking,
                 );
             }
         }
 
-        pub fn on_receive_update(&mut self, data: MsgServerFramesData) {
-            let mut buffer = ByteBuffer::new();
-            buffer.set_endian(Endian::LittleEndian);
-
-            let mut count = data.game_data.len();
-
-            if count != 0 {
-                let u16size = std::mem::size_of::<u16>();
-                let size = data.buffer_size as usize + u16size * count;
-                buffer.resize(size);
+        pub fn on_resumed(&mut self, data: MsgServerResumed) {
+            self.paused = false;
 
-                let mut offset = 0;
-                let frame_data = &data.game_data;
+            if let Some(fun) = self.resumed_cb.as_ref() {
+                fun.call(
+                    SteamPauseEvent {
+                        steam_id: BigInt::from(data.steam_id),
+                        frame_id: data.frame_id,
+                    },
+                    ThreadsafeFunctionCallMode::Blocking,
+                );
+            }
+        }
 
-                for frame in frame_data.iter() {
-                    buffer.write_u16(frame.data.len() as u16);
-                    offset += u16size;
-                    buffer.write_bytes(&frame.data);
-                    offset += frame.data.len();
-                }
+        pub fn on_desync(&mut self, data: MsgServerDesync) {
+            #[cfg(feature = "dev")]
+            dbg!("desync detected at frame", data.frame_id);
 
-                if offset != size {
-                    buffer.clear();
-                    buffer.resize(0);
-                    count = 0;
-                }
+            if let Some(fun) = self.desync_cb.as_ref() {
+                fun.call(data, ThreadsafeFunctionCallMode::Blocking);
             }
+        }
+
+        pub fn on_resume_game(&mut self, data: MsgServerResumeGame) {
+            #[cfg(feature = "dev")]
+            dbg!(
+                "JsSteamClient on_resume_game",
+                data.from_frame_id,
+                data.to_frame_id
+            );
 
-            if let Some(fun) = self.steam_on_receive_update.as_ref() {
+            if let Some(fun) = self.resume_game_cb.as_ref() {
                 fun.call(
-                    SteamReceiveUpdate {
-                        buffer: Buffer::from(buffer.into_vec()),
-                        frame_id: data.frame_id,
-                        count: count.try_into().unwrap(),
+                    SteamResumeGame {
+                        player_position: data.player_position,
+                        from_frame_id: data.from_frame_id,
+                        to_frame_id: data.to_frame_id,
                     },
                     ThreadsafeFunctionCallMode::Blocking,
                 );
             }
         }
 
+        pub fn reconnect_to_server(&mut self) {
+            let server = match self.steam_id_game_server {
+                Some(server) => server,
+                None => return,
+            };
+
+            #[cfg(feature = "dev")]
+            dbg!("JsSteamClient reconnect_to_server", server.raw());
+
+            self.disconnect_from_server();
+            self.initiate_server_connection(BigInt::from(server.raw()));
+        }
+
         pub fn on_receive_server_authentication_response(&mut self, success: bool, pos: u32) {
             if !success {
                 self.disconnect_from_server();
@@ -726,16 +1359,190 @@ pub mod steamp2p {
                 );
             }
 
+            self.frames.reset();
+            self.recent_inputs.clear();
+            self.server_features = 0;
+            self.chunks.clear();
             self.steam_id_game_server = None;
         }
 
+        /// 和服务器版本不一致,断开连接并且不再发送认证
+        pub fn on_version_mismatch(
+            &mut self,
+            steam_id: u64,
+            protocol_version: u32,
+            build: String,
+            codec: u32,
+        ) {
+            println!(
+                "server version mismatch: protocol {} (local {}), build {:?} (local {:?}), codec {} (local {})",
+                protocol_version, PROTOCOL_VERSION, build, self.build, codec, self.codec as u32
+            );
+
+            if let Some(conn) = self.conn_server.take() {
+                conn.close(EDisconnectReason::EDRVersionMismatch.into(), None, false);
+            }
+            self.disconnect_from_server();
+
+            if let Some(fun) = self.version_mismatch_cb.as_ref() {
+                fun.call(
+                    SteamVersionMismatch {
+                        steam_id: BigInt::from(steam_id),
+                        protocol_version,
+                        build,
+                        codec,
+                    },
+                    ThreadsafeFunctionCallMode::Blocking,
+                );
+            }
+        }
+
+        /// 服务器超时后断开连接,保留服务器ID用于重连
+        pub fn check_server_timeout(&mut self) {
+            if self.conn_server.is_none() || self.server_timeout <= 0 {
+                return;
+            }
+
+            if now() - self.last_network_data_received_time <= self.server_timeout {
+                return;
+            }
+
+            println!("server timed out, dropping connection");
+
+            let server = self.steam_id_game_server;
+            self.disconnect_from_server();
+            self.steam_id_game_server = server;
+
+            if let Some(fun) = self.server_timed_out.as_ref() {
+                fun.call((), ThreadsafeFunctionCallMode::Blocking);
+            }
+        }
+
+        pub fn send_heartbeat(&mut self) {
+            if self.conn_server.is_none()
+                || self.connected_status == EClientConnectionState::KEclientNotConnected
+            {
+                return;
+            }
+
+            let time = now();
+            if time - self.last_heartbeat_sent_time < self.heartbeat_interval {
+                return;
+            }
+
+            self.last_heartbeat_sent_time = time;
+            self.send_message(MsgClientHeartbeat);
+        }
+
+        /// 通过服务器发送大数据的消息,没有完成认证时暂停,重连后从断点继续
+        pub fn send_blobs(&mut self) {
+            if self.connected_status == EClientConnectionState::KEclientConnectedAndAuthenticated {
+                self.blobs.poll(now());
+                for msg in self.blobs.take_messages() {
+                    match msg {
+                        BlobMessage::Start(msg) => self.send_message(msg),
+                        BlobMessage::Chunk(msg) => self.send_message(msg),
+                        BlobMessage::Ack(msg) => self.send_message(msg),
+                        BlobMessage::Complete(msg) => self.send_message(msg),
+                    }
+                }
+            }
+
+            self.blob_callbacks.notify(self.blobs.take_events());
+        }
+
+        /// 处理服务器发来的一条消息
+        pub fn on_message(&mut self, header: EMessage, body: &[u8]) -> Result<(), DecodeError> {
+            match header {
+                EMessage::KEmsgServerSendInfo => match self.codec.decode::<MsgServerSendInfo>(body)
+                {
+                    Ok(msg) => self.on_receive_server_info(msg),
+                    // 服务器的协议版本更新,消息格式无法解析
+                    Err(_) => self.on_version_mismatch(
+                        self.steam_id_game_server.map_or(0, |id| id.raw()),
+                        peek_protocol_version(body, MsgServerSendInfo::PROTOCOL_VERSION_INDEX)
+                            .unwrap_or(0),
+                        String::new(),
+                        self.codec as u32,
+                    ),
+                },
+                EMessage::KEmsgServerFailAuthentication => {
+                    self.codec.decode::<MsgServerFailAuthentication>(body)?;
+                    self.on_receive_server_authentication_response(false, 0);
+                }
+                EMessage::KEmsgServerPassAuthentication => {
+                    let msg = self.codec.decode::<MsgServerPassAuthentication>(body)?;
+                    self.on_receive_server_authentication_response(true, msg.player_position);
+                }
+                EMessage::KEmsgServerAllReadyToGo => {
+                    if let Some(fun) = self.steam_all_ready_to_go.as_ref() {
+                        fun.call((), ThreadsafeFunctionCallMode::Blocking);
+                    }
+                }
+                EMessage::KEmsgServerFramesData => {
+                    let msg = self.codec.decode::<MsgServerFramesData>(body)?;
+                    self.on_receive_update(msg);
+                }
+                EMessage::KEmsgServerGameStart => {
+                    let msg = self.codec.decode::<MsgServerGameStart>(body)?;
+                    self.on_game_start(msg);
+                }
+                EMessage::KEmsgServerSetGameStartDataComplete => {
+                    if let Some(fun) = self.set_game_start_data.as_ref() {
+                        fun.call((), ThreadsafeFunctionCallMode::Blocking);
+                    }
+                }
+                EMessage::KEmsgServerBroadcast => {
+                    let msg = self.codec.decode::<MsgServerDataBroadcast>(body)?;
+                    self.on_broadcast_update(msg);
+                }
+                EMessage::KEmsgServerResumeGame => {
+                    let msg = self.codec.decode::<MsgServerResumeGame>(body)?;
+                    self.on_resume_game(msg);
+                }
+                EMessage::KEmsgServerHeartbeat => {}
+                EMessage::KEmsgServerPaused => {
+                    let msg = self.codec.decode::<MsgServerPaused>(body)?;
+                    self.on_paused(msg);
+                }
+                EMessage::KEmsgServerResumed => {
+                    let msg = self.codec.decode::<MsgServerResumed>(body)?;
+                    self.on_resumed(msg);
+                }
+                EMessage::KEmsgServerDesync => {
+                    let msg = self.codec.decode::<MsgServerDesync>(body)?;
+                    self.on_desync(msg);
+                }
+                EMessage::KEmsgBlobStart => self.blobs.on_start(self.codec.decode(body)?),
+                EMessage::KEmsgBlobChunk => self.blobs.on_chunk(self.codec.decode(body)?),
+                EMessage::KEmsgBlobAck => self.blobs.on_ack(self.codec.decode(body)?, now()),
+                EMessage::KEmsgBlobComplete => self.blobs.on_complete(self.codec.decode(body)?),
+                _ => return Err(DecodeError::Unexpected(header)),
+            }
+
+            Ok(())
+        }
+
         /// extract the IP address of the user from the socket
         pub fn on_receive_server_info(&mut self, msg: MsgServerSendInfo) {
             #[cfg(feature = "dev")]
             dbg!("JsSteamClient on_receive_server_info");
 
+            if !is_version_compatible(msg.protocol_version, &msg.build, &self.build)
+                || msg.codec != self.codec as u32
+            {
+                self.on_version_mismatch(
+                    msg.ul_steam_idserver,
+                    msg.protocol_version,
+                    msg.build,
+                    msg.codec,
+                );
+                return;
+            }
+
             self.connected_status = EClientConnectionState::KEclientConnectedPendingAuthentication;
             self.steam_id_game_server = Some(SteamId::from_raw(msg.ul_steam_idserver));
+            self.server_features = msg.features & SUPPORTED_FEATURES;
 
             if let Some(info) = self.conn_server.as_ref().unwrap().get_connection_info() {
                 self.un_server_ip = info.ip_v4().map_or(0, |f| f.into());
@@ -755,7 +1562,15 @@ pub mod steamp2p {
 
             self.auth_ticket = Some(auth_ticket);
 
-            let auth = MsgClientBeginAuthentication { rgch_token: ticket };
+            let auth = MsgClientBeginAuthentication {
+                rgch_token: ticket,
+                spectator: self.spectator,
+                protocol_version: PROTOCOL_VERSION,
+                build: self.build.clone(),
+                features: SUPPORTED_FEATURES,
+                codec: self.codec as u32,
+                lobby_id: self.lobby_id.map_or(0, |id| id.raw()),
+            };
             self.send_message(auth);
         }
 
@@ -763,28 +1578,54 @@ pub mod steamp2p {
         where
             T: INetMessage + serde::Serialize,
         {
-            //TODO u8 pool alloc and free
-            let mut bytes = Vec::new();
-            msg.serialize(&mut rmps::Serializer::new(&mut bytes))
-                .unwrap();
-
-            let mut header: Vec<u8> = T::ID.into();
-            header.append(&mut bytes);
+            self.send_message_with(msg, T::SEND_FLAGS, T::LANE);
+        }
 
-            let result = self
-                .conn_server
-                .as_ref()
-                .unwrap()
-                .send_message(&header, SendFlags::RELIABLE_NO_NAGLE);
-
-            let _ = result
-                .map_err(|e| match e {
-                    SteamError::InvalidParameter => println!("SteamClientFailed sending data to server: Invalid connection handle, or the individual message is too big"),
-                    SteamError::InvalidState => println!("SteamClientFailed sending data to server: Connection is in an invalid state"),
-                    SteamError::NoConnection => println!("SteamClientFailed sending data to server: Connection has ended"),
-                    SteamError::LimitExceeded => println!("SteamClientFailed sending data to server: There was already too much data queued to be sent"),
-                    _ => println!("SendMessageToConnection error,{:?}",e as i32)
-                });
+        /// 使用指定的发送方式和通道发送
+        pub fn send_message_with<T>(&self, msg: T, send_flags: SendFlags, lane: u16)
+        where
+            T: INetMessage + serde::Serialize,
+        {
+            //TODO u8 pool alloc and free
+            let bytes = self.codec.encode(&msg);
+
+            let packets = encode_packet(
+                T::ID,
+                &bytes,
+                self.server_features,
+                self.compression_threshold,
+            );
+            // 丢失一个分片整条消息都无法还原
+            let send_flags = if packets.len() > 1 {
+                send_flags | SendFlags::RELIABLE
+            } else {
+                send_flags
+            };
+
+            let messages = packets
+                .into_iter()
+                .map(|packet| {
+                    let mut message = self.utils.as_ref().unwrap().allocate_message(0);
+                    message.set_connection(self.conn_server.as_ref().unwrap());
+                    message.set_send_flags(send_flags);
+                    message.set_lane(lane);
+                    message.set_data(packet).unwrap();
+                    message
+                })
+                .collect::<Vec<_>>();
+
+            let results = self.client_socket.as_ref().unwrap().send_messages(messages);
+
+            results.into_iter().for_each(|result| {
+                let _ = result
+                    .map_err(|e| match e {
+                        SteamError::InvalidParameter => println!("SteamClientFailed sending data to server: Invalid connection handle, or the individual message is too big"),
+                        SteamError::InvalidState => println!("SteamClientFailed sending data to server: Connection is in an invalid state"),
+                        SteamError::NoConnection => println!("SteamClientFailed sending data to server: Connection has ended"),
+                        SteamError::LimitExceeded => println!("SteamClientFailed sending data to server: There was already too much data queued to be sent"),
+                        _ => println!("SendMessageToConnection error,{:?}",e as i32)
+                    });
+            });
         }
     }
 }
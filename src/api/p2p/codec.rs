@@ -0,0 +1,378 @@
+use crate::api::p2p::message::{
+    EMessage, INetMessage, MsgClientFrameData, MsgServerFrameData, MsgServerFramesData,
+};
+use crate::api::p2p::packet::{decode_body, DecodeError, MAX_PAYLOAD_SIZE};
+use bincode::Options;
+use bytebuffer::{ByteBuffer, Endian};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// 消息体的编码方式
+pub trait Codec {
+    fn encode<T>(msg: &T) -> Vec<u8>
+    where
+        T: INetMessage + Serialize;
+
+    fn decode<T>(body: &[u8]) -> Result<T, DecodeError>
+    where
+        T: INetMessage + DeserializeOwned;
+}
+
+/// MessagePack,字段可以在末尾追加,兼容旧版本
+pub struct MessagePackCodec;
+
+impl Codec for MessagePackCodec {
+    fn encode<T>(msg: &T) -> Vec<u8>
+    where
+        T: INetMessage + Serialize,
+    {
+        let mut bytes = Vec::new();
+        msg.serialize(&mut rmps::Serializer::new(&mut bytes))
+            .unwrap();
+        bytes
+    }
+
+    fn decode<T>(body: &[u8]) -> Result<T, DecodeError>
+    where
+        T: INetMessage + DeserializeOwned,
+    {
+        decode_body(body)
+    }
+}
+
+/// bincode,更紧凑但是不能省略字段
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    fn encode<T>(msg: &T) -> Vec<u8>
+    where
+        T: INetMessage + Serialize,
+    {
+        bincode::serialize(msg).unwrap()
+    }
+
+    fn decode<T>(body: &[u8]) -> Result<T, DecodeError>
+    where
+        T: INetMessage + DeserializeOwned,
+    {
+        // 和 `bincode::serialize` 相同的格式,另外限制读取的长度
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(MAX_PAYLOAD_SIZE as u64)
+            .deserialize(body)
+            .map_err(|err| DecodeError::Malformed(T::ID, err.to_string()))
+    }
+}
+
+/// 帧数据直接按字节布局读写,其他消息使用 MessagePack
+pub struct RawCodec;
+
+impl Codec for RawCodec {
+    fn encode<T>(msg: &T) -> Vec<u8>
+    where
+        T: INetMessage + Serialize,
+    {
+        msg.encode_raw()
+            .unwrap_or_else(|| MessagePackCodec::encode(msg))
+    }
+
+    fn decode<T>(body: &[u8]) -> Result<T, DecodeError>
+    where
+        T: INetMessage + DeserializeOwned,
+    {
+        match T::decode_raw(body) {
+            Some(result) => result.map_err(|err| DecodeError::Malformed(T::ID, err)),
+            None => MessagePackCodec::decode(body),
+        }
+    }
+}
+
+/// 创建客户端或服务器时选择的编码方式,握手时双方必须一致
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum CodecKind {
+    #[default]
+    MessagePack = 0,
+    Bincode = 1,
+    Raw = 2,
+}
+
+impl CodecKind {
+    /// 握手消息总是使用 MessagePack,保证编码方式不一致时也能读出对方的版本
+    fn for_message(self, id: EMessage) -> Self {
+        match id {
+            EMessage::KEmsgServerSendInfo
+            | EMessage::KEmsgServerFailAuthentication
+            | EMessage::KEmsgClientBeginAuthentication => CodecKind::MessagePack,
+            _ => self,
+        }
+    }
+
+    pub fn encode<T>(self, msg: &T) -> Vec<u8>
+    where
+        T: INetMessage + Serialize,
+    {
+        match self.for_message(T::ID) {
+            CodecKind::MessagePack => MessagePackCodec::encode(msg),
+            CodecKind::Bincode => BincodeCodec::encode(msg),
+            CodecKind::Raw => RawCodec::encode(msg),
+        }
+    }
+
+    pub fn decode<T>(self, body: &[u8]) -> Result<T, DecodeError>
+    where
+        T: INetMessage + DeserializeOwned,
+    {
+        match self.for_message(T::ID) {
+            CodecKind::MessagePack => MessagePackCodec::decode(body),
+            CodecKind::Bincode => BincodeCodec::decode(body),
+            CodecKind::Raw => RawCodec::decode(body),
+        }
+    }
+}
+
+/// 可以按固定字节布局读写的消息,数值为小端序
+///
+/// 附带的冗余数据只有一层,不会再附带自己的冗余数据
+pub trait RawMessage: Sized {
+    fn write_raw(&self, buffer: &mut ByteBuffer, top: bool);
+
+    fn read_raw(buffer: &mut ByteBuffer, top: bool) -> std::io::Result<Self>;
+
+    fn to_raw(&self) -> Vec<u8> {
+        let mut buffer = ByteBuffer::new();
+        buffer.set_endian(Endian::LittleEndian);
+        self.write_raw(&mut buffer, true);
+        buffer.into_vec()
+    }
+
+    fn from_raw(body: &[u8]) -> Result<Self, String> {
+        let mut buffer = ByteBuffer::from_bytes(body);
+        buffer.set_endian(Endian::LittleEndian);
+        let msg = Self::read_raw(&mut buffer, true).map_err(|err| err.to_string())?;
+
+        if buffer.get_rpos() != body.len() {
+            return Err(format!("{} trailing bytes", body.len() - buffer.get_rpos()));
+        }
+        Ok(msg)
+    }
+}
+
+fn write_data(buffer: &mut ByteBuffer, data: &[u8]) {
+    buffer.write_u32(data.len() as u32);
+    buffer.write_bytes(data);
+}
+
+fn read_data(buffer: &mut ByteBuffer) -> std::io::Result<Vec<u8>> {
+    let len = buffer.read_u32()?;
+    buffer.read_bytes(len as usize)
+}
+
+/// 按数量读取,不按对方声明的数量预先分配
+fn read_list<T, F>(buffer: &mut ByteBuffer, mut read: F) -> std::io::Result<Vec<T>>
+where
+    F: FnMut(&mut ByteBuffer) -> std::io::Result<T>,
+{
+    let count = buffer.read_u32()?;
+    let mut list = Vec::new();
+    for _ in 0..count {
+        list.push(read(buffer)?);
+    }
+    Ok(list)
+}
+
+impl RawMessage for MsgClientFrameData {
+    fn write_raw(&self, buffer: &mut ByteBuffer, top: bool) {
+        buffer.write_u32(self.types);
+        buffer.write_u32(self.frame_id);
+        buffer.write_u32(self.seq);
+        write_data(buffer, &self.data);
+
+        if top {
+            buffer.write_u32(self.redundant.len() as u32);
+            self.redundant
+                .iter()
+                .for_each(|f| f.write_raw(buffer, false));
+        }
+    }
+
+    fn read_raw(buffer: &mut ByteBuffer, top: bool) -> std::io::Result<Self> {
+        Ok(MsgClientFrameData {
+            types: buffer.read_u32()?,
+            frame_id: buffer.read_u32()?,
+            seq: buffer.read_u32()?,
+            data: read_data(buffer)?,
+            redundant: if top {
+                read_list(buffer, |buffer| Self::read_raw(buffer, false))?
+            } else {
+                vec![]
+            },
+        })
+    }
+}
+
+impl RawMessage for MsgServerFramesData {
+    fn write_raw(&self, buffer: &mut ByteBuffer, top: bool) {
+        buffer.write_u32(self.frame_id);
+        buffer.write_u32(self.buffer_size);
+        buffer.write_u32(self.game_data.len() as u32);
+        self.game_data.iter().for_each(|f| {
+            buffer.write_u32(f.types);
+            buffer.write_u64(f.local_steam_id);
+            buffer.write_u32(f.player_position);
+            write_data(buffer, &f.data);
+        });
+
+        if top {
+            buffer.write_u32(self.redundant.len() as u32);
+            self.redundant
+                .iter()
+                .for_each(|f| f.write_raw(buffer, false));
+        }
+    }
+
+    fn read_raw(buffer: &mut ByteBuffer, top: bool) -> std::io::Result<Self> {
+        Ok(MsgServerFramesData {
+            frame_id: buffer.read_u32()?,
+            buffer_size: buffer.read_u32()?,
+            game_data: read_list(buffer, |buffer| {
+                Ok(MsgServerFrameData {
+                    types: buffer.read_u32()?,
+                    local_steam_id: buffer.read_u64()?,
+                    player_position: buffer.read_u32()?,
+                    data: read_data(buffer)?,
+                })
+            })?,
+            redundant: if top {
+                read_list(buffer, |buffer| Self::read_raw(buffer, false))?
+            } else {
+                vec![]
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CodecKind;
+    use crate::api::p2p::message::{
+        MsgClientBeginAuthentication, MsgClientFrameData, MsgServerFrameData, MsgServerFramesData,
+    };
+    use crate::api::p2p::packet::decode_body;
+
+    fn frames(frame_id: u32, redundant: Vec<MsgServerFramesData>) -> MsgServerFramesData {
+        MsgServerFramesData {
+            game_data: vec![
+                MsgServerFrameData {
+                    types: 1,
+                    data: vec![1, 2, 3],
+                    local_steam_id: 76561198000000000,
+                    player_position: 3,
+                },
+                MsgServerFrameData {
+                    types: 2,
+                    data: vec![],
+                    local_steam_id: 0,
+                    player_position: 0,
+                },
+            ],
+            buffer_size: 9,
+            frame_id,
+            redundant,
+        }
+    }
+
+    #[test]
+    fn round_trips_frame_data() {
+        let client = MsgClientFrameData {
+            types: 3,
+            data: vec![9; 40],
+            frame_id: 12,
+            seq: 7,
+            redundant: vec![MsgClientFrameData {
+                types: 3,
+                data: vec![8; 4],
+                frame_id: 11,
+                seq: 6,
+                redundant: vec![],
+            }],
+        };
+        let server = frames(5, vec![frames(3, vec![]), frames(4, vec![])]);
+
+        for codec in [CodecKind::MessagePack, CodecKind::Bincode, CodecKind::Raw] {
+            let bytes = codec.encode(&client);
+            assert_eq!(
+                codec.decode::<MsgClientFrameData>(&bytes),
+                Ok(client.clone())
+            );
+
+            let bytes = codec.encode(&server);
+            let decoded = codec.decode::<MsgServerFramesData>(&bytes).unwrap();
+            assert_eq!(decoded.frame_id, 5);
+            assert_eq!(decoded.game_data, server.game_data);
+            assert_eq!(decoded.redundant.len(), 2);
+        }
+
+        // 截断的数据
+        let bytes = CodecKind::Raw.encode(&server);
+        for len in 0..bytes.len() {
+            assert!(CodecKind::Raw
+                .decode::<MsgServerFramesData>(&bytes[..len])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_nested_redundant_data() {
+        // 每一层都在 redundant 里再附带一条操作
+        const DEPTH: usize = 100_000;
+
+        let mut bincode = vec![];
+        for level in 0..DEPTH {
+            bincode.extend_from_slice(&0u32.to_le_bytes()); // types
+            bincode.extend_from_slice(&0u64.to_le_bytes()); // data
+            bincode.extend_from_slice(&0u32.to_le_bytes()); // frame_id
+            bincode.extend_from_slice(&0u32.to_le_bytes()); // seq
+            let nested = if level + 1 < DEPTH { 1u64 } else { 0 };
+            bincode.extend_from_slice(&nested.to_le_bytes());
+        }
+        assert!(CodecKind::Bincode
+            .decode::<MsgClientFrameData>(&bincode)
+            .is_err());
+
+        let mut msgpack = vec![];
+        for level in 0..DEPTH {
+            let nested = if level + 1 < DEPTH { 0x91 } else { 0x90 };
+            msgpack.extend_from_slice(&[0x95, 0x00, 0x90, 0x00, 0x00, nested]);
+        }
+        assert!(CodecKind::MessagePack
+            .decode::<MsgClientFrameData>(&msgpack)
+            .is_err());
+
+        // 只有一层时可以正常读取
+        let one = &bincode[bincode.len() - 2 * 28..];
+        let decoded = CodecKind::Bincode
+            .decode::<MsgClientFrameData>(one)
+            .unwrap();
+        assert_eq!(decoded.redundant.len(), 1);
+    }
+
+    #[test]
+    fn handshake_always_uses_message_pack() {
+        let auth = MsgClientBeginAuthentication {
+            rgch_token: vec![1, 2, 3],
+            spectator: false,
+            protocol_version: 1,
+            build: String::new(),
+            features: 0,
+            codec: CodecKind::Bincode as u32,
+            lobby_id: 0,
+        };
+
+        let bytes = CodecKind::Bincode.encode(&auth);
+        assert_eq!(
+            decode_body::<MsgClientBeginAuthentication>(&bytes),
+            Ok(auth)
+        );
+    }
+}
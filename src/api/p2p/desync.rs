@@ -0,0 +1,120 @@
+use crate::api::p2p::message::MsgServerDesync;
+use napi::bindgen_prelude::BigInt;
+use napi::{Env, JsUnknown};
+use std::collections::BTreeMap;
+
+/// 默认保留校验值的帧数,超过之后还没有收齐的帧直接丢弃
+pub const DEFAULT_CHECKSUM_WINDOW: u32 = 600;
+
+#[derive(Default)]
+struct FrameChecksums {
+    checksums: BTreeMap<u64, u64>,
+    desynced: bool,
+}
+
+/// 比较所有玩家同一帧的状态校验值
+pub struct ChecksumTracker {
+    frames: BTreeMap<u32, FrameChecksums>,
+    window: u32,
+}
+
+impl ChecksumTracker {
+    pub fn new(window: u32) -> Self {
+        ChecksumTracker {
+            frames: BTreeMap::new(),
+            window,
+        }
+    }
+
+    /// 记录一个玩家某一帧的校验值
+    ///
+    /// 第一次出现不一致时返回该帧所有玩家的校验值,
+    /// `players` 个玩家都上报并且一致后不再保留该帧
+    pub fn insert(
+        &mut self,
+        frame_id: u32,
+        steam_id: u64,
+        checksum: u64,
+        players: usize,
+    ) -> Option<MsgServerDesync> {
+        let oldest = self
+            .frames
+            .keys()
+            .next_back()
+            .map_or(frame_id, |last| (*last).max(frame_id))
+            .saturating_sub(self.window);
+        if frame_id < oldest {
+            return None;
+        }
+        self.frames = self.frames.split_off(&oldest);
+
+        let frame = self.frames.entry(frame_id).or_default();
+        frame.checksums.insert(steam_id, checksum);
+
+        let diverged = frame.checksums.values().any(|v| *v != checksum);
+        if diverged && !frame.desynced {
+            frame.desynced = true;
+
+            return Some(MsgServerDesync {
+                frame_id,
+                checksums: frame.checksums.clone(),
+            });
+        }
+
+        if !frame.desynced && frame.checksums.len() >= players {
+            self.frames.remove(&frame_id);
+        }
+
+        None
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// 转换为JS回调的参数 `(frameId, { steamId: checksum })`
+pub fn desync_js_args(env: &Env, msg: &MsgServerDesync) -> napi::Result<Vec<JsUnknown>> {
+    let frame_id = env.create_uint32(msg.frame_id)?;
+    let mut checksums = env.create_object()?;
+    for (steam_id, checksum) in msg.checksums.iter() {
+        checksums.set(steam_id.to_string(), BigInt::from(*checksum))?;
+    }
+
+    Ok(vec![frame_id.into_unknown(), checksums.into_unknown()])
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChecksumTracker;
+
+    #[test]
+    fn reports_first_divergence_once() {
+        let mut tracker = ChecksumTracker::new(10);
+
+        assert!(tracker.insert(1, 100, 7, 3).is_none());
+        assert!(tracker.insert(1, 200, 7, 3).is_none());
+
+        let desync = tracker.insert(1, 300, 8, 3).unwrap();
+        assert_eq!(desync.frame_id, 1);
+        assert_eq!(desync.checksums.len(), 3);
+        assert_eq!(desync.checksums[&300], 8);
+
+        assert!(tracker.insert(1, 300, 9, 3).is_none());
+    }
+
+    #[test]
+    fn forgets_matching_and_stale_frames() {
+        let mut tracker = ChecksumTracker::new(10);
+
+        assert!(tracker.insert(1, 100, 7, 2).is_none());
+        assert!(tracker.insert(1, 200, 7, 2).is_none());
+        // 第1帧已经收齐,新的校验值不再和旧值比较
+        assert!(tracker.insert(1, 100, 8, 2).is_none());
+
+        assert!(tracker.insert(2, 100, 1, 2).is_none());
+        assert!(tracker.insert(20, 100, 1, 2).is_none());
+        // 第2帧已经超出窗口
+        assert!(tracker.insert(2, 200, 2, 2).is_none());
+    }
+}
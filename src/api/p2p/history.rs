@@ -0,0 +1,130 @@
+use crate::api::p2p::message::MsgServerFramesData;
+use std::collections::VecDeque;
+
+/// 默认缓存的帧数量,30帧/秒时大约可以保留一分钟
+pub const DEFAULT_FRAME_HISTORY_SIZE: usize = 1800;
+
+/// 服务器已经下发的帧数据的环形缓存
+///
+/// 用于断线重连后补发帧,以及响应客户端的补帧请求
+pub struct FrameHistory {
+    frames: VecDeque<MsgServerFramesData>,
+    capacity: usize,
+}
+
+impl FrameHistory {
+    pub fn new(capacity: usize) -> Self {
+        FrameHistory {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 修改缓存大小,超出的旧帧会被丢弃
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    /// 追加一帧,缓存满时丢弃最旧的一帧
+    pub fn push(&mut self, frame: MsgServerFramesData) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        while self.frames.len() >= self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// 缓存中最旧的帧ID
+    pub fn first_frame_id(&self) -> Option<u32> {
+        self.frames.front().map(|f| f.frame_id)
+    }
+
+    /// 缓存中是否包含 `from..=to` 之间的所有帧
+    pub fn contains(&self, from: u32, to: u32) -> bool {
+        if from > to {
+            return true;
+        }
+
+        match (self.frames.front(), self.frames.back()) {
+            (Some(first), Some(last)) => first.frame_id <= from && last.frame_id >= to,
+            _ => false,
+        }
+    }
+
+    /// 按顺序返回 `from..=to` 之间缓存中存在的帧
+    pub fn range(&self, from: u32, to: u32) -> impl Iterator<Item = &MsgServerFramesData> {
+        self.frames
+            .iter()
+            .skip_while(move |f| f.frame_id < from)
+            .take_while(move |f| f.frame_id <= to)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FrameHistory;
+    use crate::api::p2p::message::MsgServerFramesData;
+
+    fn frame(frame_id: u32) -> MsgServerFramesData {
+        MsgServerFramesData {
+            game_data: vec![],
+            buffer_size: 0,
+            frame_id,
+            redundant: vec![],
+        }
+    }
+
+    #[test]
+    fn drops_oldest_frames_when_full() {
+        let mut history = FrameHistory::new(3);
+        (1..=5).for_each(|id| history.push(frame(id)));
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.first_frame_id(), Some(3));
+        assert!(history.contains(3, 5));
+        assert!(!history.contains(2, 5));
+
+        history.set_capacity(1);
+        assert_eq!(history.first_frame_id(), Some(5));
+
+        history.set_capacity(0);
+        history.push(frame(6));
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn range_is_inclusive() {
+        let mut history = FrameHistory::new(10);
+        (1..=8).for_each(|id| history.push(frame(id)));
+
+        let ids: Vec<u32> = history.range(3, 5).map(|f| f.frame_id).collect();
+        assert_eq!(ids, vec![3, 4, 5]);
+
+        let ids: Vec<u32> = history.range(7, 100).map(|f| f.frame_id).collect();
+        assert_eq!(ids, vec![7, 8]);
+
+        assert_eq!(history.range(5, 3).count(), 0);
+    }
+}
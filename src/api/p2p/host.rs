@@ -0,0 +1,369 @@
+use crate::api::p2p::browser::ServerDetails;
+use crate::api::p2p::codec::CodecKind;
+use crate::api::p2p::message::{
+    peek_u64, EDisconnectReason, EMessage, INetMessage, MsgClientBeginAuthentication,
+    MsgServerSendInfo, LANE_PRIORITIES, LANE_WEIGHTS,
+};
+use crate::api::p2p::packet::{decode_packet, encode_packet};
+use crate::client::now;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::NulError;
+use std::rc::{Rc, Weak};
+use steamworks::networking_sockets::{ListenSocket, NetConnection, NetPollGroup};
+use steamworks::networking_types::{
+    ListenSocketEvent, NetConnectionEnd, NetConnectionEndReason, NetworkingIdentity,
+};
+use steamworks::{Server, ServerManager, SingleClient, SteamId};
+
+/// 还没有发送握手消息的连接超过多久(毫秒)断开
+const UNROUTED_TIMEOUT: i64 = 10_000;
+
+thread_local! {
+    static HOST: RefCell<Weak<RefCell<ServerHost>>> = const { RefCell::new(Weak::new()) };
+    /// 封禁的玩家和解封时间,`None` 为永久封禁
+    static BANNED: RefCell<HashMap<u64, Option<i64>>> = RefCell::new(HashMap::new());
+}
+
+/// 封禁玩家,和游戏服务器一样在进程中所有房间之间共用
+pub fn ban(steam_id: u64, expires: Option<i64>) {
+    BANNED.with(|banned| banned.borrow_mut().insert(steam_id, expires));
+}
+
+pub fn unban(steam_id: u64) {
+    BANNED.with(|banned| banned.borrow_mut().remove(&steam_id));
+}
+
+/// 是否被封禁,同时清除已经过期的封禁
+pub fn is_banned(steam_id: u64) -> bool {
+    BANNED.with(|banned| {
+        let mut banned = banned.borrow_mut();
+        match banned.get(&steam_id) {
+            Some(Some(expires)) if *expires <= now() => {
+                banned.remove(&steam_id);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    })
+}
+
+/// 房间收到的连接事件
+pub enum RoomEvent {
+    /// 连接发送了本房间的握手消息,附带这条握手消息
+    Joined(NetworkingIdentity, NetConnection<ServerManager>, Vec<u8>),
+    /// 已经分给本房间的连接断开
+    Disconnected(NetworkingIdentity),
+}
+
+/// 登记在游戏服务器中的房间
+#[derive(Default)]
+struct Room {
+    /// 待处理的连接事件
+    events: VecDeque<RoomEvent>,
+    /// 房间已满,所有房间都满时不再接受新连接
+    full: bool,
+}
+
+struct Unrouted {
+    connection: NetConnection<ServerManager>,
+    time: i64,
+}
+
+/// 进程中所有房间共用的 steam 游戏服务器和监听
+///
+/// `Server::init` 是进程全局的,第一个打开的房间创建,之后的房间共用同一个服务器身份。
+/// 新连接先放在公共的接收组里,收到握手消息后按其中的大厅ID交给对应的房间
+pub struct ServerHost {
+    /// 还没有发送握手消息的连接
+    unrouted: HashMap<u64, Unrouted>,
+    /// steam id -> 大厅ID
+    routes: HashMap<u64, u64>,
+    /// 大厅ID -> 房间
+    rooms: HashMap<u64, Room>,
+    /// 各个房间最近推送的服务器浏览器信息,按房间打开的顺序
+    details: Vec<(u64, ServerDetails)>,
+    /// 发送给新连接的服务器信息,只包含所有房间共用的字段,不包含房间的服务器名称
+    info: MsgServerSendInfo,
+    poll_group: NetPollGroup<ServerManager>,
+    listen_socket: ListenSocket<ServerManager>,
+    single: Rc<SingleClient<ServerManager>>,
+    server: Rc<Server>,
+}
+
+impl ServerHost {
+    /// 已经创建的游戏服务器
+    pub fn current() -> Option<Rc<RefCell<ServerHost>>> {
+        HOST.with(|host| host.borrow().upgrade())
+    }
+
+    /// 为已经初始化的游戏服务器创建监听,`None` 表示创建监听失败
+    pub fn create(
+        server: Server,
+        single: SingleClient<ServerManager>,
+        info: MsgServerSendInfo,
+    ) -> Option<Rc<RefCell<ServerHost>>> {
+        let sockets = server.networking_server_sockets();
+        let listen_socket = sockets.create_listen_socket_p2p(0, vec![]).ok()?;
+
+        let host = Rc::new(RefCell::new(ServerHost {
+            unrouted: HashMap::new(),
+            routes: HashMap::new(),
+            rooms: HashMap::new(),
+            details: Vec::new(),
+            info,
+            poll_group: sockets.create_poll_group(),
+            listen_socket,
+            single: Rc::new(single),
+            server: Rc::new(server),
+        }));
+        HOST.with(|current| *current.borrow_mut() = Rc::downgrade(&host));
+
+        Some(host)
+    }
+
+    pub fn server(&self) -> Rc<Server> {
+        self.server.clone()
+    }
+
+    pub fn single(&self) -> Rc<SingleClient<ServerManager>> {
+        self.single.clone()
+    }
+
+    pub fn info(&self) -> &MsgServerSendInfo {
+        &self.info
+    }
+
+    /// 把所有房间的信息合并后推送到服务器浏览器,字符串中含有空字符时返回错误
+    fn send_details_to_steam(&self) -> Result<(), NulError> {
+        if self.details.is_empty() {
+            return Ok(());
+        }
+
+        let details = ServerDetails::merge(self.details.iter().map(|(_, details)| details));
+        let browser = &details.browser;
+        let server = &self.server;
+
+        server.set_max_players(details.max_players as i32);
+        server.set_password_protected(browser.password_protected);
+        server.set_server_name(&details.server_name);
+        server.set_bot_player_count(details.bot_players as i32);
+        server.set_map_name(&details.map_name);
+
+        server.clear_all_key_values();
+        for (key, value) in browser.rules() {
+            server.set_key_value(key, value)?;
+        }
+        server.set_game_tags(&browser.game_tags())?;
+        server.set_game_data(browser.game_data())?;
+        server.set_region(&browser.region)?;
+        server.set_spectator_port(browser.spectator_port);
+        if browser.spectator_port != 0 {
+            server.set_spectator_server_name(&browser.spectator_name)?;
+        }
+        for (steam_id, player) in browser.players() {
+            server.update_user_data(SteamId::from_raw(steam_id), &player.name, player.score)?;
+        }
+
+        Ok(())
+    }
+
+    /// 处理监听事件和公共接收组中的握手消息,把结果分发给各个房间
+    fn poll(&mut self) {
+        while let Some(event) = self.listen_socket.try_receive_event() {
+            match event {
+                ListenSocketEvent::Connecting(mut request) => {
+                    let banned = request
+                        .remote()
+                        .steam_id()
+                        .is_some_and(|steam_id| is_banned(steam_id.raw()));
+
+                    if self.rooms.is_empty() {
+                        request.reject(EDisconnectReason::EDRServerClosed.into(), Some("No room"));
+                    } else if banned {
+                        #[cfg(feature = "dev")]
+                        dbg!("Rejecting connection; banned");
+
+                        request.reject(EDisconnectReason::EDRClientBanned.into(), Some("Banned"));
+                    } else if self.rooms.values().all(|room| room.full) {
+                        #[cfg(feature = "dev")]
+                        dbg!("Rejecting connection; all rooms full");
+
+                        request.reject(
+                            EDisconnectReason::EDRServerFull.into(),
+                            Some("Server full!"),
+                        );
+                    } else if request.accept().is_err() {
+                        #[cfg(feature = "dev")]
+                        dbg!("ConnectionRequest::Accept Error");
+
+                        request.reject(
+                            NetConnectionEndReason::NetConnectionEnd(
+                                NetConnectionEnd::AppException,
+                            ),
+                            Some("Failed to accept connection"),
+                        );
+                    }
+                }
+                ListenSocketEvent::Connected(connected) => {
+                    let steam_id = connected.remote().steam_id().unwrap().raw();
+                    let connection = connected.take_connection();
+                    connection.set_poll_group(&self.poll_group);
+                    if let Err(e) = connection.configure_lanes(&LANE_PRIORITIES, &LANE_WEIGHTS) {
+                        println!("failed to configure connection lanes: {:?}", e);
+                    }
+
+                    // 握手消息总是使用 MessagePack,客户端还不知道服务器支持的功能
+                    let body = CodecKind::MessagePack.encode(&self.info);
+                    for packet in encode_packet(MsgServerSendInfo::ID, &body, 0, 0) {
+                        if let Err(e) =
+                            connection.send_message(&packet, MsgServerSendInfo::SEND_FLAGS)
+                        {
+                            println!("failed to send server info: {:?}", e);
+                        }
+                    }
+
+                    self.routes.remove(&steam_id);
+                    self.unrouted.insert(
+                        steam_id,
+                        Unrouted {
+                            connection,
+                            time: now(),
+                        },
+                    );
+                }
+                ListenSocketEvent::Disconnected(disconnected) => {
+                    let remote = disconnected.remote();
+                    let steam_id = remote.steam_id().unwrap().raw();
+
+                    if self.unrouted.remove(&steam_id).is_some() {
+                        continue;
+                    }
+                    if let Some(lobby_id) = self.routes.remove(&steam_id) {
+                        if let Some(room) = self.rooms.get_mut(&lobby_id) {
+                            room.events.push_back(RoomEvent::Disconnected(remote));
+                        }
+                    }
+                }
+            }
+        }
+
+        for message in self.poll_group.receive_messages(128) {
+            let remote = message.identity_peer();
+            let steam_id = remote.steam_id().unwrap().raw();
+
+            // 握手之前的其他消息直接忽略
+            let lobby_id = match decode_packet(message.data()) {
+                Ok((EMessage::KEmsgClientBeginAuthentication, body)) => {
+                    peek_u64(&body, MsgClientBeginAuthentication::LOBBY_ID_INDEX).unwrap_or(0)
+                }
+                _ => continue,
+            };
+            let Some(unrouted) = self.unrouted.remove(&steam_id) else {
+                continue;
+            };
+
+            // 旧版本的客户端没有大厅ID,只有一个房间时直接加入
+            let lobby_id = match (lobby_id, self.rooms.len()) {
+                (0, 1) => *self.rooms.keys().next().unwrap(),
+                _ => lobby_id,
+            };
+
+            match self.rooms.get_mut(&lobby_id) {
+                Some(room) => {
+                    self.routes.insert(steam_id, lobby_id);
+                    room.events.push_back(RoomEvent::Joined(
+                        remote,
+                        unrouted.connection,
+                        message.data().to_vec(),
+                    ));
+                }
+                None => {
+                    unrouted.connection.close(
+                        EDisconnectReason::EDRServerReject.into(),
+                        Some("Unknown lobby"),
+                        false,
+                    );
+                }
+            }
+        }
+
+        let time = now();
+        self.unrouted
+            .retain(|_, f| time - f.time <= UNROUTED_TIMEOUT);
+    }
+}
+
+/// 房间在游戏服务器中的登记,释放时注销
+pub struct RoomHandle {
+    host: Rc<RefCell<ServerHost>>,
+    lobby_id: u64,
+}
+
+impl RoomHandle {
+    /// 登记一个房间,同一个大厅ID已经有房间时返回 `None`
+    pub fn join(host: Rc<RefCell<ServerHost>>, lobby_id: u64) -> Option<RoomHandle> {
+        {
+            let mut inner = host.borrow_mut();
+            if inner.rooms.contains_key(&lobby_id) {
+                return None;
+            }
+            inner.rooms.insert(lobby_id, Room::default());
+        }
+
+        Some(RoomHandle { host, lobby_id })
+    }
+
+    pub fn host(&self) -> &Rc<RefCell<ServerHost>> {
+        &self.host
+    }
+
+    /// 是否是最后一个房间,释放后游戏服务器随之关闭
+    pub fn is_last(&self) -> bool {
+        Rc::strong_count(&self.host) == 1
+    }
+
+    /// 更新本房间在服务器浏览器中的信息,和其他房间的信息合并后推送
+    pub fn send_details_to_steam(&self, details: ServerDetails) -> Result<(), NulError> {
+        let mut host = self.host.borrow_mut();
+        match host.details.iter_mut().find(|(id, _)| *id == self.lobby_id) {
+            Some((_, current)) => *current = details,
+            None => host.details.push((self.lobby_id, details)),
+        }
+        host.send_details_to_steam()
+    }
+
+    /// 更新本房间是否已满,然后取出本房间的连接事件
+    pub fn take_events(&self, full: bool) -> Vec<RoomEvent> {
+        let mut host = self.host.borrow_mut();
+        if let Some(room) = host.rooms.get_mut(&self.lobby_id) {
+            room.full = full;
+        }
+        host.poll();
+
+        host.rooms
+            .get_mut(&self.lobby_id)
+            .map(|room| room.events.drain(..).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for RoomHandle {
+    fn drop(&mut self) {
+        let mut host = self.host.borrow_mut();
+        host.rooms.remove(&self.lobby_id);
+        let lobby_id = self.lobby_id;
+        host.routes.retain(|_, f| *f != lobby_id);
+
+        // 其他房间还在时去掉本房间的信息
+        if host.details.iter().any(|(id, _)| *id == lobby_id) {
+            host.details.retain(|(id, _)| *id != lobby_id);
+            if !host.rooms.is_empty() {
+                if let Err(e) = host.send_details_to_steam() {
+                    println!("failed to send server details: {:?}", e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use crate::api::p2p::message::MsgServerFrameData;
+use std::collections::BTreeMap;
+
+/// 默认允许客户端提前提交操作的最大帧数
+pub const DEFAULT_MAX_INPUT_LEAD: u32 = 30;
+
+/// 目标帧已经下发或超出提前范围时的处理方式
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum LateInputPolicy {
+    /// 丢弃
+    Drop,
+    /// 放到最近一个可以接受的帧
+    Clamp,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum InputResult {
+    Accepted,
+    /// 目标帧不可用,已经移到 `u32` 对应的帧
+    Clamped(u32),
+    Dropped,
+}
+
+/// 按目标帧缓存客户端提交的操作
+///
+/// 取出时按帧、玩家槽位、操作类型排序,所有客户端收到的顺序一致
+pub struct InputBuffer {
+    /// 目标帧 -> 玩家槽位 -> 操作
+    frames: BTreeMap<u32, BTreeMap<u32, Vec<MsgServerFrameData>>>,
+    max_lead: u32,
+    policy: LateInputPolicy,
+}
+
+impl InputBuffer {
+    pub fn new(max_lead: u32, policy: LateInputPolicy) -> Self {
+        InputBuffer {
+            frames: BTreeMap::new(),
+            max_lead,
+            policy,
+        }
+    }
+
+    pub fn set_max_lead(&mut self, max_lead: u32) {
+        self.max_lead = max_lead;
+    }
+
+    pub fn set_policy(&mut self, policy: LateInputPolicy) {
+        self.policy = policy;
+    }
+
+    /// 放入一条操作
+    ///
+    /// `next_frame` 是下一个要下发的帧,`target` 为0时表示下一帧
+    pub fn insert(&mut self, next_frame: u32, target: u32, msg: MsgServerFrameData) -> InputResult {
+        let last_frame = next_frame.saturating_add(self.max_lead);
+
+        let (frame, result) = if target == 0 {
+            (next_frame, InputResult::Accepted)
+        } else if target < next_frame {
+            (next_frame, InputResult::Clamped(next_frame))
+        } else if target > last_frame {
+            (last_frame, InputResult::Clamped(last_frame))
+        } else {
+            (target, InputResult::Accepted)
+        };
+
+        if result != InputResult::Accepted && self.policy == LateInputPolicy::Drop {
+            return InputResult::Dropped;
+        }
+
+        let values = self
+            .frames
+            .entry(frame)
+            .or_default()
+            .entry(msg.player_position)
+            .or_default();
+
+        // 一个逻辑帧只接受同一个类型的同一个指令
+        match values.binary_search_by_key(&msg.types, |v| v.types) {
+            // 如果有了旧的数据
+            // 就用新数据覆盖
+            Ok(index) => values[index].data = msg.data,
+            // 如果没有旧数据
+            // 按类型插入
+            Err(index) => values.insert(index, msg),
+        }
+
+        result
+    }
+
+    /// 取出某一帧的所有操作,更早的残留操作一并取出
+    pub fn take(&mut self, frame_id: u32) -> Vec<MsgServerFrameData> {
+        let rest = self.frames.split_off(&(frame_id + 1));
+        let frames = std::mem::replace(&mut self.frames, rest);
+
+        frames
+            .into_values()
+            .flat_map(|players| players.into_values().flatten())
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// 玩家操作的提前量统计,每次上报后重置
+#[derive(Default)]
+pub struct InputStats {
+    /// 最近一次操作的提前帧数
+    pub last_advantage: i32,
+    advantage_sum: i64,
+    samples: u32,
+    /// 晚到的操作数量(包括被丢弃的)
+    pub late_inputs: u32,
+    /// 被丢弃的操作数量
+    pub dropped_inputs: u32,
+}
+
+impl InputStats {
+    pub fn record(&mut self, next_frame: u32, target: u32, result: InputResult) {
+        if target != 0 {
+            self.last_advantage = (i64::from(target) - i64::from(next_frame)) as i32;
+            self.advantage_sum += i64::from(self.last_advantage);
+            self.samples += 1;
+
+            if target < next_frame {
+                self.late_inputs += 1;
+            }
+        }
+
+        if result == InputResult::Dropped {
+            self.dropped_inputs += 1;
+        }
+    }
+
+    pub fn average_advantage(&self) -> f64 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+
+        self.advantage_sum as f64 / f64::from(self.samples)
+    }
+
+    pub fn reset(&mut self) {
+        *self = InputStats {
+            last_advantage: self.last_advantage,
+            ..Default::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InputBuffer, InputResult, InputStats, LateInputPolicy};
+    use crate::api::p2p::message::MsgServerFrameData;
+
+    fn input(types: u32, data: u8) -> MsgServerFrameData {
+        MsgServerFrameData {
+            types,
+            data: vec![data],
+            local_steam_id: 0,
+            player_position: 0,
+        }
+    }
+
+    #[test]
+    fn takes_inputs_in_slot_order() {
+        let mut buffer = InputBuffer::new(5, LateInputPolicy::Drop);
+
+        for (slot, types) in [(2, 1), (0, 3), (1, 1), (0, 1)] {
+            let mut msg = input(types, slot as u8);
+            msg.player_position = slot;
+            buffer.insert(10, 0, msg);
+        }
+
+        let frame: Vec<(u32, u32)> = buffer
+            .take(10)
+            .iter()
+            .map(|f| (f.player_position, f.types))
+            .collect();
+        assert_eq!(frame, vec![(0, 1), (0, 3), (1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn buckets_inputs_by_target_frame() {
+        let mut buffer = InputBuffer::new(5, LateInputPolicy::Drop);
+
+        assert_eq!(buffer.insert(10, 12, input(1, 1)), InputResult::Accepted);
+        assert_eq!(buffer.insert(10, 0, input(1, 2)), InputResult::Accepted);
+        assert_eq!(buffer.insert(10, 12, input(1, 3)), InputResult::Accepted);
+        assert_eq!(buffer.insert(10, 9, input(2, 4)), InputResult::Dropped);
+        assert_eq!(buffer.insert(10, 16, input(2, 5)), InputResult::Dropped);
+
+        let frame: Vec<u8> = buffer.take(10).iter().map(|f| f.data[0]).collect();
+        assert_eq!(frame, vec![2]);
+        assert!(buffer.take(11).is_empty());
+
+        let frame: Vec<u8> = buffer.take(12).iter().map(|f| f.data[0]).collect();
+        assert_eq!(frame, vec![3]);
+    }
+
+    #[test]
+    fn clamps_late_inputs_and_tracks_advantage() {
+        let mut buffer = InputBuffer::new(5, LateInputPolicy::Clamp);
+        let mut stats = InputStats::default();
+
+        let result = buffer.insert(10, 8, input(1, 1));
+        stats.record(10, 8, result);
+        assert_eq!(result, InputResult::Clamped(10));
+
+        let result = buffer.insert(10, 14, input(1, 2));
+        stats.record(10, 14, result);
+        assert_eq!(result, InputResult::Accepted);
+
+        assert_eq!(stats.last_advantage, 4);
+        assert_eq!(stats.average_advantage(), 1.0);
+        assert_eq!(stats.late_inputs, 1);
+        assert_eq!(stats.dropped_inputs, 0);
+
+        assert_eq!(buffer.take(10).len(), 1);
+        assert_eq!(buffer.take(14).len(), 1);
+
+        stats.reset();
+        assert_eq!(stats.late_inputs, 0);
+        assert_eq!(stats.last_advantage, 4);
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+/// 服务器的生命周期状态
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum ServerState {
+    /// 没有打开
+    #[default]
+    Idle,
+    /// 正在初始化 steam 游戏服务器
+    Initializing,
+    /// 等待登录 steam
+    LoggingOn,
+    /// 已经登录,等待玩家加入
+    Listening,
+    /// 游戏进行中
+    InGame,
+    /// 正在关闭
+    ShuttingDown,
+}
+
+impl ServerState {
+    /// 打开之后根据服务器的标志推导当前状态,没有打开或者正在关闭时保持不变
+    ///
+    /// `can_close` 表示监听已经建立,`policy_ready` 表示不需要等待或已经收到 VAC 策略
+    pub fn refresh(
+        self,
+        can_close: bool,
+        connected: bool,
+        policy_ready: bool,
+        in_game: bool,
+    ) -> Self {
+        match self {
+            ServerState::Idle | ServerState::Initializing | ServerState::ShuttingDown => self,
+            _ if in_game => ServerState::InGame,
+            _ if can_close && connected && policy_ready => ServerState::Listening,
+            _ => ServerState::LoggingOn,
+        }
+    }
+}
+
+/// 记录状态变化,由事件循环统一通知
+#[derive(Default)]
+pub struct ServerLifecycle {
+    state: ServerState,
+    changes: Vec<ServerState>,
+}
+
+impl ServerLifecycle {
+    pub fn state(&self) -> ServerState {
+        self.state
+    }
+
+    pub fn set(&mut self, state: ServerState) {
+        if self.state != state {
+            self.state = state;
+            self.changes.push(state);
+        }
+    }
+
+    pub fn take_changes(&mut self) -> Vec<ServerState> {
+        std::mem::take(&mut self.changes)
+    }
+}
+
+/// `open()` 失败的原因
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum OpenError {
+    /// 服务器已经打开
+    AlreadyOpen,
+    /// 没有设置大厅ID
+    LobbyNotSet,
+    /// 没有设置最多玩家数
+    MaxPlayersNotSet,
+    /// 服务器模式无效
+    InvalidServerMode,
+    /// `Server::init` 失败
+    InitFailed(String),
+    /// 创建监听失败
+    ListenSocketFailed,
+    /// 同一个大厅ID已经有打开的房间
+    LobbyInUse,
+    /// 和已经打开的房间使用的编码方式或版本不一致
+    HostMismatch,
+}
+
+impl OpenError {
+    /// 传给 JS 的错误码
+    pub fn code(&self) -> &'static str {
+        match self {
+            OpenError::AlreadyOpen => "AlreadyOpen",
+            OpenError::LobbyNotSet => "LobbyNotSet",
+            OpenError::MaxPlayersNotSet => "MaxPlayersNotSet",
+            OpenError::InvalidServerMode => "InvalidServerMode",
+            OpenError::InitFailed(_) => "InitFailed",
+            OpenError::ListenSocketFailed => "ListenSocketFailed",
+            OpenError::LobbyInUse => "LobbyInUse",
+            OpenError::HostMismatch => "HostMismatch",
+        }
+    }
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::AlreadyOpen => write!(f, "server is already open"),
+            OpenError::LobbyNotSet => write!(f, "lobby id is not set"),
+            OpenError::MaxPlayersNotSet => write!(f, "max players is not set"),
+            OpenError::InvalidServerMode => write!(f, "invalid server mode"),
+            OpenError::InitFailed(err) => write!(f, "steam game server init failed: {}", err),
+            OpenError::ListenSocketFailed => write!(f, "failed to create the p2p listen socket"),
+            OpenError::LobbyInUse => write!(f, "a room is already open for this lobby"),
+            OpenError::HostMismatch => write!(
+                f,
+                "codec or build differs from the rooms already open in this process"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ServerLifecycle, ServerState};
+
+    #[test]
+    fn follows_server_flags() {
+        let mut lifecycle = ServerLifecycle::default();
+        lifecycle.set(ServerState::Initializing);
+        lifecycle.set(ServerState::LoggingOn);
+
+        let state = lifecycle.state();
+        lifecycle.set(state.refresh(true, false, false, false));
+        assert_eq!(lifecycle.state(), ServerState::LoggingOn);
+
+        let state = lifecycle.state();
+        lifecycle.set(state.refresh(true, true, true, false));
+        assert_eq!(lifecycle.state(), ServerState::Listening);
+
+        let state = lifecycle.state();
+        lifecycle.set(state.refresh(true, false, true, true));
+        assert_eq!(lifecycle.state(), ServerState::InGame);
+
+        lifecycle.set(ServerState::ShuttingDown);
+        let state = lifecycle.state();
+        assert_eq!(
+            state.refresh(true, true, true, false),
+            ServerState::ShuttingDown
+        );
+        assert_eq!(
+            ServerState::Idle.refresh(true, true, true, true),
+            ServerState::Idle
+        );
+
+        assert_eq!(
+            lifecycle.take_changes(),
+            vec![
+                ServerState::Initializing,
+                ServerState::LoggingOn,
+                ServerState::Listening,
+                ServerState::InGame,
+                ServerState::ShuttingDown,
+            ]
+        );
+        assert!(lifecycle.take_changes().is_empty());
+    }
+}
@@ -1,7 +1,23 @@
-use serde::{Deserialize, Serialize};
+use crate::api::p2p::codec::RawMessage;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use steamworks::networking_types::SendFlags;
 pub const HSTEAM_NET_CONNECTION_INVALID: u32 = 0;
 /// https://partner.steamgames.com/doc/api/steamnetworkingtypes#ESteamNetConnectionEnd
 pub const ESTEAM_NET_CONNECTION_END_APP_MIN: i32 = 1000;
+/// 网络协议版本,消息格式不兼容时需要增加
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// 发送通道,同一通道内的可靠消息按顺序到达,不同通道之间互不阻塞
+pub const LANE_CONTROL: u16 = 0;
+pub const LANE_FRAMES: u16 = 1;
+pub const LANE_BROADCAST: u16 = 2;
+pub const LANE_BLOB: u16 = 3;
+/// 每个通道的优先级(越小越先发送)
+pub const LANE_PRIORITIES: [i32; 4] = [0, 0, 1, 2];
+/// 相同优先级的通道按权重分配带宽
+pub const LANE_WEIGHTS: [u16; 4] = [1, 1, 1, 1];
 
 // 网络协议定义
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
@@ -25,6 +41,11 @@ pub enum EMessage {
     KEmsgServerGameStart = EMessage::KEmsgServerBegin as isize + 7,
     KEmsgServerSetGameStartDataComplete = EMessage::KEmsgServerBegin as isize + 8,
     KEmsgServerBroadcast = EMessage::KEmsgServerBegin as isize + 9,
+    KEmsgServerResumeGame = EMessage::KEmsgServerBegin as isize + 10,
+    KEmsgServerDesync = EMessage::KEmsgServerBegin as isize + 11,
+    KEmsgServerHeartbeat = EMessage::KEmsgServerBegin as isize + 12,
+    KEmsgServerPaused = EMessage::KEmsgServerBegin as isize + 13,
+    KEmsgServerResumed = EMessage::KEmsgServerBegin as isize + 14,
 
     // 客户端信息
     KEmsgClientBegin = 500,
@@ -32,6 +53,11 @@ pub enum EMessage {
     KEmsgClientLoadComplete = EMessage::KEmsgClientBegin as isize + 3,
     KEmsgClientFrameData = EMessage::KEmsgClientBegin as isize + 4,
     KEmsgClientBroadcast = EMessage::KEmsgClientBegin as isize + 5,
+    KEmsgClientRequestFrames = EMessage::KEmsgClientBegin as isize + 6,
+    KEmsgClientChecksum = EMessage::KEmsgClientBegin as isize + 7,
+    KEmsgClientHeartbeat = EMessage::KEmsgClientBegin as isize + 8,
+    KEmsgClientRequestPause = EMessage::KEmsgClientBegin as isize + 9,
+    KEmsgClientRequestResume = EMessage::KEmsgClientBegin as isize + 10,
 
     // P2P认证信息
     KEmsgP2pbegin = 600,
@@ -39,15 +65,34 @@ pub enum EMessage {
     // 语音聊天
     KEmsgVoiceChatBegin = 700,
 
+    // 大数据传输,客户端之间的传输由服务器转发
+    KEmsgBlobBegin = 800,
+    KEmsgBlobStart = EMessage::KEmsgBlobBegin as isize + 1,
+    KEmsgBlobChunk = EMessage::KEmsgBlobBegin as isize + 2,
+    KEmsgBlobAck = EMessage::KEmsgBlobBegin as isize + 3,
+    KEmsgBlobComplete = EMessage::KEmsgBlobBegin as isize + 4,
+
     KEforceDword = 0x7fffffff,
 }
 
-enum EDisconnectReason {
+/// 服务器主动断开连接时的原因,作为 `ESteamNetConnectionEnd` 的自定义值
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum EDisconnectReason {
     EDRClientDisconnect = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 1,
     EDRServerClosed = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 2,
     EDRServerReject = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 3,
     EDRServerFull = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 4,
     EDRClientKicked = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 5,
+    EDRClientBanned = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 6,
+    EDRClientTimeout = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 7,
+    EDRVersionMismatch = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 8,
+    EDRBadMessage = ESTEAM_NET_CONNECTION_END_APP_MIN as isize + 9,
+}
+
+impl From<EDisconnectReason> for steamworks::networking_types::NetConnectionEndReason {
+    fn from(reason: EDisconnectReason) -> Self {
+        steamworks::networking_types::NetConnectionEndReason::Customed(reason as i32)
+    }
 }
 
 impl From<EMessage> for isize {
@@ -62,11 +107,8 @@ impl From<EMessage> for Vec<u8> {
     }
 }
 
-impl Into<EMessage> for Vec<u8> {
-    fn into(self) -> EMessage {
-        let bytes: [u8; 4] = self.try_into().unwrap();
-        let id = i32::from_le_bytes(bytes);
-
+impl From<i32> for EMessage {
+    fn from(id: i32) -> Self {
         match id {
             x if x == EMessage::KEmsgBegin as i32 => EMessage::KEmsgBegin,
             x if x == EMessage::KEmsgServer as i32 => EMessage::KEmsgServer,
@@ -87,6 +129,11 @@ impl Into<EMessage> for Vec<u8> {
                 EMessage::KEmsgServerSetGameStartDataComplete
             }
             x if x == EMessage::KEmsgServerBroadcast as i32 => EMessage::KEmsgServerBroadcast,
+            x if x == EMessage::KEmsgServerResumeGame as i32 => EMessage::KEmsgServerResumeGame,
+            x if x == EMessage::KEmsgServerDesync as i32 => EMessage::KEmsgServerDesync,
+            x if x == EMessage::KEmsgServerHeartbeat as i32 => EMessage::KEmsgServerHeartbeat,
+            x if x == EMessage::KEmsgServerPaused as i32 => EMessage::KEmsgServerPaused,
+            x if x == EMessage::KEmsgServerResumed as i32 => EMessage::KEmsgServerResumed,
             x if x == EMessage::KEmsgClientBegin as i32 => EMessage::KEmsgClientBegin,
             x if x == EMessage::KEmsgClientBeginAuthentication as i32 => {
                 EMessage::KEmsgClientBeginAuthentication
@@ -94,15 +141,62 @@ impl Into<EMessage> for Vec<u8> {
             x if x == EMessage::KEmsgClientLoadComplete as i32 => EMessage::KEmsgClientLoadComplete,
             x if x == EMessage::KEmsgClientFrameData as i32 => EMessage::KEmsgClientFrameData,
             x if x == EMessage::KEmsgClientBroadcast as i32 => EMessage::KEmsgClientBroadcast,
+            x if x == EMessage::KEmsgClientRequestFrames as i32 => {
+                EMessage::KEmsgClientRequestFrames
+            }
+            x if x == EMessage::KEmsgClientChecksum as i32 => EMessage::KEmsgClientChecksum,
+            x if x == EMessage::KEmsgClientHeartbeat as i32 => EMessage::KEmsgClientHeartbeat,
+            x if x == EMessage::KEmsgClientRequestPause as i32 => EMessage::KEmsgClientRequestPause,
+            x if x == EMessage::KEmsgClientRequestResume as i32 => {
+                EMessage::KEmsgClientRequestResume
+            }
             x if x == EMessage::KEmsgP2pbegin as i32 => EMessage::KEmsgP2pbegin,
             x if x == EMessage::KEmsgVoiceChatBegin as i32 => EMessage::KEmsgVoiceChatBegin,
+            x if x == EMessage::KEmsgBlobBegin as i32 => EMessage::KEmsgBlobBegin,
+            x if x == EMessage::KEmsgBlobStart as i32 => EMessage::KEmsgBlobStart,
+            x if x == EMessage::KEmsgBlobChunk as i32 => EMessage::KEmsgBlobChunk,
+            x if x == EMessage::KEmsgBlobAck as i32 => EMessage::KEmsgBlobAck,
+            x if x == EMessage::KEmsgBlobComplete as i32 => EMessage::KEmsgBlobComplete,
             _ => EMessage::Error,
         }
     }
 }
 
+/// 版本是否兼容,任意一方没有设置应用版本号时只比较协议版本
+pub fn is_version_compatible(protocol_version: u32, build: &str, local_build: &str) -> bool {
+    protocol_version == PROTOCOL_VERSION
+        && (build.is_empty() || local_build.is_empty() || build == local_build)
+}
+
+/// 读取无法完整解析的消息(例如来自更新的版本)中的协议版本
+pub fn peek_protocol_version(body: &[u8], index: usize) -> Option<u32> {
+    u32::try_from(peek_u64(body, index)?).ok()
+}
+
+/// 读取 MessagePack 消息中第 `index` 个字段的整数值
+pub fn peek_u64(body: &[u8], index: usize) -> Option<u64> {
+    let value = rmpv::decode::read_value(&mut &body[..]).ok()?;
+    value.as_array()?.get(index)?.as_u64()
+}
+
 pub trait INetMessage {
     const ID: EMessage;
+    /// 默认的发送方式
+    const SEND_FLAGS: SendFlags = SendFlags::RELIABLE_NO_NAGLE;
+    /// 默认的发送通道
+    const LANE: u16 = LANE_CONTROL;
+
+    /// 按固定的字节布局编码,只有帧数据实现,见 `codec::RawMessage`
+    fn encode_raw(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn decode_raw(_body: &[u8]) -> Option<Result<Self, String>>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 
 macro_rules! NetMessage {
@@ -111,19 +205,81 @@ macro_rules! NetMessage {
             const ID: EMessage = $enum_pattern;
         }
     };
+    ($T:ident, $enum_pattern: expr, $send_flags: expr, $lane: expr) => {
+        impl INetMessage for $T {
+            const ID: EMessage = $enum_pattern;
+            const SEND_FLAGS: SendFlags = $send_flags;
+            const LANE: u16 = $lane;
+        }
+    };
+    ($T:ident, $enum_pattern: expr, $send_flags: expr, $lane: expr, raw) => {
+        impl INetMessage for $T {
+            const ID: EMessage = $enum_pattern;
+            const SEND_FLAGS: SendFlags = $send_flags;
+            const LANE: u16 = $lane;
+
+            fn encode_raw(&self) -> Option<Vec<u8>> {
+                Some(RawMessage::to_raw(self))
+            }
+
+            fn decode_raw(body: &[u8]) -> Option<Result<Self, String>> {
+                Some(<$T as RawMessage>::from_raw(body))
+            }
+        }
+    };
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
 pub struct MsgServerSendInfo {
     pub ul_steam_idserver: u64,
     pub is_vacsecure: bool,
     pub rgch_server_name: String,
+    #[serde(default)]
+    pub protocol_version: u32,
+    /// 应用自定义的版本号
+    #[serde(default)]
+    pub build: String,
+    /// 支持的可选功能,见 `packet::SUPPORTED_FEATURES`
+    #[serde(default)]
+    pub features: u32,
+    /// 消息体的编码方式,见 `codec::CodecKind`,双方必须一致
+    #[serde(default)]
+    pub codec: u32,
 }
 NetMessage!(MsgServerSendInfo, EMessage::KEmsgServerSendInfo);
 
+impl MsgServerSendInfo {
+    /// `protocol_version` 在消息中的位置
+    pub const PROTOCOL_VERSION_INDEX: usize = 3;
+}
+
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct MsgClientBeginAuthentication {
     pub rgch_token: Vec<u8>,
+    /// 以观战者身份加入,只接收帧数据
+    #[serde(default)]
+    pub spectator: bool,
+    #[serde(default)]
+    pub protocol_version: u32,
+    /// 应用自定义的版本号
+    #[serde(default)]
+    pub build: String,
+    /// 支持的可选功能,见 `packet::SUPPORTED_FEATURES`
+    #[serde(default)]
+    pub features: u32,
+    /// 消息体的编码方式,见 `codec::CodecKind`,双方必须一致
+    #[serde(default)]
+    pub codec: u32,
+    /// 要加入的大厅ID,服务器按它把连接分给对应的房间,为0时加入唯一的房间
+    #[serde(default)]
+    pub lobby_id: u64,
+}
+
+impl MsgClientBeginAuthentication {
+    /// `protocol_version` 在消息中的位置
+    pub const PROTOCOL_VERSION_INDEX: usize = 2;
+    /// `lobby_id` 在消息中的位置
+    pub const LOBBY_ID_INDEX: usize = 6;
 }
 
 NetMessage!(
@@ -144,20 +300,146 @@ pub struct MsgClientLoadComplete;
 
 NetMessage!(MsgClientLoadComplete, EMessage::KEmsgClientLoadComplete);
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
 pub struct MsgClientFrameData {
     pub types: u32,
     pub data: Vec<u8>,
+    /// 操作生效的目标帧,为0时放到服务器的下一帧
+    #[serde(default)]
+    pub frame_id: u32,
+    /// 操作序号,服务器用来过滤重复的操作,为0时不过滤
+    #[serde(default)]
+    pub seq: u32,
+    /// 不可靠发送时附带的之前几条操作,只有一层
+    #[serde(default, deserialize_with = "deserialize_client_redundant")]
+    pub redundant: Vec<MsgClientFrameData>,
+}
+
+/// 冗余数据里不能再附带冗余数据,读到时直接报错,避免恶意数据嵌套过深导致栈溢出
+struct NoRedundant;
+
+impl<'de> Deserialize<'de> for NoRedundant {
+    fn deserialize<D: Deserializer<'de>>(_: D) -> Result<Self, D::Error> {
+        Err(D::Error::custom("nested redundant data"))
+    }
+}
+
+/// 冗余数据中的一条操作,和 `MsgClientFrameData` 的布局相同
+#[derive(Deserialize)]
+struct RedundantClientFrameData {
+    types: u32,
+    data: Vec<u8>,
+    #[serde(default)]
+    frame_id: u32,
+    #[serde(default)]
+    seq: u32,
+    #[serde(default)]
+    #[allow(dead_code)]
+    redundant: Vec<NoRedundant>,
+}
+
+fn deserialize_client_redundant<'de, D>(
+    deserializer: D,
+) -> Result<Vec<MsgClientFrameData>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Vec::<RedundantClientFrameData>::deserialize(deserializer)?
+        .into_iter()
+        .map(|f| MsgClientFrameData {
+            types: f.types,
+            data: f.data,
+            frame_id: f.frame_id,
+            seq: f.seq,
+            redundant: vec![],
+        })
+        .collect())
+}
+
+NetMessage!(
+    MsgClientFrameData,
+    EMessage::KEmsgClientFrameData,
+    SendFlags::UNRELIABLE_NO_NAGLE,
+    LANE_FRAMES,
+    raw
+);
+
+/// 请求服务器重新发送 `from..=to` 之间的帧
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgClientRequestFrames {
+    pub from: u32,
+    pub to: u32,
+}
+
+NetMessage!(MsgClientRequestFrames, EMessage::KEmsgClientRequestFrames);
+
+/// 客户端某一帧模拟状态的校验值
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgClientChecksum {
+    pub frame_id: u32,
+    pub checksum: u64,
+}
+
+NetMessage!(MsgClientChecksum, EMessage::KEmsgClientChecksum);
+
+/// 玩家之间某一帧的校验值不一致
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct MsgServerDesync {
+    pub frame_id: u32,
+    /// steam id -> 校验值
+    pub checksums: BTreeMap<u64, u64>,
+}
+
+NetMessage!(MsgServerDesync, EMessage::KEmsgServerDesync);
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgServerHeartbeat;
+
+NetMessage!(MsgServerHeartbeat, EMessage::KEmsgServerHeartbeat);
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgClientHeartbeat;
+
+NetMessage!(MsgClientHeartbeat, EMessage::KEmsgClientHeartbeat);
+
+/// 游戏暂停,`frame_id` 之后的帧在恢复之前不会下发
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgServerPaused {
+    pub frame_id: u32,
+    /// 请求暂停的玩家,为0时表示服务器
+    pub steam_id: u64,
 }
 
-NetMessage!(MsgClientFrameData, EMessage::KEmsgClientFrameData);
+NetMessage!(MsgServerPaused, EMessage::KEmsgServerPaused);
+
+/// 游戏恢复,下一帧为 `frame_id + 1`
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgServerResumed {
+    pub frame_id: u32,
+    /// 请求恢复的玩家,为0时表示服务器
+    pub steam_id: u64,
+}
+
+NetMessage!(MsgServerResumed, EMessage::KEmsgServerResumed);
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgClientRequestPause;
+
+NetMessage!(MsgClientRequestPause, EMessage::KEmsgClientRequestPause);
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgClientRequestResume;
 
-impl From<MsgClientFrameData> for MsgServerFrameData {
-    fn from(value: MsgClientFrameData) -> Self {
+NetMessage!(MsgClientRequestResume, EMessage::KEmsgClientRequestResume);
+
+impl MsgServerFrameData {
+    /// 由服务器标记发送者,不信任客户端自己填写的身份
+    pub fn from_client(value: MsgClientFrameData, steam_id: u64, player_position: u32) -> Self {
         MsgServerFrameData {
             data: value.data,
             types: value.types,
-            local_steam_id: 0,
+            local_steam_id: steam_id,
+            player_position,
         }
     }
 }
@@ -167,6 +449,8 @@ pub struct MsgServerFrameData {
     pub types: u32,
     pub data: Vec<u8>,
     pub local_steam_id: u64,
+    #[serde(default)]
+    pub player_position: u32,
 }
 
 NetMessage!(MsgServerFrameData, EMessage::KEmsgServerFrameData);
@@ -178,7 +462,12 @@ pub struct MsgClientDataBroadcast {
     pub local_steam_id: u64,
 }
 
-NetMessage!(MsgClientDataBroadcast, EMessage::KEmsgClientBroadcast);
+NetMessage!(
+    MsgClientDataBroadcast,
+    EMessage::KEmsgClientBroadcast,
+    SendFlags::RELIABLE,
+    LANE_BROADCAST
+);
 
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct MsgServerDataBroadcast {
@@ -187,7 +476,12 @@ pub struct MsgServerDataBroadcast {
     pub local_steam_id: u64,
 }
 
-NetMessage!(MsgServerDataBroadcast, EMessage::KEmsgServerBroadcast);
+NetMessage!(
+    MsgServerDataBroadcast,
+    EMessage::KEmsgServerBroadcast,
+    SendFlags::RELIABLE,
+    LANE_BROADCAST
+);
 
 impl From<MsgClientDataBroadcast> for MsgServerDataBroadcast {
     fn from(value: MsgClientDataBroadcast) -> Self {
@@ -204,15 +498,58 @@ pub struct MsgServerGameStart {
     pub game_data: Vec<MsgServerFrameData>,
     pub buffer_size: u32,
 }
-NetMessage!(MsgServerGameStart, EMessage::KEmsgServerGameStart);
+// 和帧数据使用同一个通道,保证开局数据先于第一帧到达
+NetMessage!(
+    MsgServerGameStart,
+    EMessage::KEmsgServerGameStart,
+    SendFlags::RELIABLE_NO_NAGLE,
+    LANE_FRAMES
+);
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
 pub struct MsgServerFramesData {
     pub game_data: Vec<MsgServerFrameData>,
     pub buffer_size: u32,
     pub frame_id: u32,
+    /// 不可靠发送时附带的之前几帧,只有一层
+    #[serde(default, deserialize_with = "deserialize_server_redundant")]
+    pub redundant: Vec<MsgServerFramesData>,
+}
+
+/// 冗余数据中的一帧,和 `MsgServerFramesData` 的布局相同
+#[derive(Deserialize)]
+struct RedundantServerFramesData {
+    game_data: Vec<MsgServerFrameData>,
+    buffer_size: u32,
+    frame_id: u32,
+    #[serde(default)]
+    #[allow(dead_code)]
+    redundant: Vec<NoRedundant>,
 }
-NetMessage!(MsgServerFramesData, EMessage::KEmsgServerFramesData);
+
+fn deserialize_server_redundant<'de, D>(
+    deserializer: D,
+) -> Result<Vec<MsgServerFramesData>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Vec::<RedundantServerFramesData>::deserialize(deserializer)?
+        .into_iter()
+        .map(|f| MsgServerFramesData {
+            game_data: f.game_data,
+            buffer_size: f.buffer_size,
+            frame_id: f.frame_id,
+            redundant: vec![],
+        })
+        .collect())
+}
+NetMessage!(
+    MsgServerFramesData,
+    EMessage::KEmsgServerFramesData,
+    SendFlags::UNRELIABLE_NO_NAGLE,
+    LANE_FRAMES,
+    raw
+);
 
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct MsgServerPassAuthentication {
@@ -234,3 +571,154 @@ NetMessage!(
     MsgSetGameStartDataComplete,
     EMessage::KEmsgServerSetGameStartDataComplete
 );
+
+/// 断线重连成功后发送给客户端,随后会补发 `from_frame_id..=to_frame_id` 之间的帧数据
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct MsgServerResumeGame {
+    pub player_position: u32,
+    pub from_frame_id: u32,
+    pub to_frame_id: u32,
+}
+NetMessage!(MsgServerResumeGame, EMessage::KEmsgServerResumeGame);
+
+/// 开始传输,超时没有收到确认时重新发送,接收方回复已经收到的字节数
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct MsgBlobStart {
+    pub transfer_id: u32,
+    /// 接收方,为服务器的ID时由服务器接收
+    pub target: u64,
+    /// 发送方,由服务器转发时改为实际的发送方
+    pub source: u64,
+    pub size: u32,
+    #[serde(with = "serde_bytes")]
+    pub sha256: Vec<u8>,
+    pub meta: String,
+}
+NetMessage!(
+    MsgBlobStart,
+    EMessage::KEmsgBlobStart,
+    SendFlags::RELIABLE_NO_NAGLE,
+    LANE_BLOB
+);
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct MsgBlobChunk {
+    pub transfer_id: u32,
+    pub target: u64,
+    pub source: u64,
+    pub offset: u32,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+NetMessage!(
+    MsgBlobChunk,
+    EMessage::KEmsgBlobChunk,
+    SendFlags::RELIABLE,
+    LANE_BLOB
+);
+
+/// 接收方已经按顺序收到的字节数
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct MsgBlobAck {
+    pub transfer_id: u32,
+    pub target: u64,
+    pub source: u64,
+    pub received: u32,
+}
+NetMessage!(
+    MsgBlobAck,
+    EMessage::KEmsgBlobAck,
+    SendFlags::RELIABLE_NO_NAGLE,
+    LANE_BLOB
+);
+
+/// 传输结束,`error` 为空时校验通过
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct MsgBlobComplete {
+    pub transfer_id: u32,
+    pub target: u64,
+    pub source: u64,
+    pub error: String,
+}
+NetMessage!(
+    MsgBlobComplete,
+    EMessage::KEmsgBlobComplete,
+    SendFlags::RELIABLE_NO_NAGLE,
+    LANE_BLOB
+);
+
+#[cfg(test)]
+mod test {
+    use super::{
+        is_version_compatible, peek_protocol_version, peek_u64, MsgClientBeginAuthentication,
+        MsgClientFrameData, MsgServerFrameData, PROTOCOL_VERSION,
+    };
+    use serde::Serialize;
+
+    #[test]
+    fn reads_version_from_newer_messages() {
+        // 更新的版本在末尾追加了字段
+        let newer = (
+            vec![1u8, 2, 3],
+            false,
+            PROTOCOL_VERSION + 1,
+            "2.0",
+            3u32,
+            0u32,
+            7u64,
+            42u32,
+        );
+        let mut bytes = Vec::new();
+        newer
+            .serialize(&mut rmps::Serializer::new(&mut bytes))
+            .unwrap();
+
+        assert!(rmps::from_slice::<MsgClientBeginAuthentication>(&bytes).is_err());
+        assert_eq!(
+            peek_protocol_version(&bytes, MsgClientBeginAuthentication::PROTOCOL_VERSION_INDEX),
+            Some(PROTOCOL_VERSION + 1)
+        );
+        assert_eq!(
+            peek_u64(&bytes, MsgClientBeginAuthentication::LOBBY_ID_INDEX),
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn older_messages_default_to_version_zero() {
+        let older = (vec![1u8, 2, 3],);
+        let mut bytes = Vec::new();
+        older
+            .serialize(&mut rmps::Serializer::new(&mut bytes))
+            .unwrap();
+
+        let msg = rmps::from_slice::<MsgClientBeginAuthentication>(&bytes).unwrap();
+        assert_eq!(msg.protocol_version, 0);
+        assert!(!is_version_compatible(msg.protocol_version, &msg.build, ""));
+        assert!(is_version_compatible(PROTOCOL_VERSION, "1.0", ""));
+        assert!(!is_version_compatible(PROTOCOL_VERSION, "1.0", "1.1"));
+    }
+
+    #[test]
+    fn server_stamps_frame_sender() {
+        let input = MsgClientFrameData {
+            types: 4,
+            data: vec![1, 2],
+            frame_id: 10,
+            seq: 1,
+            redundant: vec![],
+        };
+
+        let frame = MsgServerFrameData::from_client(input, 76561198000000001, 2);
+        assert_eq!(frame.types, 4);
+        assert_eq!(frame.local_steam_id, 76561198000000001);
+        assert_eq!(frame.player_position, 2);
+
+        // 旧版本的录像没有玩家位置
+        let older = (4u32, vec![1u8, 2], 7u64);
+        let bytes = rmps::to_vec(&older).unwrap();
+        let frame = rmps::from_slice::<MsgServerFrameData>(&bytes).unwrap();
+        assert_eq!(frame.local_steam_id, 7);
+        assert_eq!(frame.player_position, 0);
+    }
+}
@@ -1,3 +1,16 @@
+pub mod browser;
 pub mod client;
+pub mod codec;
+pub mod desync;
+pub mod history;
+pub mod host;
+pub mod input;
+pub mod lifecycle;
 pub mod message;
+pub mod packet;
+pub mod redundancy;
+pub mod replay;
 pub mod server;
+pub mod slot;
+pub mod transfer;
+pub mod validation;
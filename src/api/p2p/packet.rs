@@ -0,0 +1,465 @@
+use crate::api::p2p::message::{EMessage, INetMessage};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// 消息头(`EMessage` 的编号)的字节数
+pub const HEADER_SIZE: usize = 4;
+/// 消息头中的标记位,消息体经过LZ4压缩
+pub const FLAG_COMPRESSED: i32 = 1 << 30;
+/// 消息头中的标记位,这是一条大消息的其中一个分片
+pub const FLAG_CHUNK: i32 = 1 << 29;
+const FLAGS_MASK: i32 = FLAG_COMPRESSED | FLAG_CHUNK;
+
+/// 握手时交换的可选功能,双方都支持时才会使用
+pub const FEATURE_COMPRESSION: u32 = 1;
+pub const FEATURE_CHUNKING: u32 = 1 << 1;
+pub const SUPPORTED_FEATURES: u32 = FEATURE_COMPRESSION | FEATURE_CHUNKING;
+
+/// 默认超过多少字节的消息体会被压缩
+pub const DEFAULT_COMPRESSION_THRESHOLD: u32 = 1024;
+/// Steam单条消息的上限 k_cbMaxSteamNetworkingSocketsMessageSizeSend
+pub const MAX_MESSAGE_SIZE: usize = 512 * 1024;
+/// 分片头: 消息头 + 分片编号(u32) + 序号(u16) + 分片数(u16)
+const CHUNK_HEADER_SIZE: usize = HEADER_SIZE + 8;
+/// 解压或拼接后的数据上限,避免对方让我们分配过多内存
+pub const MAX_PAYLOAD_SIZE: usize = 64 * 1024 * 1024;
+/// 每个连接同时拼接的消息上限,不同通道上的大消息可能交错到达
+pub const MAX_PARTIAL_PER_PEER: usize = 4;
+
+static NEXT_TRANSFER_ID: AtomicU32 = AtomicU32::new(1);
+
+/// 无法解析的网络消息
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum DecodeError {
+    /// 不足一个消息头,`usize` 为收到的字节数
+    TooShort(usize),
+    /// 未知的消息编号
+    UnknownMessage(i32),
+    /// 不应该由对方发送的消息
+    Unexpected(EMessage),
+    /// 消息体不是合法的 MessagePack
+    Malformed(EMessage, String),
+    /// 压缩或分片的数据无法还原
+    Corrupted(EMessage, String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::TooShort(len) => write!(f, "packet too short ({} bytes)", len),
+            DecodeError::UnknownMessage(id) => write!(f, "unknown message id {}", id),
+            DecodeError::Unexpected(msg) => write!(f, "unexpected message {:?}", msg),
+            DecodeError::Malformed(msg, err) => write!(f, "malformed {:?}: {}", msg, err),
+            DecodeError::Corrupted(msg, err) => write!(f, "corrupted {:?}: {}", msg, err),
+        }
+    }
+}
+
+fn read_header(data: &[u8]) -> Result<(i32, &[u8]), DecodeError> {
+    if data.len() < HEADER_SIZE {
+        return Err(DecodeError::TooShort(data.len()));
+    }
+
+    let (header, body) = data.split_at(HEADER_SIZE);
+    Ok((
+        i32::from_le_bytes([header[0], header[1], header[2], header[3]]),
+        body,
+    ))
+}
+
+fn message_id(id: i32, flags: i32) -> Result<EMessage, DecodeError> {
+    match EMessage::from(id & !flags) {
+        EMessage::Error => Err(DecodeError::UnknownMessage(id)),
+        msg => Ok(msg),
+    }
+}
+
+/// 数据是分片时返回分片所属的消息
+pub fn chunk_message(data: &[u8]) -> Option<EMessage> {
+    let (id, _) = read_header(data).ok()?;
+    if id & FLAG_CHUNK == 0 {
+        return None;
+    }
+    message_id(id, FLAG_CHUNK).ok()
+}
+
+/// 拆分消息头和消息体
+pub fn decode_header(data: &[u8]) -> Result<(EMessage, &[u8]), DecodeError> {
+    let (id, body) = read_header(data)?;
+    Ok((message_id(id, 0)?, body))
+}
+
+/// 拆分消息头和消息体,消息体被压缩时先解压
+pub fn decode_packet(data: &[u8]) -> Result<(EMessage, Cow<'_, [u8]>), DecodeError> {
+    let (id, body) = read_header(data)?;
+    let msg = message_id(id, FLAGS_MASK)?;
+
+    if id & FLAG_CHUNK != 0 {
+        return Err(DecodeError::Corrupted(msg, "unassembled chunk".to_string()));
+    }
+
+    if id & FLAG_COMPRESSED == 0 {
+        return Ok((msg, Cow::Borrowed(body)));
+    }
+
+    decompress(body)
+        .map(|body| (msg, Cow::Owned(body)))
+        .map_err(|err| DecodeError::Corrupted(msg, err))
+}
+
+fn decompress(body: &[u8]) -> Result<Vec<u8>, String> {
+    if body.len() < 4 {
+        return Err("missing size prefix".to_string());
+    }
+
+    let size = u32::from_le_bytes([body[0], body[1], body[2], body[3]]) as usize;
+    if size > MAX_PAYLOAD_SIZE {
+        return Err(format!("decompressed size {} too large", size));
+    }
+
+    lz4_flex::block::decompress(&body[4..], size).map_err(|err| err.to_string())
+}
+
+/// 编码要发送的消息
+///
+/// `features` 为双方都支持的功能,消息体不小于 `threshold` 字节时压缩(为0时不压缩),
+/// 超过 `MAX_MESSAGE_SIZE` 时拆分为多个分片,分片需要可靠发送
+pub fn encode_packet(msg: EMessage, body: &[u8], features: u32, threshold: u32) -> Vec<Vec<u8>> {
+    let mut id = msg as i32;
+    let mut payload = Cow::Borrowed(body);
+
+    if features & FEATURE_COMPRESSION != 0 && threshold > 0 && body.len() >= threshold as usize {
+        let compressed = lz4_flex::block::compress_prepend_size(body);
+        // 压缩后没有变小时直接发送原数据
+        if compressed.len() < body.len() {
+            id |= FLAG_COMPRESSED;
+            payload = Cow::Owned(compressed);
+        }
+    }
+
+    let mut packet = Vec::with_capacity(HEADER_SIZE + payload.len());
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&payload);
+
+    if packet.len() <= MAX_MESSAGE_SIZE || features & FEATURE_CHUNKING == 0 {
+        return vec![packet];
+    }
+
+    let transfer_id = NEXT_TRANSFER_ID.fetch_add(1, Ordering::Relaxed);
+    let pieces = packet.chunks(MAX_MESSAGE_SIZE - CHUNK_HEADER_SIZE);
+    let count = pieces.len() as u16;
+
+    pieces
+        .enumerate()
+        .map(|(index, piece)| {
+            let mut chunk = Vec::with_capacity(CHUNK_HEADER_SIZE + piece.len());
+            chunk.extend_from_slice(&(msg as i32 | FLAG_CHUNK).to_le_bytes());
+            chunk.extend_from_slice(&transfer_id.to_le_bytes());
+            chunk.extend_from_slice(&(index as u16).to_le_bytes());
+            chunk.extend_from_slice(&count.to_le_bytes());
+            chunk.extend_from_slice(piece);
+            chunk
+        })
+        .collect()
+}
+
+struct PartialPacket {
+    count: u16,
+    next: u16,
+    data: Vec<u8>,
+}
+
+/// 按连接拼接收到的分片
+///
+/// 同一条消息的分片在同一个通道上可靠发送,按顺序到达
+#[derive(Default)]
+pub struct ChunkAssembler {
+    partial: HashMap<(u64, u32), PartialPacket>,
+}
+
+impl ChunkAssembler {
+    /// 放入收到的数据,不是分片时原样返回,分片收齐后返回拼好的数据
+    pub fn push<'a>(
+        &mut self,
+        steam_id: u64,
+        data: &'a [u8],
+    ) -> Result<Option<Cow<'a, [u8]>>, DecodeError> {
+        let (id, body) = read_header(data)?;
+        if id & FLAG_CHUNK == 0 {
+            return Ok(Some(Cow::Borrowed(data)));
+        }
+
+        let msg = message_id(id, FLAG_CHUNK)?;
+        if body.len() < CHUNK_HEADER_SIZE - HEADER_SIZE {
+            return Err(DecodeError::Corrupted(
+                msg,
+                "chunk header too short".to_string(),
+            ));
+        }
+
+        let transfer_id = u32::from_le_bytes([body[0], body[1], body[2], body[3]]);
+        let index = u16::from_le_bytes([body[4], body[5]]);
+        let count = u16::from_le_bytes([body[6], body[7]]);
+        let piece = &body[8..];
+
+        let key = (steam_id, transfer_id);
+        if !self.partial.contains_key(&key) {
+            let open = self
+                .partial
+                .keys()
+                .filter(|(id, _)| *id == steam_id)
+                .count();
+            if open >= MAX_PARTIAL_PER_PEER {
+                return Err(DecodeError::Corrupted(
+                    msg,
+                    format!("too many chunked messages ({})", open),
+                ));
+            }
+        }
+
+        let partial = self.partial.entry(key).or_insert_with(|| PartialPacket {
+            count,
+            next: 0,
+            data: Vec::new(),
+        });
+
+        if index != partial.next
+            || count != partial.count
+            || partial.data.len() + piece.len() > MAX_PAYLOAD_SIZE
+        {
+            self.partial.remove(&key);
+            return Err(DecodeError::Corrupted(
+                msg,
+                format!("unexpected chunk {} of {}", index, count),
+            ));
+        }
+
+        partial.data.extend_from_slice(piece);
+        partial.next += 1;
+        if partial.next < partial.count {
+            return Ok(None);
+        }
+
+        Ok(self.partial.remove(&key).map(|f| Cow::Owned(f.data)))
+    }
+
+    /// 丢弃连接未收齐的分片
+    pub fn remove(&mut self, steam_id: u64) {
+        self.partial.retain(|(id, _), _| *id != steam_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.partial.clear();
+    }
+}
+
+/// 解析消息体
+pub fn decode_body<T>(body: &[u8]) -> Result<T, DecodeError>
+where
+    T: INetMessage + DeserializeOwned,
+{
+    rmps::from_slice::<T>(body).map_err(|err| DecodeError::Malformed(T::ID, err.to_string()))
+}
+
+/// 每个连接累计的解析错误次数
+#[derive(Default)]
+pub struct DecodeErrors {
+    counts: HashMap<u64, u32>,
+    /// 超过后断开连接,为0时不断开
+    max_errors: u32,
+}
+
+impl DecodeErrors {
+    pub fn set_max_errors(&mut self, max_errors: u32) {
+        self.max_errors = max_errors;
+    }
+
+    /// 记录一次错误,返回累计次数以及是否需要断开
+    pub fn record(&mut self, steam_id: u64) -> (u32, bool) {
+        let count = self.counts.entry(steam_id).or_insert(0);
+        *count += 1;
+
+        (*count, self.max_errors > 0 && *count >= self.max_errors)
+    }
+
+    pub fn get(&self, steam_id: u64) -> u32 {
+        self.counts.get(&steam_id).copied().unwrap_or(0)
+    }
+
+    pub fn remove(&mut self, steam_id: u64) {
+        self.counts.remove(&steam_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        chunk_message, decode_body, decode_header, decode_packet, encode_packet, ChunkAssembler,
+        DecodeError, DecodeErrors, MAX_MESSAGE_SIZE, MAX_PARTIAL_PER_PEER, SUPPORTED_FEATURES,
+    };
+    use crate::api::p2p::message::{EMessage, MsgClientChecksum, MsgClientFrameData};
+
+    /// 简单的伪随机数,保证每次运行的输入一致
+    fn next(seed: &mut u64) -> u64 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert_eq!(decode_header(&[]), Err(DecodeError::TooShort(0)));
+        assert_eq!(decode_header(&[1, 0, 0]), Err(DecodeError::TooShort(3)));
+        assert_eq!(
+            decode_header(&[0xff, 0xff, 0xff, 0xff]),
+            Err(DecodeError::UnknownMessage(-1))
+        );
+        assert_eq!(
+            decode_header(&[0x39, 0x30, 0, 0]),
+            Err(DecodeError::UnknownMessage(12345))
+        );
+
+        let mut data: Vec<u8> = EMessage::KEmsgClientChecksum.into();
+        data.extend_from_slice(&[1, 2]);
+        assert_eq!(
+            decode_header(&data),
+            Ok((EMessage::KEmsgClientChecksum, &[1u8, 2][..]))
+        );
+    }
+
+    #[test]
+    fn random_bytes_never_panic() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+
+        for _ in 0..10_000 {
+            let len = (next(&mut seed) % 48) as usize;
+            let mut data: Vec<u8> = (0..len).map(|_| next(&mut seed) as u8).collect();
+
+            // 一半的输入使用合法的消息头,让消息体也被解析
+            if len >= 4 && next(&mut seed).is_multiple_of(2) {
+                data[..4].copy_from_slice(&(EMessage::KEmsgClientFrameData as i32).to_le_bytes());
+            }
+
+            if let Ok((_, body)) = decode_header(&data) {
+                let _ = decode_body::<MsgClientFrameData>(body);
+                let _ = decode_body::<MsgClientChecksum>(body);
+            }
+
+            // 带有压缩或分片标记的消息头
+            if len >= 4 {
+                data[3] |= (next(&mut seed) % 4) as u8 * 0x20;
+            }
+            let _ = decode_packet(&data);
+            let _ = ChunkAssembler::default().push(1, &data);
+        }
+
+        assert!(matches!(
+            decode_body::<MsgClientChecksum>(&[0xc1]),
+            Err(DecodeError::Malformed(EMessage::KEmsgClientChecksum, _))
+        ));
+    }
+
+    #[test]
+    fn counts_errors_per_peer() {
+        let mut errors = DecodeErrors::default();
+        assert_eq!(errors.record(1), (1, false));
+
+        errors.set_max_errors(2);
+        assert_eq!(errors.record(2), (1, false));
+        assert_eq!(errors.record(1), (2, true));
+
+        errors.remove(1);
+        assert_eq!(errors.get(1), 0);
+        assert_eq!(errors.get(2), 1);
+    }
+
+    #[test]
+    fn compresses_and_chunks_large_messages() {
+        let body: Vec<u8> = (0..2_000_000u32).map(|i| (i / 1000) as u8).collect();
+
+        // 对方不支持时原样发送
+        let packets = encode_packet(EMessage::KEmsgServerGameStart, &body, 0, 1024);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(
+            decode_packet(&packets[0]).unwrap(),
+            (EMessage::KEmsgServerGameStart, body.as_slice().into())
+        );
+
+        // 小消息不压缩
+        let packets = encode_packet(
+            EMessage::KEmsgServerGameStart,
+            &body[..10],
+            SUPPORTED_FEATURES,
+            1024,
+        );
+        assert_eq!(packets[0].len(), 14);
+
+        let packets = encode_packet(
+            EMessage::KEmsgServerGameStart,
+            &body,
+            SUPPORTED_FEATURES,
+            1024,
+        );
+        assert_eq!(packets.len(), 1);
+        assert!(packets[0].len() < body.len());
+        assert_eq!(decode_packet(&packets[0]).unwrap().1, body.as_slice());
+
+        // 无法压缩的数据超过上限时拆分
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        let noise: Vec<u8> = (0..1_200_000).map(|_| next(&mut seed) as u8).collect();
+        let packets = encode_packet(
+            EMessage::KEmsgServerBroadcast,
+            &noise,
+            SUPPORTED_FEATURES,
+            1024,
+        );
+        assert_eq!(packets.len(), 3);
+        assert!(packets.iter().all(|p| p.len() <= MAX_MESSAGE_SIZE));
+
+        let mut chunks = ChunkAssembler::default();
+        assert_eq!(chunks.push(1, &packets[0]), Ok(None));
+        // 其他连接的分片互不影响
+        assert!(chunks.push(2, &packets[1]).is_err());
+        assert_eq!(
+            chunk_message(&packets[1]),
+            Some(EMessage::KEmsgServerBroadcast)
+        );
+        assert_eq!(chunks.push(1, &packets[1]), Ok(None));
+        let packet = chunks.push(1, &packets[2]).unwrap().unwrap();
+        assert_eq!(
+            decode_packet(&packet).unwrap(),
+            (EMessage::KEmsgServerBroadcast, noise.as_slice().into())
+        );
+
+        // 没有拼接的分片不能直接解析
+        assert!(matches!(
+            decode_packet(&packets[0]),
+            Err(DecodeError::Corrupted(EMessage::KEmsgServerBroadcast, _))
+        ));
+
+        // 每个连接同时拼接的消息有上限
+        for i in 0..=MAX_PARTIAL_PER_PEER {
+            let packets = encode_packet(
+                EMessage::KEmsgServerBroadcast,
+                &noise,
+                SUPPORTED_FEATURES,
+                1024,
+            );
+            assert_eq!(
+                chunks.push(3, &packets[0]).is_err(),
+                i == MAX_PARTIAL_PER_PEER
+            );
+        }
+        assert_eq!(chunks.push(4, &packets[0]), Ok(None));
+        chunks.remove(3);
+        assert_eq!(chunks.push(3, &packets[0]), Ok(None));
+    }
+}
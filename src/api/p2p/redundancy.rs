@@ -0,0 +1,309 @@
+use crate::api::p2p::message::MsgServerFramesData;
+use std::collections::BTreeMap;
+
+/// 客户端每条操作最多附带之前的操作数量,服务器丢弃更早的部分
+pub const MAX_INPUT_REDUNDANCY: usize = 8;
+/// 客户端默认每条操作附带之前的操作数量
+pub const DEFAULT_INPUT_REDUNDANCY: u32 = 3;
+/// 服务器默认每一帧附带之前的帧数
+pub const DEFAULT_FRAME_REDUNDANCY: u32 = 3;
+/// 每秒最多补发给一个客户端的帧数
+pub const MAX_REQUESTED_FRAMES: u32 = 600;
+/// 补发时一条消息最多打包的帧数
+pub const RESEND_BATCH_FRAMES: usize = 64;
+/// 补发时一条消息打包的帧数据大小,超过后开始下一条
+pub const RESEND_BATCH_BYTES: u32 = 64 * 1024;
+
+/// 把补发的帧打包成几条消息,每条消息的帧放在 `redundant` 中,`frame_id` 为0
+pub fn pack_resent_frames<'a>(
+    frames: impl IntoIterator<Item = &'a MsgServerFramesData>,
+) -> Vec<MsgServerFramesData> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut size = 0;
+
+    for frame in frames {
+        if !batch.is_empty()
+            && (batch.len() >= RESEND_BATCH_FRAMES || size + frame.buffer_size > RESEND_BATCH_BYTES)
+        {
+            batches.push(resent_batch(std::mem::take(&mut batch)));
+            size = 0;
+        }
+
+        size += frame.buffer_size;
+        batch.push(frame.clone());
+    }
+
+    if !batch.is_empty() {
+        batches.push(resent_batch(batch));
+    }
+
+    batches
+}
+
+fn resent_batch(frames: Vec<MsgServerFramesData>) -> MsgServerFramesData {
+    MsgServerFramesData {
+        game_data: vec![],
+        buffer_size: 0,
+        frame_id: 0,
+        redundant: frames,
+    }
+}
+
+/// 客户端按帧ID排序并去重收到的帧
+///
+/// 不可靠发送的帧会附带之前的几帧,丢包时由后面的帧补上,
+/// 补不上的部分向服务器请求补发
+#[derive(Default)]
+pub struct FrameSequencer {
+    /// 下一个要交给游戏的帧,为0时还没有收到冗余打包的帧
+    next: u32,
+    /// 已经收到但是前面还有缺失的帧
+    pending: BTreeMap<u32, MsgServerFramesData>,
+    /// 已经请求补发到的帧
+    requested_to: u32,
+}
+
+impl FrameSequencer {
+    /// 放入一个冗余打包的帧,返回可以按顺序交给游戏的帧
+    ///
+    /// `last_frame_id` 为已经交给游戏的最新一帧
+    pub fn push_packed(
+        &mut self,
+        mut frame: MsgServerFramesData,
+        last_frame_id: u32,
+    ) -> Vec<MsgServerFramesData> {
+        let redundant = std::mem::take(&mut frame.redundant);
+
+        if self.next == 0 {
+            let first = redundant.first().map_or(frame.frame_id, |f| f.frame_id);
+            self.next = if last_frame_id >= first {
+                last_frame_id + 1
+            } else {
+                first
+            };
+        }
+
+        for f in redundant.into_iter().chain(std::iter::once(frame)) {
+            self.insert(f);
+        }
+
+        self.drain()
+    }
+
+    /// 放入一个普通的帧(例如补发的帧)
+    ///
+    /// 不在序列中的帧(更早的帧)原样返回,由调用方直接交给游戏
+    pub fn push(
+        &mut self,
+        frame: MsgServerFramesData,
+    ) -> Result<Vec<MsgServerFramesData>, MsgServerFramesData> {
+        if self.next == 0 || frame.frame_id < self.next {
+            return Err(frame);
+        }
+
+        self.insert(frame);
+        Ok(self.drain())
+    }
+
+    /// 返回需要向服务器请求补发的帧范围,同一段只请求一次
+    pub fn missing(&mut self) -> Option<(u32, u32)> {
+        let first = *self.pending.keys().next()?;
+        let from = self.next.max(self.requested_to + 1);
+        if from >= first {
+            return None;
+        }
+
+        self.requested_to = first - 1;
+        Some((from, first - 1))
+    }
+
+    pub fn reset(&mut self) {
+        *self = FrameSequencer::default();
+    }
+
+    fn insert(&mut self, frame: MsgServerFramesData) {
+        if frame.frame_id >= self.next {
+            self.pending.entry(frame.frame_id).or_insert(frame);
+        }
+    }
+
+    fn drain(&mut self) -> Vec<MsgServerFramesData> {
+        let mut frames = Vec::new();
+        while let Some(frame) = self.pending.remove(&self.next) {
+            frames.push(frame);
+            self.next += 1;
+        }
+
+        frames
+    }
+}
+
+/// 服务器过滤重复收到的操作,记录最近64个序号
+#[derive(Default)]
+pub struct SeqWindow {
+    last: u32,
+    /// 第 `n` 位表示 `last - n` 已经收到
+    mask: u64,
+}
+
+impl SeqWindow {
+    /// 第一次收到 `seq` 时返回true,`seq` 为0表示没有序号
+    pub fn accept(&mut self, seq: u32) -> bool {
+        if seq == 0 {
+            return true;
+        }
+
+        if seq > self.last {
+            let shift = seq - self.last;
+            self.mask = if shift >= 64 { 0 } else { self.mask << shift };
+            self.mask |= 1;
+            self.last = seq;
+            return true;
+        }
+
+        let offset = self.last - seq;
+        if offset >= 64 || self.mask & (1 << offset) != 0 {
+            return false;
+        }
+
+        self.mask |= 1 << offset;
+        true
+    }
+}
+
+/// 限制客户端请求补发的帧数,每秒最多 `MAX_REQUESTED_FRAMES` 帧
+#[derive(Default)]
+pub struct FrameRequestLimit {
+    /// 当前统计周期开始的时间
+    since: i64,
+    /// 周期内已经补发的帧数
+    sent: u32,
+}
+
+impl FrameRequestLimit {
+    /// 返回本次可以补发的 `from..=to` 范围,超出额度的部分丢弃
+    pub fn allow(&mut self, from: u32, to: u32, now: i64) -> Option<(u32, u32)> {
+        if from > to {
+            return None;
+        }
+
+        if now - self.since >= 1000 {
+            self.since = now;
+            self.sent = 0;
+        }
+
+        let remaining = MAX_REQUESTED_FRAMES - self.sent;
+        if remaining == 0 {
+            return None;
+        }
+
+        let to = to.min(from.saturating_add(remaining - 1));
+        self.sent += to - from + 1;
+        Some((from, to))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        pack_resent_frames, FrameRequestLimit, FrameSequencer, SeqWindow, MAX_REQUESTED_FRAMES,
+        RESEND_BATCH_BYTES, RESEND_BATCH_FRAMES,
+    };
+    use crate::api::p2p::message::MsgServerFramesData;
+
+    fn frame(frame_id: u32, redundant: &[u32]) -> MsgServerFramesData {
+        MsgServerFramesData {
+            game_data: vec![],
+            buffer_size: 0,
+            frame_id,
+            redundant: redundant.iter().map(|id| frame(*id, &[])).collect(),
+        }
+    }
+
+    fn ids(frames: Vec<MsgServerFramesData>) -> Vec<u32> {
+        frames.iter().map(|f| f.frame_id).collect()
+    }
+
+    #[test]
+    fn orders_and_deduplicates_packed_frames() {
+        let mut sequencer = FrameSequencer::default();
+
+        // 第1帧没有冗余,已经直接交给游戏
+        assert!(sequencer.push(frame(1, &[])).is_err());
+        assert_eq!(ids(sequencer.push_packed(frame(2, &[1]), 1)), vec![2]);
+        // 第3帧丢失,由第4帧补上
+        assert_eq!(ids(sequencer.push_packed(frame(4, &[2, 3]), 2)), vec![3, 4]);
+        assert!(sequencer.push_packed(frame(4, &[2, 3]), 4).is_empty());
+
+        // 第5到7帧全部丢失
+        assert!(sequencer.push_packed(frame(10, &[8, 9]), 4).is_empty());
+        assert_eq!(sequencer.missing(), Some((5, 7)));
+        assert_eq!(sequencer.missing(), None);
+
+        assert_eq!(ids(sequencer.push(frame(5, &[])).unwrap()), vec![5]);
+        assert_eq!(
+            ids(sequencer.push_packed(frame(11, &[6, 7]), 5)),
+            vec![6, 7, 8, 9, 10, 11]
+        );
+
+        // 更早的帧由调用方处理
+        assert!(sequencer.push(frame(3, &[])).is_err());
+    }
+
+    #[test]
+    fn packs_resent_frames_into_batches() {
+        let frames: Vec<_> = (1..=RESEND_BATCH_FRAMES as u32 + 1)
+            .map(|id| frame(id, &[]))
+            .collect();
+
+        let batches = pack_resent_frames(&frames);
+        assert_eq!(batches.len(), 2);
+        assert!(batches.iter().all(|f| f.frame_id == 0));
+        assert_eq!(ids(batches[0].redundant.clone()).len(), RESEND_BATCH_FRAMES);
+        assert_eq!(
+            ids(batches[1].redundant.clone()),
+            vec![RESEND_BATCH_FRAMES as u32 + 1]
+        );
+
+        // 单帧超过大小限制时单独成一条
+        let mut large = frame(1, &[]);
+        large.buffer_size = RESEND_BATCH_BYTES + 1;
+        let batches = pack_resent_frames([&large, &frame(2, &[])]);
+        assert_eq!(batches.len(), 2);
+
+        assert!(pack_resent_frames(&[]).is_empty());
+    }
+
+    #[test]
+    fn filters_repeated_sequence_numbers() {
+        let mut window = SeqWindow::default();
+
+        assert!(window.accept(1));
+        assert!(window.accept(3));
+        assert!(!window.accept(1));
+        assert!(window.accept(2));
+        assert!(!window.accept(3));
+        assert!(window.accept(0));
+        assert!(window.accept(0));
+
+        assert!(window.accept(100));
+        assert!(!window.accept(30));
+        assert!(window.accept(40));
+        assert!(!window.accept(40));
+    }
+
+    #[test]
+    fn limits_requested_frames_per_second() {
+        let mut limit = FrameRequestLimit::default();
+        let max = MAX_REQUESTED_FRAMES;
+
+        assert_eq!(limit.allow(10, 5, 1000), None);
+        assert_eq!(limit.allow(1, 100, 1000), Some((1, 100)));
+        // 超出额度的部分被截断
+        assert_eq!(limit.allow(1, u32::MAX, 1500), Some((1, max - 100)));
+        assert_eq!(limit.allow(1, 1, 1999), None);
+        // 下一秒恢复额度
+        assert_eq!(limit.allow(1, 1, 2000), Some((1, 1)));
+    }
+}
@@ -0,0 +1,431 @@
+use crate::api::p2p::message::*;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+
+/// 录像文件头
+pub const REPLAY_MAGIC: &[u8; 4] = b"SWRP";
+/// 录像格式版本,格式不兼容时递增
+pub const REPLAY_VERSION: u32 = 1;
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct ReplayPlayerEvent {
+    pub steam_id: u64,
+    pub player_position: u32,
+    pub frame_id: u32,
+}
+
+/// 录像中的一条记录
+///
+/// 文件由 `REPLAY_MAGIC` + 版本号开头,之后是若干条
+/// `u32` 长度前缀的 MessagePack 记录
+#[derive(Deserialize, Debug, PartialEq)]
+pub enum ReplayRecord {
+    GameStart(MsgServerGameStart),
+    Frames(MsgServerFramesData),
+    Broadcast(u32, MsgServerDataBroadcast),
+    PlayerJoined(ReplayPlayerEvent),
+    PlayerLeft(ReplayPlayerEvent),
+}
+
+/// 与 `ReplayRecord` 编码相同,写入时不需要复制帧数据
+#[derive(Serialize)]
+enum ReplayRecordRef<'a> {
+    GameStart(&'a MsgServerGameStart),
+    Frames(&'a MsgServerFramesData),
+    Broadcast(u32, &'a MsgServerDataBroadcast),
+    PlayerJoined(&'a ReplayPlayerEvent),
+    PlayerLeft(&'a ReplayPlayerEvent),
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    Decode(rmps::decode::Error),
+    Encode(rmps::encode::Error),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "replay io error: {}", e),
+            ReplayError::BadMagic => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported replay version {}, expected {}",
+                v, REPLAY_VERSION
+            ),
+            ReplayError::Truncated => write!(f, "replay file is truncated"),
+            ReplayError::Decode(e) => write!(f, "bad replay record: {}", e),
+            ReplayError::Encode(e) => write!(f, "failed to encode replay record: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(e: std::io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+/// 把服务器的一局游戏写入录像文件
+pub struct ReplayRecorder<W: Write = BufWriter<File>> {
+    writer: W,
+}
+
+impl ReplayRecorder {
+    pub fn create(path: &str) -> Result<Self, ReplayError> {
+        ReplayRecorder::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> ReplayRecorder<W> {
+    pub fn new(mut writer: W) -> Result<Self, ReplayError> {
+        writer.write_all(REPLAY_MAGIC)?;
+        writer.write_all(&REPLAY_VERSION.to_le_bytes())?;
+
+        Ok(ReplayRecorder { writer })
+    }
+
+    fn write(&mut self, record: ReplayRecordRef) -> Result<(), ReplayError> {
+        let bytes = rmps::to_vec(&record).map_err(ReplayError::Encode)?;
+
+        self.writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn record_game_start(&mut self, data: &MsgServerGameStart) -> Result<(), ReplayError> {
+        self.write(ReplayRecordRef::GameStart(data))
+    }
+
+    pub fn record_frames(&mut self, data: &MsgServerFramesData) -> Result<(), ReplayError> {
+        self.write(ReplayRecordRef::Frames(data))
+    }
+
+    pub fn record_broadcast(
+        &mut self,
+        frame_id: u32,
+        data: &MsgServerDataBroadcast,
+    ) -> Result<(), ReplayError> {
+        self.write(ReplayRecordRef::Broadcast(frame_id, data))
+    }
+
+    pub fn record_player_joined(&mut self, event: &ReplayPlayerEvent) -> Result<(), ReplayError> {
+        self.write(ReplayRecordRef::PlayerJoined(event))
+    }
+
+    pub fn record_player_left(&mut self, event: &ReplayPlayerEvent) -> Result<(), ReplayError> {
+        self.write(ReplayRecordRef::PlayerLeft(event))
+    }
+
+    pub fn flush(&mut self) -> Result<(), ReplayError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// 解析整个录像文件
+pub fn read_replay<R: Read>(mut reader: R) -> Result<Vec<ReplayRecord>, ReplayError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.len() < 8 || &bytes[0..4] != REPLAY_MAGIC {
+        return Err(ReplayError::BadMagic);
+    }
+
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if version != REPLAY_VERSION {
+        return Err(ReplayError::UnsupportedVersion(version));
+    }
+
+    let mut records = Vec::new();
+    let mut rest = &bytes[8..];
+
+    while !rest.is_empty() {
+        if rest.len() < 4 {
+            return Err(ReplayError::Truncated);
+        }
+
+        let size = u32::from_le_bytes(rest[0..4].try_into().unwrap()) as usize;
+        rest = &rest[4..];
+
+        if rest.len() < size {
+            return Err(ReplayError::Truncated);
+        }
+
+        records.push(rmps::from_slice(&rest[..size]).map_err(ReplayError::Decode)?);
+        rest = &rest[size..];
+    }
+
+    Ok(records)
+}
+
+#[napi]
+pub mod steamp2p {
+    use super::{read_replay, ReplayPlayerEvent, ReplayRecord};
+    use crate::api::p2p::client::steamp2p::{BroadcastData, GameStart, SteamReceiveUpdate};
+    use napi::bindgen_prelude::{BigInt, Error};
+    use napi::threadsafe_function::{
+        ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+    };
+    use napi::JsFunction;
+    use std::fs::File;
+
+    #[napi]
+    pub struct ReplayPlayerChanged {
+        pub steam_id: BigInt,
+        pub player_position: u32,
+        pub frame_id: u32,
+    }
+
+    impl From<&ReplayPlayerEvent> for ReplayPlayerChanged {
+        fn from(event: &ReplayPlayerEvent) -> Self {
+            ReplayPlayerChanged {
+                steam_id: BigInt::from(event.steam_id),
+                player_position: event.player_position,
+                frame_id: event.frame_id,
+            }
+        }
+    }
+
+    /// 回放 `SteamServerManager.startRecording` 录制的文件
+    ///
+    /// 回调的参数与 `SteamClientManager` 相同,可以直接复用游戏中的帧处理逻辑
+    #[napi]
+    pub struct ReplayPlayer {
+        records: Vec<ReplayRecord>,
+        position: usize,
+
+        steam_on_receive_update:
+            Option<ThreadsafeFunction<SteamReceiveUpdate, ErrorStrategy::Fatal>>,
+        game_start_data_cb: Option<ThreadsafeFunction<GameStart, ErrorStrategy::Fatal>>,
+        broadcast_cb: Option<ThreadsafeFunction<BroadcastData, ErrorStrategy::Fatal>>,
+        player_joined: Option<ThreadsafeFunction<ReplayPlayerChanged, ErrorStrategy::Fatal>>,
+        player_left: Option<ThreadsafeFunction<ReplayPlayerChanged, ErrorStrategy::Fatal>>,
+    }
+
+    #[napi]
+    impl ReplayPlayer {
+        #[napi(constructor)]
+        pub fn new(path: String) -> Result<Self, Error> {
+            let file = File::open(&path).map_err(|e| Error::from_reason(e.to_string()))?;
+            let records = read_replay(file).map_err(|e| Error::from_reason(e.to_string()))?;
+
+            Ok(ReplayPlayer {
+                records,
+                position: 0,
+                steam_on_receive_update: None,
+                game_start_data_cb: None,
+                broadcast_cb: None,
+                player_joined: None,
+                player_left: None,
+            })
+        }
+
+        #[napi(
+            ts_args_type = "callback: ({buffer,index,frameID,count}:{buffer:ArrayBuffer,index:Uint32Array,frameID:number,count:number}) => void"
+        )]
+        pub fn on_steam_on_receive_update(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<SteamReceiveUpdate, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.steam_on_receive_update = Some(threadsafe_handler);
+        }
+
+        #[napi(
+            ts_args_type = "callback: ({buffer,index,count}:{buffer:ArrayBuffer,index:Uint32Array,count:number}) => void"
+        )]
+        pub fn game_start_data_callback(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<GameStart, ErrorStrategy::Fatal> = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
+            self.game_start_data_cb = Some(threadsafe_handler);
+        }
+
+        #[napi(
+            ts_args_type = "callback: ({buffer,steamID}:{buffer:ArrayBuffer,steamID:bigint}) => void"
+        )]
+        pub fn broadcast_callback(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<BroadcastData, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.broadcast_cb = Some(threadsafe_handler);
+        }
+
+        #[napi(
+            ts_args_type = "callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void"
+        )]
+        pub fn on_player_joined(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<ReplayPlayerChanged, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.player_joined = Some(threadsafe_handler);
+        }
+
+        #[napi(
+            ts_args_type = "callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void"
+        )]
+        pub fn on_player_left(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<ReplayPlayerChanged, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.player_left = Some(threadsafe_handler);
+        }
+
+        /// 播放到下一帧为止(包括之前的开局数据、广播和玩家进出事件)
+        ///
+        /// 返回 `false` 表示录像已经播放完毕
+        #[napi]
+        pub fn step(&mut self) -> bool {
+            while let Some(record) = self.records.get(self.position) {
+                self.position += 1;
+
+                match record {
+                    ReplayRecord::GameStart(data) => {
+                        if let Some(fun) = self.game_start_data_cb.as_ref() {
+                            fun.call(
+                                GameStart::from(data.clone()),
+                                ThreadsafeFunctionCallMode::Blocking,
+                            );
+                        }
+                    }
+                    ReplayRecord::Frames(data) => {
+                        if let Some(fun) = self.steam_on_receive_update.as_ref() {
+                            fun.call(
+                                SteamReceiveUpdate::from(data.clone()),
+                                ThreadsafeFunctionCallMode::Blocking,
+                            );
+                        }
+                        return true;
+                    }
+                    ReplayRecord::Broadcast(_, data) => {
+                        if let Some(fun) = self.broadcast_cb.as_ref() {
+                            fun.call(
+                                BroadcastData::from(data),
+                                ThreadsafeFunctionCallMode::Blocking,
+                            );
+                        }
+                    }
+                    ReplayRecord::PlayerJoined(event) => {
+                        if let Some(fun) = self.player_joined.as_ref() {
+                            fun.call(
+                                ReplayPlayerChanged::from(event),
+                                ThreadsafeFunctionCallMode::Blocking,
+                            );
+                        }
+                    }
+                    ReplayRecord::PlayerLeft(event) => {
+                        if let Some(fun) = self.player_left.as_ref() {
+                            fun.call(
+                                ReplayPlayerChanged::from(event),
+                                ThreadsafeFunctionCallMode::Blocking,
+                            );
+                        }
+                    }
+                }
+            }
+
+            false
+        }
+
+        /// 回到录像开头
+        #[napi]
+        pub fn rewind(&mut self) {
+            self.position = 0;
+        }
+
+        #[napi]
+        pub fn is_finished(&self) -> bool {
+            self.position >= self.records.len()
+        }
+
+        /// 录像中的总帧数
+        #[napi]
+        pub fn get_frame_count(&self) -> u32 {
+            self.records
+                .iter()
+                .filter(|f| matches!(f, ReplayRecord::Frames(_)))
+                .count() as u32
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records_round_trip() {
+        let start = MsgServerGameStart {
+            game_data: vec![MsgServerFrameData {
+                types: 0,
+                data: vec![1, 2, 3],
+                local_steam_id: 7,
+                player_position: 0,
+            }],
+            buffer_size: 3,
+        };
+        let frames = MsgServerFramesData {
+            game_data: vec![],
+            buffer_size: 0,
+            frame_id: 1,
+            redundant: vec![],
+        };
+        let joined = ReplayPlayerEvent {
+            steam_id: 7,
+            player_position: 0,
+            frame_id: 0,
+        };
+
+        let mut recorder = ReplayRecorder::new(Vec::new()).unwrap();
+        recorder.record_player_joined(&joined).unwrap();
+        recorder.record_game_start(&start).unwrap();
+        recorder.record_frames(&frames).unwrap();
+
+        let records = read_replay(recorder.into_inner().as_slice()).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                ReplayRecord::PlayerJoined(joined),
+                ReplayRecord::GameStart(start),
+                ReplayRecord::Frames(frames),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(matches!(
+            read_replay(&b"nope"[..]),
+            Err(ReplayError::BadMagic)
+        ));
+
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.extend_from_slice(&(REPLAY_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            read_replay(bytes.as_slice()),
+            Err(ReplayError::UnsupportedVersion(_))
+        ));
+
+        let mut bytes = ReplayRecorder::new(Vec::new()).unwrap().into_inner();
+        bytes.extend_from_slice(&10u32.to_le_bytes());
+        bytes.push(0);
+        assert!(matches!(
+            read_replay(bytes.as_slice()),
+            Err(ReplayError::Truncated)
+        ));
+    }
+}
@@ -2,21 +2,52 @@ use napi_derive::napi;
 
 #[napi]
 pub mod steamp2p {
+    use crate::api::p2p::browser::{BrowserDetails, ServerDetails};
+    use crate::api::p2p::client::steamp2p::ECodec;
+    use crate::api::p2p::client::steamp2p::SteamPauseEvent;
+    use crate::api::p2p::client::steamp2p::SteamVersionMismatch;
+    use crate::api::p2p::client::steamp2p::{
+        BlobCallbacks, BlobFailed, BlobProgress, BlobReceived,
+    };
+    use crate::api::p2p::codec::CodecKind;
+    use crate::api::p2p::desync::{desync_js_args, ChecksumTracker, DEFAULT_CHECKSUM_WINDOW};
+    use crate::api::p2p::history::{FrameHistory, DEFAULT_FRAME_HISTORY_SIZE};
+    use crate::api::p2p::host::{self, RoomEvent, RoomHandle, ServerHost};
+    use crate::api::p2p::input::{
+        InputBuffer, InputResult, InputStats, LateInputPolicy, DEFAULT_MAX_INPUT_LEAD,
+    };
+    use crate::api::p2p::lifecycle::{OpenError, ServerLifecycle, ServerState};
     use crate::api::p2p::message::*;
+    use crate::api::p2p::packet::{
+        chunk_message, decode_packet, encode_packet, ChunkAssembler, DecodeError, DecodeErrors,
+        DEFAULT_COMPRESSION_THRESHOLD, SUPPORTED_FEATURES,
+    };
+    use crate::api::p2p::redundancy::{
+        pack_resent_frames, FrameRequestLimit, SeqWindow, DEFAULT_FRAME_REDUNDANCY,
+        MAX_INPUT_REDUNDANCY,
+    };
+    use crate::api::p2p::replay::{ReplayError, ReplayPlayerEvent, ReplayRecorder};
+    use crate::api::p2p::slot::SlotTable;
+    use crate::api::p2p::transfer::{BlobMessage, BlobTransfers};
+    use crate::api::p2p::validation::{InputRate, InputRule, InputRules, InputViolation};
     use crate::client::now;
     use napi::bindgen_prelude::ToNapiValue;
     use napi::bindgen_prelude::*;
     use napi::{
-        threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode},
-        JsFunction,
+        threadsafe_function::{
+            ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+        },
+        JsBuffer, JsFunction, JsUnknown, ValueType,
     };
     use networking_sockets::*;
-    use std::collections::{HashMap, HashSet};
+    use std::cell::RefCell;
+    use std::collections::{HashSet, VecDeque};
     use std::hash::Hash;
     use std::hash::Hasher;
     use std::net::Ipv4Addr;
+    use std::rc::Rc;
     use std::sync::mpsc::{channel, Receiver, Sender};
-    use steamworks::networking_types::{ListenSocketEvent, SendFlags};
+    use steamworks::networking_types::SendFlags;
     use steamworks::networking_types::{NetConnectionEndReason, NetworkingIdentity};
     use steamworks::networking_utils::NetworkingUtils;
     use steamworks::{ServerManager, SteamServersConnected, *};
@@ -71,6 +102,40 @@ pub mod steamp2p {
         KEserverExiting,
     }
 
+    /// 服务器的生命周期状态
+    #[napi]
+    #[derive(PartialEq, Eq, Debug)]
+    pub enum EServerState {
+        KEserverStateIdle,
+        KEserverStateInitializing,
+        KEserverStateLoggingOn,
+        KEserverStateListening,
+        KEserverStateInGame,
+        KEserverStateShuttingDown,
+    }
+
+    impl From<ServerState> for EServerState {
+        fn from(value: ServerState) -> Self {
+            match value {
+                ServerState::Idle => EServerState::KEserverStateIdle,
+                ServerState::Initializing => EServerState::KEserverStateInitializing,
+                ServerState::LoggingOn => EServerState::KEserverStateLoggingOn,
+                ServerState::Listening => EServerState::KEserverStateListening,
+                ServerState::InGame => EServerState::KEserverStateInGame,
+                ServerState::ShuttingDown => EServerState::KEserverStateShuttingDown,
+            }
+        }
+    }
+
+    impl From<OpenError> for Error {
+        fn from(value: OpenError) -> Self {
+            Error::new(
+                Status::GenericFailure,
+                format!("{}: {}", value.code(), value),
+            )
+        }
+    }
+
     #[napi]
     pub struct SteamServerConnectFailure {
         /// The reason we failed to connect to the Steam servers
@@ -95,17 +160,121 @@ pub mod steamp2p {
         pub owner_steam_id: BigInt,
     }
 
+    /// 目标帧已经下发(或超出提前范围)的操作如何处理
+    #[napi]
+    #[derive(PartialEq, Eq)]
+    pub enum ELateInputPolicy {
+        /// 丢弃
+        KElateInputDrop,
+        /// 放到最近一个可以接受的帧
+        KElateInputClamp,
+    }
+
+    impl From<ELateInputPolicy> for LateInputPolicy {
+        fn from(value: ELateInputPolicy) -> Self {
+            match value {
+                ELateInputPolicy::KElateInputDrop => LateInputPolicy::Drop,
+                ELateInputPolicy::KElateInputClamp => LateInputPolicy::Clamp,
+            }
+        }
+    }
+
+    /// 玩家操作没有通过校验的原因
+    #[napi]
+    #[derive(PartialEq, Eq)]
+    pub enum EInputViolation {
+        /// 严格模式下没有注册规则的操作类型
+        KEinputUnknownType,
+        /// 数据超过限制大小
+        KEinputTooLarge,
+        /// 提交过于频繁
+        KEinputRateLimited,
+        /// 被校验函数拒绝
+        KEinputRejected,
+    }
+
+    impl From<InputViolation> for EInputViolation {
+        fn from(value: InputViolation) -> Self {
+            match value {
+                InputViolation::UnknownType => EInputViolation::KEinputUnknownType,
+                InputViolation::TooLarge => EInputViolation::KEinputTooLarge,
+                InputViolation::RateLimited => EInputViolation::KEinputRateLimited,
+                InputViolation::Rejected => EInputViolation::KEinputRejected,
+            }
+        }
+    }
+
+    #[napi]
+    pub struct InputViolationEvent {
+        pub steam_id: BigInt,
+        pub types: u32,
+        pub violation: EInputViolation,
+        /// 该玩家累计的违规次数
+        pub count: u32,
+    }
+
+    #[napi]
+    pub struct DecodeErrorEvent {
+        pub steam_id: BigInt,
+        pub error: String,
+        /// 该连接累计的错误次数
+        pub count: u32,
+    }
+
+    #[napi]
+    pub struct FrameAdvantage {
+        pub steam_id: BigInt,
+        pub player_position: u32,
+        /// 最近一次操作的目标帧比服务器下一帧提前的帧数,负数表示晚到
+        pub advantage: i32,
+        /// 上报周期内的平均提前帧数
+        pub average_advantage: f64,
+        /// 上报周期内晚到的操作数量
+        pub late_inputs: u32,
+        /// 上报周期内被丢弃的操作数量
+        pub dropped_inputs: u32,
+    }
+
     #[napi]
     pub struct GSPolicyResponseCallback {
         pub secure: u8,
     }
 
+    #[napi]
+    pub struct PlayerConnectionEvent {
+        pub steam_id: BigInt,
+        /// 玩家在服务器中的槽位
+        pub player_position: u32,
+        /// 事件发生时服务器的逻辑帧
+        pub frame_id: u32,
+    }
+
     struct ClientConnectionData {
         active: bool,
         load_complete: bool,
         ul_tick_count_last_data: i64,
         steam_iduser: NetworkingIdentity,
         hsteam_net_connection: Option<NetConnection<ServerManager>>,
+        /// 玩家在服务器中的槽位
+        player_position: u32,
+        /// 游戏中掉线的时间,在重连窗口内保留槽位
+        disconnected_time: Option<i64>,
+        /// 掉线时已经发送给该玩家的最后一帧
+        disconnected_frame_id: u32,
+        /// 观战者,只接收帧数据
+        spectator: bool,
+        /// 操作提前量统计
+        input_stats: InputStats,
+        /// 每种操作的提交频率
+        input_rate: InputRate,
+        /// 操作校验失败的次数
+        input_violations: u32,
+        /// 已经使用的暂停次数
+        pauses_used: u32,
+        /// 最近收到的操作序号
+        input_seq: SeqWindow,
+        /// 请求补发的帧数限制
+        frame_requests: FrameRequestLimit,
     }
 
     impl ClientConnectionData {
@@ -119,15 +288,46 @@ pub mod steamp2p {
                 ul_tick_count_last_data: 0,
                 steam_iduser: identity,
                 hsteam_net_connection: connection,
+                player_position: 0,
+                disconnected_time: None,
+                disconnected_frame_id: 0,
+                spectator: false,
+                input_stats: InputStats::default(),
+                input_rate: InputRate::default(),
+                input_violations: 0,
+                pauses_used: 0,
+                input_seq: SeqWindow::default(),
+                frame_requests: FrameRequestLimit::default(),
+            }
+        }
+
+        pub fn is_disconnected(&self) -> bool {
+            self.disconnected_time.is_some()
+        }
+
+        pub fn replay_event(&self, frame_id: u32) -> ReplayPlayerEvent {
+            ReplayPlayerEvent {
+                steam_id: self.steam_iduser.steam_id().unwrap().raw(),
+                player_position: self.player_position,
+                frame_id,
             }
         }
     }
 
+    enum ConnectionLost {
+        /// 游戏中掉线,保留槽位等待重连
+        Reconnecting(usize),
+        /// 已经移出服务器
+        Removed(Box<ClientConnectionData>),
+    }
+
     #[napi(js_name = "SteamServer")]
     pub struct JsSteamServer {
         is_connected_to_steam: bool,
         policy_response_callback: bool,
         can_close: bool,
+        /// 生命周期状态
+        lifecycle: ServerLifecycle,
         setp: bool,
         dt_total: f64,
         interval: f64,
@@ -140,9 +340,25 @@ pub mod steamp2p {
         rg_pending_client_data: Vec<ClientConnectionData>,
         /// 当前房间最多玩家数
         max_players: u8,
+        /// 玩家槽位,重新加入时使用原来的槽位
+        slots: SlotTable,
+        /// 从大厅成员数据的这个键读取玩家指定的槽位
+        slot_lobby_key: Option<String>,
+        /// 已经链接到服务器的观战者
+        rg_spectator_data: Vec<ClientConnectionData>,
+        /// 当前房间最多观战人数
+        max_spectators: u8,
+        /// 观战者延迟接收的帧数
+        spectator_delay: u32,
         name: String,
         map_name: String,
         server_name: String,
+        /// 规则、标签、玩家等服务器浏览器中显示的信息
+        browser: BrowserDetails,
+        /// 应用版本号,和客户端不一致时拒绝加入
+        build: String,
+        /// 消息体的编码方式,和客户端不一致时拒绝加入
+        codec: CodecKind,
         server_id: u64,
         player_bot: u32,
         lobby_id: u64,
@@ -158,10 +374,14 @@ pub mod steamp2p {
         server_mode: EServerMode,
         pch_version_string: String,
 
-        listen_socket: Option<ListenSocket<ServerManager>>, // drop CloseListenSocket
         net_poll_group: Option<NetPollGroup<ServerManager>>, // drop DestroyPollGroup
-        server_raw: Option<Server>,                         // drop LogOff Shutdown
-        server_single: Option<SingleClient<ServerManager>>,
+        /// 和其他房间共用,最后一个房间释放时 LogOff Shutdown
+        server_raw: Option<Rc<Server>>,
+        server_single: Option<Rc<SingleClient<ServerManager>>>,
+        /// 在共用的游戏服务器中登记的房间,按大厅ID接收连接
+        room: Option<RoomHandle>,
+        /// 由共用的接收组转交过来的握手消息
+        routed_packets: VecDeque<(SteamId, Vec<u8>)>,
         server_sockets: Option<NetworkingSockets<ServerManager>>,
 
         utils: Option<NetworkingUtils<ServerManager>>,
@@ -169,9 +389,52 @@ pub mod steamp2p {
         send: Option<Sender<SteamServerEvent>>,
 
         frame_id: u32,
-        frame_messages: HashMap<u64, Vec<MsgServerFrameData>>,
-        frame_messages_size: u32,
+        /// 按目标帧缓存的玩家操作
+        input_buffer: InputBuffer,
+        /// 每隔多少帧上报一次操作提前量,为0时不上报
+        frame_advantage_interval: u32,
+        /// 每一帧附带之前的帧数,大于0时帧数据改为不可靠发送
+        frame_redundancy: u32,
+        /// 玩家上报的状态校验值
+        checksums: ChecksumTracker,
+        /// 玩家操作的校验规则
+        input_rules: InputRules,
+        /// 违规达到多少次后踢出玩家,为0时不踢出
+        max_input_violations: u32,
+        /// 每个连接无法解析的消息数量
+        decode_errors: DecodeErrors,
+        /// 超过多少字节的消息压缩后发送,为0时不压缩
+        compression_threshold: u32,
+        /// 拼接客户端发来的分片
+        chunks: ChunkAssembler,
+        /// 大数据传输
+        blobs: BlobTransfers,
+        /// 禁言的玩家,广播消息会被丢弃
+        muted: HashSet<u64>,
+        /// 客户端超过多久(毫秒)没有发送任何数据视为超时,为0时不检测
+        client_timeout: i64,
+        /// 等待验证的客户端超过多久(毫秒)没有完成验证就断开,为0时不检测
+        auth_timeout: i64,
+        /// 向客户端发送心跳的间隔(毫秒)
+        heartbeat_interval: i64,
+        last_heartbeat_time: i64,
+        /// 暂停游戏的玩家,为0时表示服务器
+        paused_by: Option<u64>,
+        pause_time: i64,
+        /// 每个玩家每局可以暂停的次数,为0时客户端不能暂停
+        pause_budget: u32,
+        /// 玩家暂停超过多久(毫秒)自动恢复,为0时不自动恢复
+        max_pause_time: i64,
         game_start_data: Option<MsgServerGameStart>,
+        /// 已经下发的开局数据,发给中途加入的观战者
+        game_start_sent: Option<MsgServerGameStart>,
+
+        /// 断线重连的等待时间(毫秒),为0时不保留掉线玩家的槽位
+        reconnect_timeout: i64,
+        /// 最近下发过的帧,用于断线重连和客户端补帧
+        frame_history: FrameHistory,
+        /// 录像
+        recorder: Option<ReplayRecorder>,
     }
 
     #[napi]
@@ -186,6 +449,24 @@ pub mod steamp2p {
             Option<ThreadsafeFunction<SteamServersDisconnected, ErrorStrategy::Fatal>>,
         all_ready_to_go: Option<ThreadsafeFunction<i32, ErrorStrategy::Fatal>>,
         gspolicy_response_callback: Option<ThreadsafeFunction<(), ErrorStrategy::Fatal>>,
+        player_disconnected:
+            Option<ThreadsafeFunction<PlayerConnectionEvent, ErrorStrategy::Fatal>>,
+        player_reconnected: Option<ThreadsafeFunction<PlayerConnectionEvent, ErrorStrategy::Fatal>>,
+        player_left: Option<ThreadsafeFunction<PlayerConnectionEvent, ErrorStrategy::Fatal>>,
+        spectator_joined: Option<ThreadsafeFunction<PlayerConnectionEvent, ErrorStrategy::Fatal>>,
+        spectator_left: Option<ThreadsafeFunction<PlayerConnectionEvent, ErrorStrategy::Fatal>>,
+        frame_advantage: Option<ThreadsafeFunction<Vec<FrameAdvantage>, ErrorStrategy::Fatal>>,
+        desync: Option<ThreadsafeFunction<MsgServerDesync, ErrorStrategy::Fatal>>,
+        input_violation: Option<ThreadsafeFunction<InputViolationEvent, ErrorStrategy::Fatal>>,
+        /// 同步调用的操作校验函数
+        input_validator: Option<FunctionRef<(BigInt, u32, Buffer, u32), JsUnknown>>,
+        paused: Option<ThreadsafeFunction<SteamPauseEvent, ErrorStrategy::Fatal>>,
+        resumed: Option<ThreadsafeFunction<SteamPauseEvent, ErrorStrategy::Fatal>>,
+        version_mismatch: Option<ThreadsafeFunction<SteamVersionMismatch, ErrorStrategy::Fatal>>,
+        decode_error: Option<ThreadsafeFunction<DecodeErrorEvent, ErrorStrategy::Fatal>>,
+        blob_callbacks: BlobCallbacks,
+        state_changed: Option<ThreadsafeFunction<EServerState, ErrorStrategy::Fatal>>,
+        shutdown_cb: Option<ThreadsafeFunction<String, ErrorStrategy::Fatal>>,
     }
 
     #[napi]
@@ -257,321 +538,914 @@ pub mod steamp2p {
             dbg!("on_gspolicy_response_callback");
         }
 
-        pub fn receive(&mut self) {
-            let mut server = &mut self.raw;
+        /// 玩家在游戏中掉线,进入重连等待
+        #[napi(
+            ts_args_type = "callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void"
+        )]
+        pub fn on_player_disconnected(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<
+                PlayerConnectionEvent,
+                ErrorStrategy::Fatal,
+            > = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
 
-            loop {
-                if let Some(socket) = server.listen_socket.as_ref() {
-                    if let Some(event) = socket.try_receive_event() {
-                        #[cfg(feature = "dev")]
-                        dbg!("ListenSocketEvent Receive");
-
-                        match event {
-                            ListenSocketEvent::Connecting(mut request) => {
-                                #[cfg(feature = "dev")]
-                                dbg!("ListenSocketEvent::Connecting");
-
-                                if server.rg_pending_client_data.len() >= server.max_players.into()
-                                {
-                                    #[cfg(feature = "dev")]
-                                    dbg!("Rejecting connection; server full");
-
-                                    request.reject(
-                                        NetConnectionEndReason::NetConnectionEnd(
-                                            networking_types::NetConnectionEnd::AppException,
-                                        ),
-                                        Some("Server full!"),
-                                    );
-                                    return;
-                                }
+            self.player_disconnected = Some(threadsafe_handler);
 
-                                let remote = request.remote();
-                                let find = server
-                                    .rg_pending_client_data
-                                    .iter()
-                                    .find(|f| {
-                                        f.steam_iduser.steam_id().unwrap()
-                                            == remote.steam_id().unwrap()
-                                    })
-                                    .or_else(|| {
-                                        server.rg_client_data.iter().find(|f| {
-                                            f.steam_iduser.steam_id().unwrap()
-                                                == remote.steam_id().unwrap()
-                                        })
-                                    });
-                                if find.is_some() {
-                                    return;
-                                }
+            #[cfg(feature = "dev")]
+            dbg!("on_player_disconnected");
+        }
 
-                                if let Err(_) = request.accept() {
-                                    #[cfg(feature = "dev")]
-                                    dbg!("ConnectionRequest::Accept Error");
+        /// 掉线玩家重连成功,错过的帧已经补发
+        #[napi(
+            ts_args_type = "callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void"
+        )]
+        pub fn on_player_reconnected(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<
+                PlayerConnectionEvent,
+                ErrorStrategy::Fatal,
+            > = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
 
-                                    request.reject(
-                                        NetConnectionEndReason::NetConnectionEnd(
-                                            networking_types::NetConnectionEnd::AppException,
-                                        ),
-                                        Some("Failed to accept connection"),
-                                    );
+            self.player_reconnected = Some(threadsafe_handler);
 
-                                    return;
-                                }
+            #[cfg(feature = "dev")]
+            dbg!("on_player_reconnected");
+        }
 
-                                let pending = ClientConnectionData::new(remote, None);
-                                server.rg_pending_client_data.push(pending);
-                            }
-                            ListenSocketEvent::Connected(connected) => {
-                                #[cfg(feature = "dev")]
-                                dbg!("ListenSocketEvent::Connected");
-
-                                let remote = connected.remote();
-
-                                let find = server.rg_pending_client_data.iter().position(|f| {
-                                    f.steam_iduser.steam_id().unwrap() == remote.steam_id().unwrap()
-                                });
-
-                                if let Some(f) = find {
-                                    let request = connected.connection();
-                                    request.set_poll_group(server.net_poll_group.as_ref().unwrap());
-
-                                    let msg = MsgServerSendInfo {
-                                        ul_steam_idserver: server.server_id,
-                                        is_vacsecure: server.server_raw.as_ref().unwrap().secure(),
-                                        rgch_server_name: server.server_name.clone(),
-                                    };
-
-                                    #[cfg(feature = "dev")]
-                                    dbg!("ListenSocketEvent::Connected::send_message MsgServerSendInfo");
-
-                                    server.send_message(msg, request);
-                                    server
-                                        .rg_pending_client_data
-                                        .get_mut(f)
-                                        .unwrap()
-                                        .hsteam_net_connection = Some(connected.take_connection());
-                                } else {
-                                    #[cfg(feature = "dev")]
-                                    dbg!("ListenSocketEvent::Connected take_connection().close");
-
-                                    connected.take_connection().close(
-                                        NetConnectionEndReason::NetConnectionEnd(
-                                            networking_types::NetConnectionEnd::AppException,
-                                        ),
-                                        Some("can not find rg_pending_client_data"),
-                                        false,
-                                    );
-                                    return;
-                                }
-                            }
-                            ListenSocketEvent::Disconnected(disconnected) => {
-                                #[cfg(feature = "dev")]
-                                dbg!("ListenSocketEvent::Disconnected");
-
-                                let remote = disconnected.remote();
-
-                                server
-                                    .rg_client_data
-                                    .iter()
-                                    .position(|f| {
-                                        f.steam_iduser.steam_id().unwrap()
-                                            == remote.steam_id().unwrap()
-                                    })
-                                    .map(|f| {
-                                        let data = server.rg_client_data.get_mut(f).unwrap();
-                                        data.hsteam_net_connection.take().unwrap().close(
-                                            NetConnectionEndReason::NetConnectionEnd(
-                                                networking_types::NetConnectionEnd::AppGeneric,
-                                            ),
-                                            None,
-                                            false,
-                                        );
+        /// 玩家离开服务器(包括重连超时)
+        #[napi(
+            ts_args_type = "callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void"
+        )]
+        pub fn on_player_left(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<
+                PlayerConnectionEvent,
+                ErrorStrategy::Fatal,
+            > = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
 
-                                        server.rg_client_data.remove(f);
-                                    });
-                            }
-                        }
-                    } else {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
+            self.player_left = Some(threadsafe_handler);
 
-            loop {
-                if let Ok(result) = self.rx.try_recv() {
-                    match result {
-                        SteamServerEvent::SteamServersConnected(_) => {
-                            #[cfg(feature = "dev")]
-                            dbg!("SteamServerEvent::SteamServersConnected");
+            #[cfg(feature = "dev")]
+            dbg!("on_player_left");
+        }
 
-                            server.is_connected_to_steam = true;
-                            server.connected_success_count += 1;
-                            server.send_updated_server_details_to_steam();
+        /// 观战者验证成功并加入服务器
+        #[napi(
+            ts_args_type = "callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void"
+        )]
+        pub fn on_spectator_joined(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<
+                PlayerConnectionEvent,
+                ErrorStrategy::Fatal,
+            > = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
 
-                            if let Some(fun) = self.steam_servers_connected.as_ref() {
-                                fun.call(
-                                    server.connected_success_count as i32,
-                                    ThreadsafeFunctionCallMode::Blocking,
-                                );
-                            }
-                        }
-                        SteamServerEvent::SteamServerConnectFailure(failure) => {
-                            #[cfg(feature = "dev")]
-                            dbg!("SteamServerEvent::SteamServerConnectFailure");
+            self.spectator_joined = Some(threadsafe_handler);
 
-                            server.is_connected_to_steam = false;
-                            if let Some(fun) = self.steam_server_connect_failure.as_ref() {
-                                fun.call(failure, ThreadsafeFunctionCallMode::Blocking);
-                            }
-                        }
-                        SteamServerEvent::SteamServersDisconnected(disconnected) => {
-                            #[cfg(feature = "dev")]
-                            dbg!("SteamServerEvent::SteamServersDisconnected");
+            #[cfg(feature = "dev")]
+            dbg!("on_spectator_joined");
+        }
 
-                            server.is_connected_to_steam = false;
-                            if let Some(fun) = self.steam_servers_disconnected.as_ref() {
-                                fun.call(disconnected, ThreadsafeFunctionCallMode::Blocking);
-                            }
-                        }
-                        SteamServerEvent::ValidateAuthTicketResponse(response) => {
-                            let index = server.rg_pending_client_data.iter().position(|data| {
-                                if data.active {
-                                    data.steam_iduser.steam_id().unwrap()
-                                        == SteamId::from_raw(response.steam_id.get_u64().1)
-                                } else {
-                                    return false;
-                                }
-                            });
+        /// 观战者离开服务器
+        #[napi(
+            ts_args_type = "callback: ({steamId,playerPosition,frameId}:{steamId:bigint,playerPosition:number,frameId:number}) => void"
+        )]
+        pub fn on_spectator_left(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<
+                PlayerConnectionEvent,
+                ErrorStrategy::Fatal,
+            > = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
 
-                            if let Some(pending_auth_index) = index {
-                                if response.response.is_none() {
-                                    println!("auth completed for a client");
-                                } else {
-                                    println!("auth failed for a client");
-                                };
+            self.spectator_left = Some(threadsafe_handler);
 
-                                if server.on_auth_completed(
-                                    response.response.is_none(),
-                                    pending_auth_index,
-                                ) {
-                                    if let Some(fun) = self.all_ready_to_go.as_ref() {
-                                        fun.call(1, ThreadsafeFunctionCallMode::Blocking);
-                                    }
-                                }
-                            }
-                        }
-                        SteamServerEvent::GSPolicyResponseCallback(_) => {
-                            if let Some(raw) = server.server_raw.as_ref() {
-                                if raw.secure() {
-                                    println!("server is val secure");
-                                } else {
-                                    println!("server is not vac secure");
-                                }
+            #[cfg(feature = "dev")]
+            dbg!("on_spectator_left");
+        }
 
-                                println!("server steam id is : {:?}", raw.steam_id().raw());
-                                server.policy_response_callback = true;
+        /// 定期上报每个玩家的操作提前量,用于调整输入延迟
+        #[napi(
+            ts_args_type = "callback: (players:{steamId:bigint,playerPosition:number,advantage:number,averageAdvantage:number,lateInputs:number,droppedInputs:number}[]) => void"
+        )]
+        pub fn on_frame_advantage(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<Vec<FrameAdvantage>, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
 
-                                if let Some(fun) = self.gspolicy_response_callback.as_ref() {
-                                    fun.call((), ThreadsafeFunctionCallMode::Blocking);
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    break;
-                }
-            }
+            self.frame_advantage = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_frame_advantage");
         }
 
-        #[napi]
-        pub fn receive_network_data(&mut self) {
-            if self.raw.listen_socket.as_ref().is_none() && !self.raw.is_connected_to_steam() {
-                return;
-            }
+        /// 玩家之间同一帧的状态校验值不一致
+        #[napi(
+            ts_args_type = "callback: (frameId:number, checksums:Record<string,bigint>) => void"
+        )]
+        pub fn on_desync(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<MsgServerDesync, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<MsgServerDesync>| {
+                        desync_js_args(&ctx.env, &ctx.value)
+                    })
+                    .unwrap();
 
-            if let Some(group) = self.raw.net_poll_group.as_ref() {
-                for e in group.receive_messages(128) {
-                    let remote = e.identity_peer().steam_id().unwrap();
-                    let data = e.data();
+            self.desync = Some(threadsafe_handler);
 
-                    if data.len() < 4 {
-                        println!("got garbage on server socket, too short");
+            #[cfg(feature = "dev")]
+            dbg!("on_desync");
+        }
 
-                        drop(e); // drop call SteamAPI_SteamNetworkingMessage_t_Release
-                        continue;
-                    }
+        /// 收到完整并且校验通过的大数据
+        #[napi(
+            ts_args_type = "callback: ({steamId,transferId,meta,buffer}:{steamId:bigint,transferId:number,meta:string,buffer:Buffer}) => void"
+        )]
+        pub fn on_blob_received(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<BlobReceived, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
 
-                    let header: EMessage = data[0..4].to_vec().into();
-                    let body = &data[4..];
+            self.blob_callbacks.received = Some(threadsafe_handler);
 
-                    if header == EMessage::Error {
-                        drop(e); // drop call SteamAPI_SteamNetworkingMessage_t_Release
-                        continue;
-                    }
+            #[cfg(feature = "dev")]
+            dbg!("on_blob_received");
+        }
 
-                    match header {
-                        EMessage::KEmsgClientFrameData => {
-                            if let Ok(msg) = rmps::from_slice::<MsgClientFrameData>(body) {
-                                if msg.types == 0 {
-                                    self.raw.on_client_games_data(msg, remote);
-                                } else {
-                                    self.raw.on_client_frame_data(msg, remote);
-                                };
-                            }
-                        }
-                        EMessage::KEmsgClientBroadcast => {
-                            if let Ok(msg) = rmps::from_slice::<MsgClientDataBroadcast>(body) {
-                                self.raw.on_client_broadcast(msg);
-                            }
-                        }
-                        EMessage::KEmsgClientBeginAuthentication => {
-                            if let Ok(msg) = rmps::from_slice::<MsgClientBeginAuthentication>(body)
-                            {
-                                self.raw.on_client_begin_authentication(msg, remote);
-                            }
-                        }
-                        EMessage::KEmsgClientLoadComplete => {
-                            if self.raw.game_state == EServerGameState::KEserverActive {
-                                break;
-                            }
+        /// 大数据的发送或接收进度,客户端之间转发的传输不会通知
+        #[napi(
+            ts_args_type = "callback: ({steamId,transferId,transferred,total,outgoing}:{steamId:bigint,transferId:number,transferred:number,total:number,outgoing:boolean}) => void"
+        )]
+        pub fn on_blob_progress(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<BlobProgress, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
 
-                            let mut all_load = true;
-                            self.raw.rg_client_data.iter_mut().for_each(|data| {
-                                if data.steam_iduser.steam_id().unwrap() == remote {
-                                    data.load_complete = true;
-                                }
+            self.blob_callbacks.progress = Some(threadsafe_handler);
 
-                                if !data.load_complete {
-                                    all_load = false;
-                                }
-                            });
+            #[cfg(feature = "dev")]
+            dbg!("on_blob_progress");
+        }
 
-                            if all_load {
-                                let take = self.raw.game_start_data.take().unwrap();
+        /// 大数据传输失败,例如校验不一致
+        #[napi(
+            ts_args_type = "callback: ({steamId,transferId,error,outgoing}:{steamId:bigint,transferId:number,error:string,outgoing:boolean}) => void"
+        )]
+        pub fn on_blob_failed(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<BlobFailed, ErrorStrategy::Fatal> = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
 
-                                self.raw.rg_client_data.iter().for_each(|f| {
-                                    self.raw.send_message_ref(
-                                        &take,
-                                        f.hsteam_net_connection.as_ref().unwrap(),
-                                    );
-                                });
-                            }
-                        }
-                        _ => panic!("Bad client info msg,{:?}", header),
-                    }
+            self.blob_callbacks.failed = Some(threadsafe_handler);
 
-                    drop(e); // drop call SteamAPI_SteamNetworkingMessage_t_Release
-                }
-            }
+            #[cfg(feature = "dev")]
+            dbg!("on_blob_failed");
         }
 
-        #[napi]
-        pub fn run_callbacks(&mut self, dt: f64) {
-            self.raw.run_callbacks();
-            self.receive();
-            self.receive_network_data();
-
+        /// 收到无法解析的消息,`count` 为该连接累计的错误次数
+        #[napi(
+            ts_args_type = "callback: ({steamId,error,count}:{steamId:bigint,error:string,count:number}) => void"
+        )]
+        pub fn on_decode_error(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<DecodeErrorEvent, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+
+            self.decode_error = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_decode_error");
+        }
+
+        /// 客户端的协议版本或应用版本号不一致,已经拒绝加入
+        #[napi(
+            ts_args_type = "callback: ({steamId,protocolVersion,build}:{steamId:bigint,protocolVersion:number,build:string}) => void"
+        )]
+        pub fn on_version_mismatch(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<SteamVersionMismatch, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+
+            self.version_mismatch = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_version_mismatch");
+        }
+
+        /// 游戏暂停,`frameId` 为暂停前下发的最后一帧
+        #[napi(
+            ts_args_type = "callback: ({steamId,frameId}:{steamId:bigint,frameId:number}) => void"
+        )]
+        pub fn on_paused(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<SteamPauseEvent, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+
+            self.paused = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_paused");
+        }
+
+        /// 游戏恢复,下一帧为 `frameId + 1`
+        #[napi(
+            ts_args_type = "callback: ({steamId,frameId}:{steamId:bigint,frameId:number}) => void"
+        )]
+        pub fn on_resumed(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<SteamPauseEvent, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+
+            self.resumed = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_resumed");
+        }
+
+        fn notify_version_mismatch(&self, event: SteamVersionMismatch) {
+            if let Some(fun) = self.version_mismatch.as_ref() {
+                fun.call(event, ThreadsafeFunctionCallMode::Blocking);
+            }
+        }
+
+        fn notify_paused(&self, msg: Option<MsgServerPaused>) {
+            if let (Some(msg), Some(fun)) = (msg, self.paused.as_ref()) {
+                fun.call(
+                    SteamPauseEvent {
+                        steam_id: BigInt::from(msg.steam_id),
+                        frame_id: msg.frame_id,
+                    },
+                    ThreadsafeFunctionCallMode::Blocking,
+                );
+            }
+        }
+
+        fn notify_resumed(&self, msg: Option<MsgServerResumed>) {
+            if let (Some(msg), Some(fun)) = (msg, self.resumed.as_ref()) {
+                fun.call(
+                    SteamPauseEvent {
+                        steam_id: BigInt::from(msg.steam_id),
+                        frame_id: msg.frame_id,
+                    },
+                    ThreadsafeFunctionCallMode::Blocking,
+                );
+            }
+        }
+
+        /// 玩家操作没有通过校验
+        #[napi(
+            ts_args_type = "callback: ({steamId,types,violation,count}:{steamId:bigint,types:number,violation:EInputViolation,count:number}) => void"
+        )]
+        pub fn on_input_violation(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<InputViolationEvent, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+
+            self.input_violation = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_input_violation");
+        }
+
+        /// 设置操作校验函数,在操作进入帧之前同步调用
+        ///
+        /// 返回 `false` 拒绝该操作,返回 `Buffer` 替换操作数据,其它返回值表示接受
+        #[napi(
+            ts_args_type = "validator?: ((steamId:bigint, types:number, data:Buffer, frameId:number) => boolean | Buffer | undefined) | null"
+        )]
+        pub fn set_input_validator(
+            &mut self,
+            validator: Option<Function<(BigInt, u32, Buffer, u32), JsUnknown>>,
+        ) -> Result<()> {
+            self.input_validator = match validator {
+                Some(f) => Some(f.create_ref()?),
+                None => None,
+            };
+
+            Ok(())
+        }
+
+        /// 按规则和校验函数检查一条操作,没有通过时返回 `None`
+        fn validate_input(
+            &mut self,
+            env: &Env,
+            mut msg: MsgClientFrameData,
+            remote: SteamId,
+        ) -> Option<MsgClientFrameData> {
+            if let Err(violation) = self.raw.check_input(&msg, remote) {
+                self.on_input_violation_detected(remote, msg.types, violation);
+                return None;
+            }
+
+            if let Some(validator) = self.input_validator.as_ref() {
+                let result = validator.borrow_back(env).and_then(|f| {
+                    f.call((
+                        BigInt::from(remote.raw()),
+                        msg.types,
+                        msg.data.clone().into(),
+                        msg.frame_id,
+                    ))
+                });
+
+                let value = match result {
+                    Ok(value) => value,
+                    Err(_e) => {
+                        #[cfg(feature = "dev")]
+                        dbg!("input validator failed", _e.to_string());
+
+                        return None;
+                    }
+                };
+
+                if value.is_buffer().unwrap_or(false) {
+                    if let Ok(data) = JsBuffer::try_from(value).and_then(|b| b.into_value()) {
+                        msg.data = data.to_vec();
+                    }
+                } else if matches!(value.get_type(), Ok(ValueType::Boolean)) {
+                    let accepted = value.coerce_to_bool().and_then(|b| b.get_value());
+                    if matches!(accepted, Ok(false)) {
+                        self.on_input_violation_detected(
+                            remote,
+                            msg.types,
+                            InputViolation::Rejected,
+                        );
+                        return None;
+                    }
+                }
+            }
+
+            Some(msg)
+        }
+
+        fn on_input_violation_detected(
+            &mut self,
+            remote: SteamId,
+            types: u32,
+            violation: InputViolation,
+        ) {
+            let data = self
+                .raw
+                .rg_client_data
+                .iter_mut()
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote);
+
+            let count = match data {
+                Some(data) => {
+                    data.input_violations += 1;
+                    data.input_violations
+                }
+                None => return,
+            };
+
+            #[cfg(feature = "dev")]
+            dbg!("input violation", types, violation, count);
+
+            if let Some(fun) = self.input_violation.as_ref() {
+                fun.call(
+                    InputViolationEvent {
+                        steam_id: BigInt::from(remote.raw()),
+                        types,
+                        violation: violation.into(),
+                        count,
+                    },
+                    ThreadsafeFunctionCallMode::Blocking,
+                );
+            }
+
+            let max = self.raw.max_input_violations;
+            if max > 0 && count >= max {
+                println!(
+                    "kicking player {:?} after {} input violations",
+                    remote, count
+                );
+
+                if let Some(data) = self.raw.remove_player_from_server(
+                    remote,
+                    EDisconnectReason::EDRClientKicked,
+                    Some("Too many invalid inputs"),
+                ) {
+                    Self::notify_player(self.player_left.as_ref(), &data, self.raw.frame_id);
+                }
+            }
+        }
+
+        fn notify_player(
+            handler: Option<&ThreadsafeFunction<PlayerConnectionEvent, ErrorStrategy::Fatal>>,
+            data: &ClientConnectionData,
+            frame_id: u32,
+        ) {
+            if let Some(fun) = handler {
+                fun.call(
+                    PlayerConnectionEvent {
+                        steam_id: BigInt::from(data.steam_iduser.steam_id().unwrap().raw()),
+                        player_position: data.player_position,
+                        frame_id,
+                    },
+                    ThreadsafeFunctionCallMode::Blocking,
+                );
+            }
+        }
+
+        pub fn receive(&mut self) {
+            let mut server = &mut self.raw;
+
+            let events = match server.room.as_ref() {
+                Some(room) => room.take_events(server.is_full()),
+                None => vec![],
+            };
+
+            for event in events {
+                match event {
+                    RoomEvent::Joined(remote, connection, packet) => {
+                        #[cfg(feature = "dev")]
+                        dbg!("RoomEvent::Joined");
+
+                        if let Some((reason, debug)) = server.admit(&remote) {
+                            connection.close(reason, Some(debug), false);
+                            continue;
+                        }
+
+                        connection.set_poll_group(server.net_poll_group.as_ref().unwrap());
+
+                        // 握手消息已经被公共接收组取出,交给 `receive_network_data` 处理
+                        let steam_id = remote.steam_id().unwrap();
+                        let mut pending = ClientConnectionData::new(remote, Some(connection));
+                        pending.ul_tick_count_last_data = now();
+                        server.rg_pending_client_data.push(pending);
+                        server.routed_packets.push_back((steam_id, packet));
+                    }
+                    RoomEvent::Disconnected(remote) => {
+                        #[cfg(feature = "dev")]
+                        dbg!("RoomEvent::Disconnected");
+
+                        let spectator = server.rg_spectator_data.iter().position(|f| {
+                            f.steam_iduser.steam_id().unwrap() == remote.steam_id().unwrap()
+                        });
+
+                        if let Some(f) = spectator {
+                            let mut data = server.rg_spectator_data.remove(f);
+                            if let Some(conn) = data.hsteam_net_connection.take() {
+                                conn.close(
+                                    NetConnectionEndReason::NetConnectionEnd(
+                                        networking_types::NetConnectionEnd::AppGeneric,
+                                    ),
+                                    None,
+                                    false,
+                                );
+                            }
+
+                            if let Some(raw) = server.server_raw.as_ref() {
+                                raw.end_authentication_session(remote.steam_id().unwrap());
+                            }
+
+                            Self::notify_player(
+                                self.spectator_left.as_ref(),
+                                &data,
+                                server.frame_id,
+                            );
+                            continue;
+                        }
+
+                        let find = server.rg_client_data.iter().position(|f| {
+                            f.steam_iduser.steam_id().unwrap() == remote.steam_id().unwrap()
+                                && !f.is_disconnected()
+                        });
+
+                        if let Some(f) = find {
+                            let frame_id = server.frame_id;
+                            match server.on_player_connection_lost(
+                                f,
+                                NetConnectionEndReason::NetConnectionEnd(
+                                    networking_types::NetConnectionEnd::AppGeneric,
+                                ),
+                                None,
+                            ) {
+                                ConnectionLost::Reconnecting(index) => {
+                                    Self::notify_player(
+                                        self.player_disconnected.as_ref(),
+                                        &server.rg_client_data[index],
+                                        frame_id,
+                                    );
+                                }
+                                ConnectionLost::Removed(data) => {
+                                    Self::notify_player(self.player_left.as_ref(), &data, frame_id);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            loop {
+                if let Ok(result) = self.rx.try_recv() {
+                    match result {
+                        SteamServerEvent::SteamServersConnected(_) => {
+                            #[cfg(feature = "dev")]
+                            dbg!("SteamServerEvent::SteamServersConnected");
+
+                            server.is_connected_to_steam = true;
+                            server.connected_success_count += 1;
+                            if let Err(e) = server.send_updated_server_details_to_steam() {
+                                println!("failed to send server details: {}", e);
+                            }
+
+                            if let Some(fun) = self.steam_servers_connected.as_ref() {
+                                fun.call(
+                                    server.connected_success_count as i32,
+                                    ThreadsafeFunctionCallMode::Blocking,
+                                );
+                            }
+                        }
+                        SteamServerEvent::SteamServerConnectFailure(failure) => {
+                            #[cfg(feature = "dev")]
+                            dbg!("SteamServerEvent::SteamServerConnectFailure");
+
+                            server.is_connected_to_steam = false;
+                            if let Some(fun) = self.steam_server_connect_failure.as_ref() {
+                                fun.call(failure, ThreadsafeFunctionCallMode::Blocking);
+                            }
+                        }
+                        SteamServerEvent::SteamServersDisconnected(disconnected) => {
+                            #[cfg(feature = "dev")]
+                            dbg!("SteamServerEvent::SteamServersDisconnected");
+
+                            server.is_connected_to_steam = false;
+                            if let Some(fun) = self.steam_servers_disconnected.as_ref() {
+                                fun.call(disconnected, ThreadsafeFunctionCallMode::Blocking);
+                            }
+                        }
+                        SteamServerEvent::ValidateAuthTicketResponse(response) => {
+                            let index = server.rg_pending_client_data.iter().position(|data| {
+                                if data.active {
+                                    data.steam_iduser.steam_id().unwrap()
+                                        == SteamId::from_raw(response.steam_id.get_u64().1)
+                                } else {
+                                    return false;
+                                }
+                            });
+
+                            if let Some(pending_auth_index) = index {
+                                if response.response.is_none() {
+                                    println!("auth completed for a client");
+                                } else {
+                                    println!("auth failed for a client");
+                                };
+
+                                let steam_id = SteamId::from_raw(response.steam_id.get_u64().1);
+                                let reconnect = response.response.is_none()
+                                    && server.rg_client_data.iter().any(|f| {
+                                        f.is_disconnected()
+                                            && f.steam_iduser.steam_id().unwrap() == steam_id
+                                    });
+
+                                let spectator =
+                                    server.rg_pending_client_data[pending_auth_index].spectator;
+
+                                if reconnect {
+                                    if let Some(data) =
+                                        server.on_reconnect_completed(pending_auth_index)
+                                    {
+                                        Self::notify_player(
+                                            self.player_reconnected.as_ref(),
+                                            server.rg_client_data.get(data).unwrap(),
+                                            server.frame_id,
+                                        );
+                                    }
+                                } else if spectator && response.response.is_none() {
+                                    let data =
+                                        server.on_spectator_auth_completed(pending_auth_index);
+                                    Self::notify_player(
+                                        self.spectator_joined.as_ref(),
+                                        server.rg_spectator_data.get(data).unwrap(),
+                                        server.frame_id,
+                                    );
+                                } else if server.on_auth_completed(
+                                    response.response.is_none(),
+                                    pending_auth_index,
+                                ) {
+                                    if let Some(fun) = self.all_ready_to_go.as_ref() {
+                                        fun.call(1, ThreadsafeFunctionCallMode::Blocking);
+                                    }
+                                }
+                            }
+                        }
+                        SteamServerEvent::GSPolicyResponseCallback(_) => {
+                            if let Some(raw) = server.server_raw.as_ref() {
+                                if raw.secure() {
+                                    println!("server is val secure");
+                                } else {
+                                    println!("server is not vac secure");
+                                }
+
+                                println!("server steam id is : {:?}", raw.steam_id().raw());
+                                server.policy_response_callback = true;
+
+                                if let Some(fun) = self.gspolicy_response_callback.as_ref() {
+                                    fun.call((), ThreadsafeFunctionCallMode::Blocking);
+                                }
+                            }
+                        }
+                    }
+                } else {
+                    break;
+                }
+            }
+        }
+
+        #[napi]
+        pub fn receive_network_data(&mut self, env: Env) {
+            if self.raw.room.is_none() {
+                return;
+            }
+
+            while let Some((remote, data)) = self.raw.routed_packets.pop_front() {
+                self.on_packet(&env, remote, &data);
+            }
+
+            if let Some(group) = self.raw.net_poll_group.as_ref() {
+                for e in group.receive_messages(128) {
+                    let remote = e.identity_peer().steam_id().unwrap();
+                    self.on_packet(&env, remote, e.data());
+
+                    drop(e); // drop call SteamAPI_SteamNetworkingMessage_t_Release
+                }
+            }
+        }
+
+        /// 处理客户端发来的一个数据包,分片拼接完整后交给 `on_message`
+        fn on_packet(&mut self, env: &Env, remote: SteamId, data: &[u8]) {
+            self.raw.touch(remote);
+
+            // 完成认证前不接收分片,避免未认证的连接占用拼接缓存
+            if let Some(msg) = chunk_message(data) {
+                if self.raw.client_connection(remote.raw()).is_none() {
+                    self.report_decode_error(remote, DecodeError::Unexpected(msg));
+                    return;
+                }
+            }
+
+            let result = self
+                .raw
+                .chunks
+                .push(remote.raw(), data)
+                .and_then(|packet| match packet {
+                    Some(packet) => decode_packet(&packet)
+                        .and_then(|(header, body)| self.on_message(env, remote, header, &body)),
+                    // 等待剩下的分片
+                    None => Ok(()),
+                });
+            if let Err(err) = result {
+                self.report_decode_error(remote, err);
+            }
+        }
+
+        /// 处理客户端发来的一条消息
+        fn on_message(
+            &mut self,
+            env: &Env,
+            remote: SteamId,
+            header: EMessage,
+            body: &[u8],
+        ) -> std::result::Result<(), DecodeError> {
+            match header {
+                EMessage::KEmsgClientFrameData => {
+                    let mut msg = self.raw.codec.decode::<MsgClientFrameData>(body)?;
+
+                    // 观战者只能接收数据
+                    if self.raw.is_spectator(remote) {
+                        #[cfg(feature = "dev")]
+                        dbg!("rejecting frame data from spectator");
+
+                        return Ok(());
+                    }
+
+                    // 只处理最近的几条,避免每条消息都要校验大量操作
+                    let mut redundant = std::mem::take(&mut msg.redundant);
+                    if redundant.len() > MAX_INPUT_REDUNDANCY {
+                        redundant.drain(..redundant.len() - MAX_INPUT_REDUNDANCY);
+                    }
+                    for msg in redundant.into_iter().chain(std::iter::once(msg)) {
+                        // 不可靠发送时同一条操作可能收到多次
+                        if !self.raw.accept_input_seq(remote, msg.seq) {
+                            continue;
+                        }
+
+                        if msg.types == 0 {
+                            self.raw.on_client_games_data(msg, remote);
+                        } else if let Some(msg) = self.validate_input(env, msg, remote) {
+                            self.raw.on_client_frame_data(msg, remote);
+                        };
+                    }
+                }
+                EMessage::KEmsgClientBroadcast => {
+                    let msg = self.raw.codec.decode::<MsgClientDataBroadcast>(body)?;
+
+                    if !self.raw.is_spectator(remote) && !self.raw.is_muted(remote) {
+                        self.raw.on_client_broadcast(msg, remote);
+                    }
+                }
+                EMessage::KEmsgClientBeginAuthentication => {
+                    match self.raw.codec.decode::<MsgClientBeginAuthentication>(body) {
+                        Ok(msg) => {
+                            let mismatch = self.raw.check_client_version(
+                                remote,
+                                msg.protocol_version,
+                                &msg.build,
+                                msg.codec,
+                            );
+                            match mismatch {
+                                Some(event) => self.notify_version_mismatch(event),
+                                None => {
+                                    self.raw.set_client_features(remote, msg.features);
+                                    self.raw.on_client_begin_authentication(msg, remote);
+                                }
+                            }
+                        }
+                        // 客户端的协议版本更新,消息格式无法解析
+                        Err(err) => {
+                            let version = peek_protocol_version(
+                                body,
+                                MsgClientBeginAuthentication::PROTOCOL_VERSION_INDEX,
+                            );
+                            let codec = self.raw.codec as u32;
+                            match version.and_then(|version| {
+                                self.raw.check_client_version(remote, version, "", codec)
+                            }) {
+                                Some(event) => self.notify_version_mismatch(event),
+                                None => return Err(err),
+                            }
+                        }
+                    }
+                }
+                EMessage::KEmsgClientHeartbeat => {}
+                EMessage::KEmsgClientRequestPause => {
+                    let msg = self.raw.on_client_request_pause(remote);
+                    self.notify_paused(msg);
+                }
+                EMessage::KEmsgClientRequestResume => {
+                    let msg = self.raw.on_client_request_resume(remote);
+                    self.notify_resumed(msg);
+                }
+                EMessage::KEmsgClientChecksum => {
+                    let msg = self.raw.codec.decode::<MsgClientChecksum>(body)?;
+                    if let Some(desync) = self.raw.on_client_checksum(msg, remote) {
+                        if let Some(fun) = self.desync.as_ref() {
+                            fun.call(desync, ThreadsafeFunctionCallMode::Blocking);
+                        }
+                    }
+                }
+                EMessage::KEmsgClientRequestFrames => {
+                    let msg = self.raw.codec.decode::<MsgClientRequestFrames>(body)?;
+                    self.raw.on_client_request_frames(msg, remote);
+                }
+                EMessage::KEmsgClientLoadComplete => {
+                    // 游戏已经开始(例如断线重连的玩家),不再重复下发开局数据
+                    if self.raw.game_state == EServerGameState::KEserverActive
+                        || self.raw.game_start_data.is_none()
+                    {
+                        return Ok(());
+                    }
+
+                    let mut all_load = true;
+                    self.raw.rg_client_data.iter_mut().for_each(|data| {
+                        if data.steam_iduser.steam_id().unwrap() == remote {
+                            data.load_complete = true;
+                        }
+
+                        if !data.load_complete {
+                            all_load = false;
+                        }
+                    });
+
+                    if all_load {
+                        let mut take = self.raw.game_start_data.take().unwrap();
+                        take.game_data.sort_by_key(|f| f.player_position);
+
+                        self.raw
+                            .rg_client_data
+                            .iter()
+                            .chain(self.raw.rg_spectator_data.iter())
+                            .for_each(|f| {
+                                if let Some(conn) = f.hsteam_net_connection.as_ref() {
+                                    self.raw.send_message_ref(&take, conn);
+                                }
+                            });
+                        self.raw.record(|r| r.record_game_start(&take));
+                        self.raw.game_start_sent = Some(take);
+                    }
+                }
+                // 完成认证前不接收也不转发大数据
+                EMessage::KEmsgBlobStart
+                | EMessage::KEmsgBlobChunk
+                | EMessage::KEmsgBlobAck
+                | EMessage::KEmsgBlobComplete
+                    if self.raw.client_connection(remote.raw()).is_none() =>
+                {
+                    return Err(DecodeError::Unexpected(header));
+                }
+                EMessage::KEmsgBlobStart => {
+                    let mut msg = self.raw.codec.decode::<MsgBlobStart>(body)?;
+                    msg.source = remote.raw();
+                    if msg.target == self.raw.server_id {
+                        self.raw.blobs.on_start(msg);
+                    } else if !self.raw.relay_blob(remote, msg.target, &msg) {
+                        let reply = MsgBlobComplete {
+                            transfer_id: msg.transfer_id,
+                            target: remote.raw(),
+                            source: msg.target,
+                            error: "peer not connected".to_string(),
+                        };
+                        if let Some(conn) = self.raw.client_connection(remote.raw()) {
+                            self.raw.send_message(reply, conn);
+                        }
+                    }
+                }
+                EMessage::KEmsgBlobChunk => {
+                    let mut msg = self.raw.codec.decode::<MsgBlobChunk>(body)?;
+                    msg.source = remote.raw();
+                    if msg.target == self.raw.server_id {
+                        self.raw.blobs.on_chunk(msg);
+                    } else {
+                        self.raw.relay_blob(remote, msg.target, &msg);
+                    }
+                }
+                EMessage::KEmsgBlobAck => {
+                    let mut msg = self.raw.codec.decode::<MsgBlobAck>(body)?;
+                    msg.source = remote.raw();
+                    if msg.target == self.raw.server_id {
+                        self.raw.blobs.on_ack(msg, now());
+                    } else {
+                        self.raw.relay_blob(remote, msg.target, &msg);
+                    }
+                }
+                EMessage::KEmsgBlobComplete => {
+                    let mut msg = self.raw.codec.decode::<MsgBlobComplete>(body)?;
+                    msg.source = remote.raw();
+                    if msg.target == self.raw.server_id {
+                        self.raw.blobs.on_complete(msg);
+                    } else {
+                        self.raw.relay_blob(remote, msg.target, &msg);
+                    }
+                }
+                _ => return Err(DecodeError::Unexpected(header)),
+            }
+
+            Ok(())
+        }
+
+        /// 记录无法解析的消息,超过上限时断开该连接
+        fn report_decode_error(&mut self, remote: SteamId, err: DecodeError) {
+            let (count, disconnect) = self.raw.decode_errors.record(remote.raw());
+
+            #[cfg(feature = "dev")]
+            dbg!("bad message", remote, count, err.to_string());
+
+            if let Some(fun) = self.decode_error.as_ref() {
+                fun.call(
+                    DecodeErrorEvent {
+                        steam_id: BigInt::from(remote.raw()),
+                        error: err.to_string(),
+                        count,
+                    },
+                    ThreadsafeFunctionCallMode::Blocking,
+                );
+            }
+
+            if disconnect {
+                let data = self.raw.remove_player_from_server(
+                    remote,
+                    EDisconnectReason::EDRBadMessage,
+                    Some("Too many malformed messages"),
+                );
+                self.notify_removed(data);
+            }
+        }
+
+        #[napi]
+        pub fn run_callbacks(&mut self, env: Env, dt: f64) {
+            self.raw.run_callbacks();
+            self.receive();
+            self.notify_state_changed();
+            self.receive_network_data(env);
+            self.drop_expired_reconnects();
+            self.drop_timed_out_clients();
+            self.raw.send_heartbeat();
+            self.raw.send_blobs();
+            self.blob_callbacks.notify(self.raw.blobs.take_events());
+            self.resume_expired_pause();
+
             if !self.raw.setp {
                 return;
             };
@@ -581,6 +1455,166 @@ pub mod steamp2p {
                 self.raw.dt_total -= self.raw.interval;
 
                 self.raw.dispatch_message();
+
+                let interval = self.raw.frame_advantage_interval;
+                if interval > 0 && self.raw.frame_id.is_multiple_of(interval) {
+                    self.report_frame_advantage();
+                }
+            }
+        }
+
+        /// 上报每个玩家的操作提前量并重置统计
+        fn report_frame_advantage(&mut self) {
+            let players = self
+                .raw
+                .rg_client_data
+                .iter_mut()
+                .map(|f| {
+                    let advantage = FrameAdvantage {
+                        steam_id: BigInt::from(f.steam_iduser.steam_id().unwrap().raw()),
+                        player_position: f.player_position,
+                        advantage: f.input_stats.last_advantage,
+                        average_advantage: f.input_stats.average_advantage(),
+                        late_inputs: f.input_stats.late_inputs,
+                        dropped_inputs: f.input_stats.dropped_inputs,
+                    };
+                    f.input_stats.reset();
+                    advantage
+                })
+                .collect::<Vec<FrameAdvantage>>();
+
+            if let Some(fun) = self.frame_advantage.as_ref() {
+                fun.call(players, ThreadsafeFunctionCallMode::Blocking);
+            }
+        }
+
+        /// 玩家暂停超过最长时间,或者发起暂停的玩家已经离开(被踢出、超时、重连超时)后自动恢复
+        fn resume_expired_pause(&mut self) {
+            let server = &self.raw;
+            let expired = match server.paused_by {
+                Some(0) | None => false,
+                Some(steam_id) => {
+                    (server.max_pause_time > 0 && now() - server.pause_time > server.max_pause_time)
+                        || !server
+                            .rg_client_data
+                            .iter()
+                            .any(|f| f.steam_iduser.steam_id().unwrap().raw() == steam_id)
+                }
+            };
+
+            if expired {
+                #[cfg(feature = "dev")]
+                dbg!("pause expired, resuming");
+
+                let msg = self.raw.resume(0);
+                self.notify_resumed(msg);
+            }
+        }
+
+        /// 断开长时间没有数据的客户端,以及没有按时完成验证的客户端
+        fn drop_timed_out_clients(&mut self) {
+            let time = now();
+            let server = &mut self.raw;
+
+            if server.auth_timeout > 0 {
+                let mut expired = Vec::new();
+                server.rg_pending_client_data.retain_mut(|f| {
+                    if time - f.ul_tick_count_last_data <= server.auth_timeout {
+                        return true;
+                    }
+
+                    #[cfg(feature = "dev")]
+                    dbg!("pending client timed out");
+
+                    if let Some(conn) = f.hsteam_net_connection.take() {
+                        conn.close(
+                            EDisconnectReason::EDRClientTimeout.into(),
+                            Some("Authentication timed out"),
+                            false,
+                        );
+                    }
+
+                    if f.active {
+                        if let Some(raw) = server.server_raw.as_ref() {
+                            raw.end_authentication_session(f.steam_iduser.steam_id().unwrap());
+                        }
+                    }
+
+                    expired.extend(f.steam_iduser.steam_id().map(|f| f.raw()));
+                    false
+                });
+
+                expired
+                    .into_iter()
+                    .for_each(|f| server.release_player_state(f));
+            }
+
+            if server.client_timeout <= 0 {
+                return;
+            }
+
+            let timeout = server.client_timeout;
+            let timed_out = |f: &ClientConnectionData| {
+                !f.is_disconnected() && time - f.ul_tick_count_last_data > timeout
+            };
+
+            while let Some(index) = server.rg_spectator_data.iter().position(timed_out) {
+                let remote = server.rg_spectator_data[index]
+                    .steam_iduser
+                    .steam_id()
+                    .unwrap();
+                if let Some(data) = server.remove_player_from_server(
+                    remote,
+                    EDisconnectReason::EDRClientTimeout,
+                    Some("Timed out"),
+                ) {
+                    Self::notify_player(self.spectator_left.as_ref(), &data, server.frame_id);
+                }
+            }
+
+            while let Some(index) = server.rg_client_data.iter().position(timed_out) {
+                println!("client timed out, dropping connection");
+
+                let frame_id = server.frame_id;
+                match server.on_player_connection_lost(
+                    index,
+                    EDisconnectReason::EDRClientTimeout.into(),
+                    Some("Timed out"),
+                ) {
+                    ConnectionLost::Reconnecting(index) => {
+                        Self::notify_player(
+                            self.player_disconnected.as_ref(),
+                            &server.rg_client_data[index],
+                            frame_id,
+                        );
+                    }
+                    ConnectionLost::Removed(data) => {
+                        Self::notify_player(self.player_left.as_ref(), &data, frame_id);
+                    }
+                }
+            }
+        }
+
+        fn drop_expired_reconnects(&mut self) {
+            let server = &mut self.raw;
+            let time = now();
+
+            let mut index = 0;
+            while index < server.rg_client_data.len() {
+                let data = &server.rg_client_data[index];
+                match data.disconnected_time {
+                    Some(t) if time - t > server.reconnect_timeout => {
+                        #[cfg(feature = "dev")]
+                        dbg!("reconnect timeout, removing player");
+
+                        let data = server.rg_client_data.remove(index);
+                        if let Some(steam_id) = data.steam_iduser.steam_id() {
+                            server.release_player_state(steam_id.raw());
+                        }
+                        Self::notify_player(self.player_left.as_ref(), &data, server.frame_id);
+                    }
+                    _ => index += 1,
+                }
             }
         }
 
@@ -589,91 +1623,413 @@ pub mod steamp2p {
             self.raw.setp = true;
             self.raw.dt_total = 0.0;
             self.raw.frame_id = 0;
+            self.raw.frame_history.clear();
+            self.raw.input_buffer.clear();
+            self.raw.checksums.clear();
+            self.raw.paused_by = None;
+            self.raw
+                .rg_client_data
+                .iter_mut()
+                .for_each(|f| f.pauses_used = 0);
+            self.raw.game_state = EServerGameState::KEserverActive;
         }
 
+        /// 暂停游戏并通知所有客户端
         #[napi]
         pub fn setp_pause(&mut self) {
-            self.raw.setp = false;
+            let msg = self.raw.pause(0);
+            self.notify_paused(msg);
         }
 
+        /// 恢复游戏并通知所有客户端,可以恢复任何玩家发起的暂停
         #[napi]
         pub fn setp_resume(&mut self) {
+            let msg = self.raw.resume(0);
+            self.notify_resumed(msg);
+        }
+
+        #[napi]
+        pub fn setp_close(&mut self) {
             self.raw.setp = false;
+            self.raw.paused_by = None;
+        }
+
+        #[napi]
+        pub fn is_paused(&self) -> bool {
+            self.raw.paused_by.is_some()
+        }
+
+        /// 设置每个玩家每局可以暂停的次数,为0时客户端不能暂停
+        #[napi]
+        pub fn set_pause_budget(&mut self, count: u32) {
+            self.raw.pause_budget = count;
+        }
+
+        /// 设置玩家暂停超过多久(毫秒)自动恢复,为0时不自动恢复,发起暂停的玩家离开后总是恢复
+        #[napi]
+        pub fn set_max_pause_time(&mut self, time: u32) {
+            self.raw.max_pause_time = time as i64;
+        }
+
+        #[napi]
+        pub fn is_connected_to_steam(&self) -> bool {
+            self.raw.is_connected_to_steam
+        }
+
+        #[napi]
+        pub fn is_policy_response_callback(&self) -> bool {
+            self.raw.policy_response_callback
+        }
+
+        /// 设置应用ID
+        #[napi]
+        pub fn set_appid(&mut self, appid: u32) {
+            self.raw.set_appid(appid);
+        }
+
+        /// 可以加入一个服务器并同时游戏的最大玩家数量
+        #[napi]
+        pub fn set_max_player(&mut self, max: u8) {
+            self.raw.set_max_player(max);
+        }
+
+        /// 设置应用名称
+        #[napi]
+        pub fn set_app_name(&mut self, name: String) {
+            self.raw.set_app_name(name);
+        }
+
+        /// 设置地图名称
+        #[napi]
+        pub fn set_map_name(&mut self, name: String) {
+            self.raw.set_map_name(name);
+        }
+
+        /// 设置服务器名称
+        #[napi]
+        pub fn set_server_name(&mut self, name: String) {
+            self.raw.set_server_name(name);
+        }
+
+        /// 设置机器人的数量
+        #[napi]
+        pub fn set_bot_player_count(&mut self, bot: u32) {
+            self.raw.set_bot_player_count(bot);
+        }
+
+        /// 设置服务器浏览器中是否显示需要密码
+        #[napi]
+        pub fn set_password_protected(&mut self, protected: bool) {
+            self.raw.set_password_protected(protected);
+        }
+
+        /// 设置服务器规则,`value` 为空时删除
+        #[napi]
+        pub fn set_server_rule(&mut self, key: String, value: Option<String>) {
+            self.raw.set_server_rule(key, value);
+        }
+
+        /// 清空服务器规则
+        #[napi]
+        pub fn clear_server_rules(&mut self) {
+            self.raw.clear_server_rules();
+        }
+
+        /// 设置游戏标签,服务器列表可以按标签过滤,合计最多128字节
+        #[napi]
+        pub fn set_game_tags(&mut self, tags: Vec<String>) {
+            self.raw.set_game_tags(tags);
+        }
+
+        /// 设置游戏数据,服务器列表可以按游戏数据过滤,最多2048字节
+        #[napi]
+        pub fn set_game_data(&mut self, data: String) {
+            self.raw.set_game_data(data);
+        }
+
+        /// 设置服务器所在的地区
+        #[napi]
+        pub fn set_region(&mut self, region: String) {
+            self.raw.set_region(region);
+        }
+
+        /// 设置观战服务器的端口和名称,端口为0时不显示
+        #[napi]
+        pub fn set_spectator_server(&mut self, port: u16, name: String) {
+            self.raw.set_spectator_server(port, name);
+        }
+
+        /// 设置服务器浏览器中显示的玩家名称和分数,玩家没有通过验证时抛出错误
+        #[napi]
+        pub fn set_browser_player(
+            &mut self,
+            steam_id: BigInt,
+            name: String,
+            score: u32,
+        ) -> Result<()> {
+            self.raw.set_browser_player(steam_id, name, score)
+        }
+
+        /// 删除服务器浏览器中显示的玩家
+        #[napi]
+        pub fn remove_browser_player(&mut self, steam_id: BigInt) {
+            self.raw.remove_browser_player(steam_id);
+        }
+
+        /// 把服务器名称、规则、标签和玩家等信息推送到服务器浏览器
+        ///
+        /// 多个房间共用一个游戏服务器时,和其他房间推送的信息合并后显示
+        ///
+        /// 没有设置最大玩家数量,或者名称、规则等字符串中含有空字符时抛出错误
+        #[napi]
+        pub fn send_updated_server_details_to_steam(&self) -> Result<()> {
+            self.raw.send_updated_server_details_to_steam()
+        }
+
+        /// 设置FPS
+        #[napi]
+        pub fn set_interval(&mut self, interval: f64) {
+            self.raw.set_interval(interval);
+        }
+
+        /// 获取游戏服务器的steam 唯一ID
+        #[napi]
+        pub fn get_server_steam_id(&self) -> u64 {
+            self.raw.get_server_steam_id()
+        }
+
+        /// 设置当前服务器的大厅唯一ID,也是客户端加入时用来找到房间的ID
+        #[napi]
+        pub fn set_lobby_id(&mut self, lobby_id: BigInt) {
+            self.raw.set_lobby_id(lobby_id);
+        }
+
+        ///  获取大厅唯一ID
+        #[napi]
+        pub fn get_lobby_id(&self) -> u64 {
+            self.raw.lobby_id
+        }
+
+        /// 设置断线重连的等待时间(毫秒),为0时玩家掉线后直接移除
+        #[napi]
+        pub fn set_reconnect_timeout(&mut self, timeout: u32) {
+            self.raw.set_reconnect_timeout(timeout);
+        }
+
+        /// 指定玩家的槽位,玩家加入时如果槽位空闲就使用这个槽位
+        #[napi]
+        pub fn set_player_slot(&mut self, steam_id: BigInt, slot: u32) {
+            self.raw.set_player_slot(steam_id, slot);
+        }
+
+        /// 从大厅成员数据的 `key` 读取玩家指定的槽位,为空时不读取,不能占用其他玩家保留的槽位
+        #[napi]
+        pub fn set_slot_lobby_key(&mut self, key: Option<String>) {
+            self.raw.set_slot_lobby_key(key);
+        }
+
+        /// 设置服务器缓存的历史帧数量,需要覆盖断线重连的等待时间
+        #[napi]
+        pub fn set_frame_history_size(&mut self, size: u32) {
+            self.raw.set_frame_history_size(size);
+        }
+
+        /// 可以同时观战的最大人数,观战者不占用玩家槽位
+        #[napi]
+        pub fn set_max_spectators(&mut self, max: u8) {
+            self.raw.set_max_spectators(max);
+        }
+
+        /// 设置观战者延迟接收的帧数,超过服务器缓存的历史帧数量时取历史帧数量减一
+        #[napi]
+        pub fn set_spectator_delay(&mut self, frames: u32) {
+            self.raw.set_spectator_delay(frames);
+        }
+
+        /// 获取当前观战人数
+        #[napi]
+        pub fn get_spectator_count(&self) -> u32 {
+            self.raw.get_spectator_count()
+        }
+
+        /// 设置目标帧已经下发的操作如何处理,默认丢弃
+        #[napi]
+        pub fn set_late_input_policy(&mut self, policy: ELateInputPolicy) {
+            self.raw.set_late_input_policy(policy);
+        }
+
+        /// 设置客户端超过多久(毫秒)没有发送任何数据视为超时,为0时不检测(默认不检测)
+        #[napi]
+        pub fn set_client_timeout(&mut self, timeout: u32) {
+            self.raw.set_client_timeout(timeout);
+        }
+
+        /// 设置等待验证的客户端超过多久(毫秒)没有完成验证就断开,为0时不检测(默认不检测)
+        #[napi]
+        pub fn set_auth_timeout(&mut self, timeout: u32) {
+            self.raw.set_auth_timeout(timeout);
+        }
+
+        /// 设置向客户端发送心跳的间隔(毫秒)
+        #[napi]
+        pub fn set_heartbeat_interval(&mut self, interval: u32) {
+            self.raw.set_heartbeat_interval(interval);
+        }
+
+        /// 设置应用版本号,和客户端不一致时拒绝加入,为空时只比较协议版本
+        #[napi]
+        pub fn set_build_version(&mut self, build: String) {
+            self.raw.set_build_version(build);
+        }
+
+        /// 踢出玩家(或观战者),不保留重连槽位
+        ///
+        /// 返回 `false` 表示该玩家不在服务器中
+        #[napi]
+        pub fn kick(&mut self, steam_id: BigInt, reason: Option<String>) -> bool {
+            let remote = SteamId::from_raw(steam_id.get_u64().1);
+            let data = self.raw.remove_player_from_server(
+                remote,
+                EDisconnectReason::EDRClientKicked,
+                Some(reason.as_deref().unwrap_or("Kicked")),
+            );
+
+            self.notify_removed(data)
+        }
+
+        /// 封禁玩家并踢出本房间,`duration_secs` 为0时永久封禁
+        ///
+        /// 封禁列表由同一进程中的所有房间共用,被封禁的玩家在连接被接受之前就会被拒绝,
+        /// 但不会断开其他房间中已有的连接
+        #[napi]
+        pub fn ban(&mut self, steam_id: BigInt, duration_secs: u32) {
+            let remote = SteamId::from_raw(steam_id.get_u64().1);
+            self.raw.ban(steam_id, duration_secs);
+
+            let data = self.raw.remove_player_from_server(
+                remote,
+                EDisconnectReason::EDRClientBanned,
+                Some("Banned"),
+            );
+            self.notify_removed(data);
+        }
+
+        #[napi]
+        pub fn unban(&mut self, steam_id: BigInt) {
+            self.raw.unban(steam_id);
+        }
+
+        #[napi]
+        pub fn is_banned(&mut self, steam_id: BigInt) -> bool {
+            self.raw.is_banned(SteamId::from_raw(steam_id.get_u64().1))
+        }
+
+        /// 禁言玩家,该玩家的广播消息会被丢弃
+        #[napi]
+        pub fn mute(&mut self, steam_id: BigInt) {
+            self.raw.mute(steam_id);
+        }
+
+        #[napi]
+        pub fn unmute(&mut self, steam_id: BigInt) {
+            self.raw.unmute(steam_id);
+        }
+
+        fn notify_removed(&self, data: Option<ClientConnectionData>) -> bool {
+            match data {
+                Some(data) => {
+                    let handler = if data.spectator {
+                        self.spectator_left.as_ref()
+                    } else {
+                        self.player_left.as_ref()
+                    };
+                    Self::notify_player(handler, &data, self.raw.frame_id);
+
+                    true
+                }
+                None => false,
+            }
         }
 
+        /// 设置某一类操作的最大字节数和每秒最多提交次数,为0时不限制
         #[napi]
-        pub fn setp_close(&mut self) {
-            self.raw.setp = false;
+        pub fn set_input_rule(&mut self, types: u32, max_size: u32, max_per_second: u32) {
+            self.raw.set_input_rule(types, max_size, max_per_second);
         }
 
         #[napi]
-        pub fn is_connected_to_steam(&self) -> bool {
-            self.raw.is_connected_to_steam
+        pub fn remove_input_rule(&mut self, types: u32) {
+            self.raw.remove_input_rule(types);
         }
 
+        /// 为true时拒绝没有注册规则的操作类型
         #[napi]
-        pub fn is_policy_response_callback(&self) -> bool {
-            self.raw.policy_response_callback
+        pub fn set_input_rules_strict(&mut self, strict: bool) {
+            self.raw.set_input_rules_strict(strict);
         }
 
-        /// 设置应用ID
+        /// 玩家操作违规达到多少次后踢出,为0时不踢出
         #[napi]
-        pub fn set_appid(&mut self, appid: u32) {
-            self.raw.set_appid(appid);
+        pub fn set_max_input_violations(&mut self, count: u32) {
+            self.raw.set_max_input_violations(count);
         }
 
-        /// 可以加入一个服务器并同时游戏的最大玩家数量
+        /// 设置一个连接最多发送多少条无法解析的消息,超过后断开,为0时不断开
         #[napi]
-        pub fn set_max_player(&mut self, max: u8) {
-            self.raw.set_max_player(max);
+        pub fn set_max_decode_errors(&mut self, count: u32) {
+            self.raw.set_max_decode_errors(count);
         }
 
-        /// 设置应用名称
+        /// 设置超过多少字节的消息压缩后发送,为0时不压缩
         #[napi]
-        pub fn set_app_name(&mut self, name: String) {
-            self.raw.set_app_name(name);
+        pub fn set_compression_threshold(&mut self, bytes: u32) {
+            self.raw.set_compression_threshold(bytes);
         }
 
-        /// 设置地图名称
+        /// 向玩家或观战者发送大数据(地图、存档等),返回传输ID
+        ///
+        /// 在单独的通道上按带宽限制发送,不会阻塞帧数据,断线重连后从断点继续
         #[napi]
-        pub fn set_map_name(&mut self, name: String) {
-            self.raw.set_map_name(name);
+        pub fn send_blob(&mut self, steam_id: BigInt, buffer: Buffer, meta: String) -> Result<u32> {
+            self.raw.send_blob(steam_id, buffer, meta)
         }
 
-        /// 设置服务器名称
+        /// 设置大数据每秒最多发送的字节数,为0时不限制
         #[napi]
-        pub fn set_server_name(&mut self, name: String) {
-            self.raw.set_server_name(name);
+        pub fn set_blob_bandwidth(&mut self, bytes_per_second: u32) {
+            self.raw.set_blob_bandwidth(bytes_per_second);
         }
 
-        /// 设置机器人的数量
+        /// 设置客户端最多可以提前多少帧提交操作
         #[napi]
-        pub fn set_bot_player_count(&mut self, bot: u32) {
-            self.raw.set_bot_player_count(bot);
+        pub fn set_max_input_lead(&mut self, frames: u32) {
+            self.raw.set_max_input_lead(frames);
         }
 
-        /// 设置FPS
+        /// 设置每隔多少帧上报一次操作提前量,为0时不上报
         #[napi]
-        pub fn set_interval(&mut self, interval: f64) {
-            self.raw.set_interval(interval);
+        pub fn set_frame_advantage_interval(&mut self, frames: u32) {
+            self.raw.set_frame_advantage_interval(frames);
         }
 
-        /// 获取游戏服务器的steam 唯一ID
+        /// 设置每一帧附带之前的帧数(默认3帧),帧数据不可靠发送,丢包时由后面的帧补上
+        ///
+        /// 为0时不附带之前的帧,帧数据改为可靠发送
         #[napi]
-        pub fn get_server_steam_id(&self) -> u64 {
-            self.raw.get_server_steam_id()
+        pub fn set_frame_redundancy(&mut self, frames: u32) {
+            self.raw.set_frame_redundancy(frames);
         }
 
-        /// 设置当前服务器的大厅唯一ID
+        /// 开始录像,记录开局数据、每一帧、广播以及玩家进出
         #[napi]
-        pub fn set_lobby_id(&mut self, lobby_id: BigInt) {
-            self.raw.set_lobby_id(lobby_id);
+        pub fn start_recording(&mut self, path: String) -> Result<()> {
+            self.raw.start_recording(path)
         }
 
-        ///  获取大厅唯一ID
+        /// 停止录像并写入文件
         #[napi]
-        pub fn get_lobby_id(&self) -> u64 {
-            self.raw.lobby_id
+        pub fn stop_recording(&mut self) {
+            self.raw.stop_recording();
         }
 
         /// 初始化参数
@@ -714,9 +2070,70 @@ pub mod steamp2p {
             );
         }
 
+        /// 打开服务器,失败时错误信息以错误码开头,例如 `LobbyNotSet: lobby id is not set`
+        ///
+        /// 同一进程中的多个服务器共用一个游戏服务器和监听,按握手中的大厅ID分配连接
+        #[napi]
+        pub fn open(&mut self) -> Result<()> {
+            self.raw.open()
+        }
+
+        /// 获取服务器的生命周期状态
+        #[napi]
+        pub fn get_state(&self) -> EServerState {
+            self.raw.get_state()
+        }
+
+        #[napi(ts_args_type = "callback: (state:EServerState) => void")]
+        pub fn on_state_changed(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<EServerState, ErrorStrategy::Fatal> =
+                handler
+                    .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                    .unwrap();
+            self.state_changed = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_state_changed");
+        }
+
+        /// 关闭服务器,断开所有客户端并退出 steam,之后可以重新 `open()`
+        ///
+        /// 返回 `false` 表示服务器没有打开
         #[napi]
-        pub fn open(&mut self) {
-            self.raw.open();
+        pub fn shutdown(&mut self, reason: Option<String>) -> bool {
+            let reason = reason.unwrap_or_else(|| "Server closed".to_string());
+            if !self.raw.shutdown(Some(reason.clone())) {
+                return false;
+            }
+
+            self.notify_state_changed();
+            if let Some(fun) = self.shutdown_cb.as_ref() {
+                fun.call(reason, ThreadsafeFunctionCallMode::Blocking);
+            }
+
+            true
+        }
+
+        #[napi(ts_args_type = "callback: (reason:string) => void")]
+        pub fn on_shutdown(&mut self, handler: JsFunction) {
+            let threadsafe_handler: ThreadsafeFunction<String, ErrorStrategy::Fatal> = handler
+                .create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))
+                .unwrap();
+            self.shutdown_cb = Some(threadsafe_handler);
+
+            #[cfg(feature = "dev")]
+            dbg!("on_shutdown");
+        }
+
+        /// 根据服务器的标志更新生命周期状态并通知 JS
+        fn notify_state_changed(&mut self) {
+            self.raw.refresh_state();
+
+            for state in self.raw.lifecycle.take_changes() {
+                if let Some(fun) = self.state_changed.as_ref() {
+                    fun.call(state.into(), ThreadsafeFunctionCallMode::Blocking);
+                }
+            }
         }
     }
 
@@ -728,9 +2145,11 @@ pub mod steamp2p {
         GSPolicyResponseCallback(GSPolicyResponseCallback),
     }
 
+    /// `codec` 为空时使用 MessagePack,需要和客户端一致
     #[napi]
-    pub fn create_async_server() -> SteamServerManager {
+    pub fn create_async_server(codec: Option<ECodec>) -> SteamServerManager {
         let mut server = JsSteamServer::new();
+        server.codec = codec.map_or(CodecKind::default(), CodecKind::from);
         let (tx, rx) = channel();
 
         server.send = Some(tx);
@@ -747,6 +2166,22 @@ pub mod steamp2p {
             steam_servers_disconnected: None,
             all_ready_to_go: None,
             gspolicy_response_callback: None,
+            player_disconnected: None,
+            player_reconnected: None,
+            player_left: None,
+            spectator_joined: None,
+            spectator_left: None,
+            frame_advantage: None,
+            desync: None,
+            input_violation: None,
+            input_validator: None,
+            paused: None,
+            resumed: None,
+            version_mismatch: None,
+            decode_error: None,
+            blob_callbacks: BlobCallbacks::default(),
+            state_changed: None,
+            shutdown_cb: None,
         }
     }
 
@@ -758,6 +2193,7 @@ pub mod steamp2p {
                 is_connected_to_steam: false,
                 policy_response_callback: false,
                 can_close: false,
+                lifecycle: ServerLifecycle::default(),
                 setp: false,
                 dt_total: 0.0,
                 interval: 0f64,
@@ -766,9 +2202,17 @@ pub mod steamp2p {
                 rg_client_data: Vec::<ClientConnectionData>::new(),
                 rg_pending_client_data: Vec::<ClientConnectionData>::new(),
                 max_players: 0,
+                slots: SlotTable::default(),
+                slot_lobby_key: None,
+                rg_spectator_data: Vec::<ClientConnectionData>::new(),
+                max_spectators: 0,
+                spectator_delay: 0,
                 name: String::from(""),
                 map_name: String::from(""),
                 server_name: String::from(""),
+                browser: BrowserDetails::default(),
+                build: String::new(),
+                codec: CodecKind::default(),
                 server_id: 0,
                 player_bot: 0,
                 lobby_id: 0,
@@ -783,138 +2227,535 @@ pub mod steamp2p {
                 server_mode: EServerMode::EServerModeNoAuthentication,
                 pch_version_string: String::from(""),
 
-                server_raw: None,
-                server_single: None,
-                server_sockets: None,
-                listen_socket: None,
-                net_poll_group: None,
+                server_raw: None,
+                server_single: None,
+                server_sockets: None,
+                room: None,
+                routed_packets: VecDeque::new(),
+                net_poll_group: None,
+
+                utils: None,
+
+                handle: None,
+                send: None,
+
+                frame_id: 0,
+                input_buffer: InputBuffer::new(DEFAULT_MAX_INPUT_LEAD, LateInputPolicy::Drop),
+                frame_advantage_interval: 30,
+                frame_redundancy: DEFAULT_FRAME_REDUNDANCY,
+                checksums: ChecksumTracker::new(DEFAULT_CHECKSUM_WINDOW),
+                input_rules: InputRules::default(),
+                max_input_violations: 0,
+                decode_errors: DecodeErrors::default(),
+                compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+                chunks: ChunkAssembler::default(),
+                blobs: BlobTransfers::default(),
+                muted: HashSet::new(),
+                client_timeout: 0,
+                auth_timeout: 0,
+                heartbeat_interval: 1_000,
+                last_heartbeat_time: 0,
+                paused_by: None,
+                pause_time: 0,
+                pause_budget: 3,
+                max_pause_time: 0,
+                game_start_data: Some(MsgServerGameStart {
+                    game_data: vec![],
+                    buffer_size: 0,
+                }),
+                game_start_sent: None,
+
+                reconnect_timeout: 30_000,
+                frame_history: FrameHistory::new(DEFAULT_FRAME_HISTORY_SIZE),
+                recorder: None,
+            };
+
+            server
+        }
+
+        #[napi]
+        pub fn run_callbacks(&self) {
+            if let Some(single) = self.server_single.as_ref() {
+                single.run_callbacks();
+            }
+        }
+
+        #[napi]
+        pub fn dispatch_message(&mut self) {
+            self.frame_id += 1;
+
+            let mut msg = MsgServerFramesData {
+                game_data: Vec::<MsgServerFrameData>::new(),
+                buffer_size: 0,
+                frame_id: self.frame_id,
+                redundant: vec![],
+            };
+
+            msg.game_data = self.input_buffer.take(self.frame_id);
+            msg.buffer_size = msg
+                .game_data
+                .iter()
+                .fold(0, |acc, e| acc + e.data.len() as u32);
+
+            self.send_frames_data(&msg);
+
+            self.record(|r| r.record_frames(&msg));
+            self.frame_history.push(msg);
+
+            // 观战者延迟接收,防止通过观战作弊
+            if self.spectator_delay > 0 && self.frame_id > self.spectator_delay {
+                let delayed = self.frame_id - self.spectator_delay;
+                if let Some(frame) = self.frame_history.range(delayed, delayed).next() {
+                    self.rg_spectator_data
+                        .iter()
+                        .filter_map(|f| f.hsteam_net_connection.as_ref())
+                        .for_each(|conn| self.send_frame_reliable(frame, conn));
+                }
+            }
+        }
+
+        /// 下发一帧,默认附带之前的帧并且不可靠发送,关闭冗余时可靠发送
+        fn send_frames_data(&self, msg: &MsgServerFramesData) {
+            let spectators = self
+                .rg_spectator_data
+                .iter()
+                .filter(|_| self.spectator_delay == 0);
+            let conns = self
+                .rg_client_data
+                .iter()
+                .chain(spectators)
+                .filter_map(|f| f.hsteam_net_connection.as_ref());
+
+            if self.frame_redundancy == 0 {
+                conns.for_each(|conn| {
+                    self.send_message_with(msg, conn, SendFlags::RELIABLE_NO_NAGLE, LANE_FRAMES)
+                });
+                return;
+            }
+
+            let from = msg.frame_id.saturating_sub(self.frame_redundancy);
+            let packed = MsgServerFramesData {
+                redundant: self
+                    .frame_history
+                    .range(from, msg.frame_id.saturating_sub(1))
+                    .cloned()
+                    .collect(),
+                ..msg.clone()
+            };
+
+            conns.for_each(|conn| self.send_message_ref(&packed, conn));
+        }
+
+        /// 把 `from..=to` 之间的帧打包后可靠补发给一个客户端
+        fn resend_frames(&self, from: u32, to: u32, conn: &NetConnection<ServerManager>) {
+            pack_resent_frames(self.frame_history.range(from, to))
+                .iter()
+                .for_each(|f| self.send_frame_reliable(f, conn));
+        }
+
+        /// 可靠发送一帧,用于补发的帧和延迟发给观战者的帧
+        fn send_frame_reliable(
+            &self,
+            frame: &MsgServerFramesData,
+            conn: &NetConnection<ServerManager>,
+        ) {
+            self.send_message_with(frame, conn, SendFlags::RELIABLE_NO_NAGLE, LANE_FRAMES);
+        }
+
+        /// 收到一条操作的序号,重复的操作返回false
+        fn accept_input_seq(&mut self, remote: SteamId, seq: u32) -> bool {
+            self.rg_client_data
+                .iter_mut()
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote)
+                .is_none_or(|f| f.input_seq.accept(seq))
+        }
+
+        /// 把服务器名称、规则、标签和玩家等信息推送到服务器浏览器
+        ///
+        /// 多个房间共用一个游戏服务器时,和其他房间推送的信息合并后显示
+        ///
+        /// 没有设置最大玩家数量,或者名称、规则等字符串中含有空字符时抛出错误
+        #[napi]
+        pub fn send_updated_server_details_to_steam(&self) -> Result<()> {
+            if let Some(room) = self.room.as_ref() {
+                #[cfg(feature = "dev")]
+                dbg!("send_updated_server_details_to_steam open");
+
+                if self.max_players == 0 {
+                    return Err(Error::from_reason("max players is not set"));
+                }
+
+                room.send_details_to_steam(ServerDetails {
+                    server_name: self.server_name.clone(),
+                    map_name: self.map_name.clone(),
+                    max_players: self.max_players.into(),
+                    bot_players: self.player_bot,
+                    browser: self.browser.clone(),
+                })
+                .map_err(|e| {
+                    Error::from_reason(format!("server details contain a nul byte: {}", e))
+                })?;
+            }
+
+            Ok(())
+        }
+
+        #[napi]
+        pub fn is_connected_to_steam(&self) -> bool {
+            self.is_connected_to_steam
+        }
+
+        /// 设置应用ID
+        #[napi]
+        pub fn set_appid(&mut self, appid: u32) {
+            self.app_id = appid;
+        }
+
+        /// 可以加入一个服务器并同时游戏的最大玩家数量
+        #[napi]
+        pub fn set_max_player(&mut self, max: u8) {
+            self.max_players = max;
+        }
+
+        /// 设置应用名称
+        #[napi]
+        pub fn set_app_name(&mut self, name: String) {
+            self.name = name;
+        }
+
+        /// 设置地图名称
+        #[napi]
+        pub fn set_map_name(&mut self, name: String) {
+            self.map_name = name;
+        }
+
+        /// 设置服务器名称
+        #[napi]
+        pub fn set_server_name(&mut self, name: String) {
+            self.server_name = name;
+        }
+
+        /// 设置机器人的数量
+        #[napi]
+        pub fn set_bot_player_count(&mut self, bot: u32) {
+            self.player_bot = bot;
+        }
+
+        /// 设置服务器浏览器中是否显示需要密码
+        #[napi]
+        pub fn set_password_protected(&mut self, protected: bool) {
+            self.browser.password_protected = protected;
+        }
+
+        /// 设置服务器规则,`value` 为空时删除
+        #[napi]
+        pub fn set_server_rule(&mut self, key: String, value: Option<String>) {
+            self.browser.set_rule(key, value);
+        }
+
+        /// 清空服务器规则
+        #[napi]
+        pub fn clear_server_rules(&mut self) {
+            self.browser.clear_rules();
+        }
+
+        /// 设置游戏标签,服务器列表可以按标签过滤,合计最多128字节
+        #[napi]
+        pub fn set_game_tags(&mut self, tags: Vec<String>) {
+            self.browser.set_tags(tags);
+        }
+
+        /// 设置游戏数据,服务器列表可以按游戏数据过滤,最多2048字节
+        #[napi]
+        pub fn set_game_data(&mut self, data: String) {
+            self.browser.game_data = data;
+        }
+
+        /// 设置服务器所在的地区
+        #[napi]
+        pub fn set_region(&mut self, region: String) {
+            self.browser.region = region;
+        }
+
+        /// 设置观战服务器的端口和名称,端口为0时不显示
+        #[napi]
+        pub fn set_spectator_server(&mut self, port: u16, name: String) {
+            self.browser.spectator_port = port;
+            self.browser.spectator_name = name;
+        }
+
+        /// 设置服务器浏览器中显示的玩家名称和分数,玩家没有通过验证时抛出错误
+        #[napi]
+        pub fn set_browser_player(
+            &mut self,
+            steam_id: BigInt,
+            name: String,
+            score: u32,
+        ) -> Result<()> {
+            let steam_id = steam_id.get_u64().1;
+            if self.client_connection(steam_id).is_none() {
+                return Err(Error::from_reason("player is not connected"));
+            }
+
+            self.browser.set_player(steam_id, name, score);
+            Ok(())
+        }
+
+        /// 删除服务器浏览器中显示的玩家
+        #[napi]
+        pub fn remove_browser_player(&mut self, steam_id: BigInt) {
+            self.browser.remove_player(steam_id.get_u64().1);
+        }
+
+        /// 设置FPS
+        #[napi]
+        pub fn set_interval(&mut self, interval: f64) {
+            self.interval = 1000.0 / interval / 1000.0;
+        }
+
+        /// 获取游戏服务器的steam 唯一ID
+        #[napi]
+        pub fn get_server_steam_id(&self) -> u64 {
+            self.server_raw.as_ref().unwrap().steam_id().raw()
+        }
+
+        /// 设置当前服务器的大厅唯一ID,也是客户端加入时用来找到房间的ID
+        #[napi]
+        pub fn set_lobby_id(&mut self, lobby_id: BigInt) {
+            self.lobby_id = lobby_id.get_u64().1;
+        }
+
+        ///  获取大厅唯一ID
+        #[napi]
+        pub fn get_lobby_id(&self) -> u64 {
+            self.lobby_id
+        }
+
+        /// 设置断线重连的等待时间(毫秒),为0时玩家掉线后直接移除
+        #[napi]
+        pub fn set_reconnect_timeout(&mut self, timeout: u32) {
+            self.reconnect_timeout = timeout as i64;
+        }
+
+        /// 指定玩家的槽位,玩家加入时如果槽位空闲就使用这个槽位
+        #[napi]
+        pub fn set_player_slot(&mut self, steam_id: BigInt, slot: u32) {
+            self.slots.reserve(steam_id.get_u64().1, slot);
+        }
+
+        /// 从大厅成员数据的 `key` 读取玩家指定的槽位,为空时不读取,不能占用其他玩家保留的槽位
+        #[napi]
+        pub fn set_slot_lobby_key(&mut self, key: Option<String>) {
+            self.slot_lobby_key = key;
+        }
+
+        /// 设置服务器缓存的历史帧数量,需要覆盖断线重连的等待时间
+        #[napi]
+        pub fn set_frame_history_size(&mut self, size: u32) {
+            self.frame_history.set_capacity(size as usize);
+            self.spectator_delay = self.spectator_delay.min(size.saturating_sub(1));
+        }
+
+        /// 可以同时观战的最大人数,观战者不占用玩家槽位
+        #[napi]
+        pub fn set_max_spectators(&mut self, max: u8) {
+            self.max_spectators = max;
+        }
 
-                utils: None,
+        /// 设置观战者延迟接收的帧数,超过服务器缓存的历史帧数量时取历史帧数量减一
+        #[napi]
+        pub fn set_spectator_delay(&mut self, frames: u32) {
+            let max = self.frame_history.capacity().saturating_sub(1) as u32;
+            self.spectator_delay = frames.min(max);
+        }
 
-                handle: None,
-                send: None,
+        /// 获取当前观战人数
+        #[napi]
+        pub fn get_spectator_count(&self) -> u32 {
+            self.rg_spectator_data.len() as u32
+        }
 
-                frame_id: 0,
-                frame_messages: HashMap::new(),
-                frame_messages_size: 0,
-                game_start_data: Some(MsgServerGameStart {
-                    game_data: vec![],
-                    buffer_size: 0,
-                }),
-            };
+        /// 设置目标帧已经下发的操作如何处理,默认丢弃
+        #[napi]
+        pub fn set_late_input_policy(&mut self, policy: ELateInputPolicy) {
+            self.input_buffer.set_policy(policy.into());
+        }
 
-            server
+        /// 设置客户端超过多久(毫秒)没有发送任何数据视为超时,为0时不检测(默认不检测)
+        #[napi]
+        pub fn set_client_timeout(&mut self, timeout: u32) {
+            self.client_timeout = timeout as i64;
         }
 
+        /// 设置等待验证的客户端超过多久(毫秒)没有完成验证就断开,为0时不检测(默认不检测)
         #[napi]
-        pub fn run_callbacks(&self) {
-            if let Some(single) = self.server_single.as_ref() {
-                single.run_callbacks();
-            }
+        pub fn set_auth_timeout(&mut self, timeout: u32) {
+            self.auth_timeout = timeout as i64;
         }
 
+        /// 设置向客户端发送心跳的间隔(毫秒)
         #[napi]
-        pub fn dispatch_message(&mut self) {
-            self.frame_id += 1;
+        pub fn set_heartbeat_interval(&mut self, interval: u32) {
+            self.heartbeat_interval = interval as i64;
+        }
 
-            let mut msg = MsgServerFramesData {
-                game_data: Vec::<MsgServerFrameData>::new(),
-                buffer_size: 0,
-                frame_id: self.frame_id,
+        /// 设置应用版本号,和客户端不一致时拒绝加入,为空时只比较协议版本
+        #[napi]
+        pub fn set_build_version(&mut self, build: String) {
+            self.build = build;
+        }
+
+        /// 封禁玩家,`duration_secs` 为0时永久封禁,不会断开已有的连接
+        ///
+        /// 封禁列表由同一进程中的所有房间共用,被封禁的玩家在连接被接受之前就会被拒绝
+        #[napi]
+        pub fn ban(&mut self, steam_id: BigInt, duration_secs: u32) {
+            let expires = if duration_secs > 0 {
+                Some(now() + i64::from(duration_secs) * 1000)
+            } else {
+                None
             };
 
-            for (_, messages) in self.frame_messages.iter_mut() {
-                msg.game_data.append(messages);
-                msg.buffer_size += messages.iter().fold(0, |acc, e| acc + e.data.len() as u32);
-            }
+            host::ban(steam_id.get_u64().1, expires);
+        }
 
-            self.rg_client_data.iter().for_each(|client| {
-                self.send_message_ref(&msg, client.hsteam_net_connection.as_ref().unwrap());
-            });
+        #[napi]
+        pub fn unban(&mut self, steam_id: BigInt) {
+            host::unban(steam_id.get_u64().1);
+        }
 
-            self.frame_messages.clear();
-            self.frame_messages_size = 0;
+        /// 是否被封禁,同时清除已经过期的封禁
+        pub fn is_banned(&mut self, remote: SteamId) -> bool {
+            host::is_banned(remote.raw())
         }
 
+        /// 禁言玩家,该玩家的广播消息会被丢弃
         #[napi]
-        pub fn send_updated_server_details_to_steam(&self) {
-            if let Some(server) = self.server_raw.as_ref() {
-                #[cfg(feature = "dev")]
-                dbg!("send_updated_server_details_to_steam open");
+        pub fn mute(&mut self, steam_id: BigInt) {
+            self.muted.insert(steam_id.get_u64().1);
+        }
 
-                assert!(self.max_players != 0);
+        #[napi]
+        pub fn unmute(&mut self, steam_id: BigInt) {
+            self.muted.remove(&steam_id.get_u64().1);
+        }
 
-                server.set_max_players(self.max_players as i32);
-                server.set_password_protected(false);
-                server.set_server_name(&self.server_name);
-                server.set_bot_player_count(self.player_bot as i32);
-                server.set_map_name(&self.map_name);
-            }
+        pub fn is_muted(&self, remote: SteamId) -> bool {
+            self.muted.contains(&remote.raw())
         }
 
+        /// 设置某一类操作的最大字节数和每秒最多提交次数,为0时不限制
         #[napi]
-        pub fn is_connected_to_steam(&self) -> bool {
-            self.is_connected_to_steam
+        pub fn set_input_rule(&mut self, types: u32, max_size: u32, max_per_second: u32) {
+            self.input_rules.set_rule(
+                types,
+                InputRule {
+                    max_size,
+                    max_per_second,
+                },
+            );
         }
 
-        /// 设置应用ID
         #[napi]
-        pub fn set_appid(&mut self, appid: u32) {
-            self.app_id = appid;
+        pub fn remove_input_rule(&mut self, types: u32) {
+            self.input_rules.remove_rule(types);
         }
 
-        /// 可以加入一个服务器并同时游戏的最大玩家数量
+        /// 为true时拒绝没有注册规则的操作类型
         #[napi]
-        pub fn set_max_player(&mut self, max: u8) {
-            self.max_players = max;
+        pub fn set_input_rules_strict(&mut self, strict: bool) {
+            self.input_rules.set_strict(strict);
         }
 
-        /// 设置应用名称
+        /// 玩家操作违规达到多少次后踢出,为0时不踢出
         #[napi]
-        pub fn set_app_name(&mut self, name: String) {
-            self.name = name;
+        pub fn set_max_input_violations(&mut self, count: u32) {
+            self.max_input_violations = count;
         }
 
-        /// 设置地图名称
+        /// 设置一个连接最多发送多少条无法解析的消息,超过后断开,为0时不断开
         #[napi]
-        pub fn set_map_name(&mut self, name: String) {
-            self.map_name = name;
+        pub fn set_max_decode_errors(&mut self, count: u32) {
+            self.decode_errors.set_max_errors(count);
         }
 
-        /// 设置服务器名称
+        /// 设置超过多少字节的消息压缩后发送,为0时不压缩
         #[napi]
-        pub fn set_server_name(&mut self, name: String) {
-            self.server_name = name;
+        pub fn set_compression_threshold(&mut self, bytes: u32) {
+            self.compression_threshold = bytes;
         }
 
-        /// 设置机器人的数量
+        /// 向玩家或观战者发送大数据(地图、存档等),返回传输ID
+        ///
+        /// 在单独的通道上按带宽限制发送,不会阻塞帧数据,断线重连后从断点继续
         #[napi]
-        pub fn set_bot_player_count(&mut self, bot: u32) {
-            self.player_bot = bot;
+        pub fn send_blob(&mut self, steam_id: BigInt, buffer: Buffer, meta: String) -> Result<u32> {
+            let steam_id = steam_id.get_u64().1;
+            let known = self
+                .rg_client_data
+                .iter()
+                .chain(self.rg_spectator_data.iter())
+                .any(|f| f.steam_iduser.steam_id().unwrap().raw() == steam_id);
+            if !known {
+                return Err(Error::from_reason("player not connected"));
+            }
+
+            Ok(self.blobs.send(steam_id, buffer.to_vec(), meta))
         }
 
-        /// 设置FPS
+        /// 设置大数据每秒最多发送的字节数,为0时不限制
         #[napi]
-        pub fn set_interval(&mut self, interval: f64) {
-            self.interval = 1000.0 / interval / 1000.0;
+        pub fn set_blob_bandwidth(&mut self, bytes_per_second: u32) {
+            self.blobs.set_bandwidth(bytes_per_second);
         }
 
-        /// 获取游戏服务器的steam 唯一ID
+        /// 设置客户端最多可以提前多少帧提交操作
         #[napi]
-        pub fn get_server_steam_id(&self) -> u64 {
-            self.server_raw.as_ref().unwrap().steam_id().raw()
+        pub fn set_max_input_lead(&mut self, frames: u32) {
+            self.input_buffer.set_max_lead(frames);
         }
 
-        /// 设置当前服务器的大厅唯一ID
+        /// 设置每隔多少帧上报一次操作提前量,为0时不上报
         #[napi]
-        pub fn set_lobby_id(&mut self, lobby_id: BigInt) {
-            self.lobby_id = lobby_id.get_u64().1;
+        pub fn set_frame_advantage_interval(&mut self, frames: u32) {
+            self.frame_advantage_interval = frames;
         }
 
-        ///  获取大厅唯一ID
+        /// 设置每一帧附带之前的帧数(默认3帧),帧数据不可靠发送,丢包时由后面的帧补上
+        ///
+        /// 为0时不附带之前的帧,帧数据改为可靠发送
         #[napi]
-        pub fn get_lobby_id(&self) -> u64 {
-            self.lobby_id
+        pub fn set_frame_redundancy(&mut self, frames: u32) {
+            self.frame_redundancy = frames;
+        }
+
+        /// 开始录像,记录开局数据、每一帧、广播以及玩家进出
+        #[napi]
+        pub fn start_recording(&mut self, path: String) -> Result<()> {
+            self.stop_recording();
+
+            let mut recorder =
+                ReplayRecorder::create(&path).map_err(|e| Error::from_reason(e.to_string()))?;
+
+            // 录像开始时已经在房间中的玩家
+            for data in self.rg_client_data.iter().filter(|f| !f.is_disconnected()) {
+                recorder
+                    .record_player_joined(&data.replay_event(self.frame_id))
+                    .map_err(|e| Error::from_reason(e.to_string()))?;
+            }
+
+            self.recorder = Some(recorder);
+            Ok(())
+        }
+
+        /// 停止录像并写入文件
+        #[napi]
+        pub fn stop_recording(&mut self) {
+            if let Some(mut recorder) = self.recorder.take() {
+                if let Err(e) = recorder.flush() {
+                    println!("failed to flush replay: {}", e);
+                }
+            }
         }
 
         /// 初始化参数
@@ -953,98 +2794,310 @@ pub mod steamp2p {
             self.pch_version_string = pch_version_string;
         }
 
+        /// 打开服务器,失败时错误信息以错误码开头,例如 `LobbyNotSet: lobby id is not set`
+        ///
+        /// 同一进程中的多个服务器共用一个游戏服务器和监听,按握手中的大厅ID分配连接
         #[napi]
-        pub fn open(&mut self) {
+        pub fn open(&mut self) -> Result<()> {
             if self.server_raw.is_some() {
-                return;
+                return Err(OpenError::AlreadyOpen.into());
             }
 
+            self.try_open().map_err(|err| {
+                self.release_server();
+                self.lifecycle.set(ServerState::Idle);
+                err.into()
+            })
+        }
+
+        fn try_open(&mut self) -> std::result::Result<(), OpenError> {
             if self.lobby_id == 0 {
-                return;
+                return Err(OpenError::LobbyNotSet);
             }
 
             if self.max_players == 0 {
-                return;
+                return Err(OpenError::MaxPlayersNotSet);
             }
 
             #[cfg(feature = "dev")]
             dbg!("steam server open entry");
 
+            let server_mode = match self.server_mode {
+                EServerMode::EServerModeNoAuthentication => {
+                    steamworks::ServerMode::NoAuthentication
+                }
+                EServerMode::EServerModeAuthentication => steamworks::ServerMode::Authentication,
+                EServerMode::EServerModeAuthenticationAndSecure => {
+                    steamworks::ServerMode::AuthenticationAndSecure
+                }
+                EServerMode::EServerModeInvalid => {
+                    return Err(OpenError::InvalidServerMode);
+                }
+            };
+
+            self.lifecycle.set(ServerState::Initializing);
             self.connected_success_count = 0;
-            if let Ok((server, single)) = Server::init(
+
+            let host = match ServerHost::current() {
+                Some(host) => {
+                    // 同一进程中的房间共用一个游戏服务器,编码方式和版本必须一致
+                    let info = host.borrow().info().clone();
+                    if info.codec != self.codec as u32 || info.build != self.build {
+                        return Err(OpenError::HostMismatch);
+                    }
+                    host
+                }
+                None => self.create_host(server_mode)?,
+            };
+            let room =
+                RoomHandle::join(host.clone(), self.lobby_id).ok_or(OpenError::LobbyInUse)?;
+
+            let (server, single) = {
+                let host = host.borrow();
+                (host.server(), host.single())
+            };
+            // 共用的游戏服务器可能已经登录,不会再收到登录回调
+            self.is_connected_to_steam = server.logged_on();
+            self.policy_response_callback = server.logged_on();
+            self.server_id = server.steam_id().raw();
+            self.blobs.set_local_id(self.server_id);
+
+            self.server_single = Some(single);
+            self.server_sockets = Some(server.networking_server_sockets());
+            self.utils = Some(server.networking_utils());
+            self.server_raw = Some(server);
+            self.room = Some(room);
+
+            if self.send.is_some() {
+                self.register();
+            }
+            self.lifecycle.set(ServerState::LoggingOn);
+
+            self.player_count = 0;
+            self.game_state = EServerGameState::KEserverWaitingForPlayers;
+
+            let sockets = self.server_sockets.as_ref().unwrap();
+            self.net_poll_group = Some(sockets.create_poll_group());
+
+            #[cfg(feature = "dev")]
+            dbg!("server create_poll_group success");
+
+            self.can_close = true;
+            Ok(())
+        }
+
+        /// 初始化进程中第一个房间使用的游戏服务器并创建监听
+        fn create_host(
+            &self,
+            server_mode: steamworks::ServerMode,
+        ) -> std::result::Result<Rc<RefCell<ServerHost>>, OpenError> {
+            let (server, single) = Server::init(
                 Ipv4Addr::from(self.un_ip),
                 self.us_steam_port,
                 self.us_game_port,
                 self.us_query_port,
-                match self.server_mode {
-                    EServerMode::EServerModeNoAuthentication => {
-                        steamworks::ServerMode::NoAuthentication
-                    }
-                    EServerMode::EServerModeAuthentication => {
-                        steamworks::ServerMode::Authentication
-                    }
-                    EServerMode::EServerModeAuthenticationAndSecure => {
-                        steamworks::ServerMode::AuthenticationAndSecure
-                    }
-                    _ => panic!(),
-                },
+                server_mode,
                 self.pch_version_string.as_str(),
-            ) {
-                self.server_single = Some(single);
-                self.server_sockets = Some(server.networking_server_sockets());
-                self.utils = Some(server.networking_utils());
-                self.server_raw = Some(server);
-
-                if self.send.is_some() {
-                    self.register();
-                }
-            } else {
-                return;
+            )
+            .map_err(|err| OpenError::InitFailed(err.to_string()))?;
+
+            server.set_mod_dir(&self.pch_game_dir);
+            server.set_product(&self.app_id.to_string());
+            server.set_game_description(&self.name);
+            server.log_on_anonymous();
+
+            let client = crate::client::get_client();
+            client.networking_utils().init_relay_network_access();
+
+            if self.server_mode == EServerMode::EServerModeAuthenticationAndSecure {
+                server.enable_heartbeats(true);
             }
 
-            if let Some(server) = self.server_raw.as_ref() {
-                server.set_mod_dir(&self.pch_game_dir);
-                server.set_product(&self.app_id.to_string());
-                server.set_game_description(&self.name);
-                server.log_on_anonymous();
-                self.server_id = server.steam_id().raw();
+            #[cfg(feature = "dev")]
+            dbg!("server init_relay_network_access success");
+
+            let info = MsgServerSendInfo {
+                ul_steam_idserver: server.steam_id().raw(),
+                is_vacsecure: server.secure(),
+                // 握手前不知道连接属于哪个房间,只发送所有房间共用的信息
+                rgch_server_name: String::new(),
+                protocol_version: PROTOCOL_VERSION,
+                build: self.build.clone(),
+                features: SUPPORTED_FEATURES,
+                codec: self.codec as u32,
+            };
+            let host =
+                ServerHost::create(server, single, info).ok_or(OpenError::ListenSocketFailed)?;
 
-                let client = crate::client::get_client();
-                client.networking_utils().init_relay_network_access();
+            #[cfg(feature = "dev")]
+            dbg!("server create_listen_socket_p2p success");
 
-                if self.server_mode == EServerMode::EServerModeAuthenticationAndSecure {
-                    server.enable_heartbeats(true);
-                }
+            Ok(host)
+        }
+
+        /// 在线的玩家、观战者和等待验证的连接占满了所有位置,掉线等待重连的玩家不计算在内
+        fn is_full(&self) -> bool {
+            let connected = self
+                .rg_client_data
+                .iter()
+                .filter(|f| !f.is_disconnected())
+                .count()
+                + self.rg_spectator_data.len()
+                + self.rg_pending_client_data.len();
+
+            connected >= usize::from(self.max_players) + usize::from(self.max_spectators)
+        }
 
+        /// 检查分到本房间的连接能否加入,不能加入时返回断开的原因
+        fn admit(
+            &mut self,
+            remote: &NetworkingIdentity,
+        ) -> Option<(NetConnectionEndReason, &'static str)> {
+            if self.rg_pending_client_data.len()
+                >= usize::from(self.max_players) + usize::from(self.max_spectators)
+            {
                 #[cfg(feature = "dev")]
-                dbg!("server init_relay_network_access success", self.server_id);
+                dbg!("Rejecting connection; server full");
+
+                return Some((
+                    NetConnectionEndReason::NetConnectionEnd(
+                        networking_types::NetConnectionEnd::AppException,
+                    ),
+                    "Server full!",
+                ));
             }
 
-            self.player_count = 0;
-            self.game_state = EServerGameState::KEserverWaitingForPlayers;
+            let steam_id = remote.steam_id().unwrap();
+            if self.is_banned(steam_id) {
+                #[cfg(feature = "dev")]
+                dbg!("Rejecting connection; banned");
 
-            if let Some(sockets) = self.server_sockets.as_ref() {
-                if let Ok(listen) = sockets.create_listen_socket_p2p(0, vec![]) {
-                    #[cfg(feature = "dev")]
-                    dbg!("server create_listen_socket_p2p success");
+                return Some((EDisconnectReason::EDRClientBanned.into(), "Banned"));
+            }
 
-                    self.listen_socket = Some(listen);
-                } else {
-                    return;
+            // 掉线的玩家允许在重连窗口内重新连接
+            let duplicate =
+                self.rg_pending_client_data
+                    .iter()
+                    .any(|f| f.steam_iduser.steam_id() == Some(steam_id))
+                    || self.rg_client_data.iter().any(|f| {
+                        f.steam_iduser.steam_id() == Some(steam_id) && !f.is_disconnected()
+                    })
+                    || self
+                        .rg_spectator_data
+                        .iter()
+                        .any(|f| f.steam_iduser.steam_id() == Some(steam_id));
+            if duplicate {
+                return Some((
+                    NetConnectionEndReason::NetConnectionEnd(
+                        networking_types::NetConnectionEnd::AppException,
+                    ),
+                    "Already connected",
+                ));
+            }
+
+            None
+        }
+
+        /// 关闭服务器,断开所有客户端并退出 steam,之后可以重新 `open()`
+        ///
+        /// 返回 `false` 表示服务器没有打开
+        #[napi]
+        pub fn shutdown(&mut self, reason: Option<String>) -> bool {
+            if self.server_raw.is_none() {
+                return false;
+            }
+            let reason = reason.unwrap_or_else(|| "Server closed".to_string());
+
+            self.lifecycle.set(ServerState::ShuttingDown);
+
+            // 结束验证会话并告知客户端服务器已经关闭
+            let remotes: Vec<SteamId> = self
+                .rg_client_data
+                .iter()
+                .chain(self.rg_spectator_data.iter())
+                .chain(self.rg_pending_client_data.iter())
+                .filter_map(|f| f.steam_iduser.steam_id())
+                .collect();
+            for remote in remotes {
+                self.remove_player_from_server(
+                    remote,
+                    EDisconnectReason::EDRServerClosed,
+                    Some(&reason),
+                );
+            }
+
+            self.stop_recording();
+            // 其他房间还在使用游戏服务器时不退出 steam
+            if self.room.as_ref().is_some_and(|room| room.is_last()) {
+                if let Some(server) = self.server_raw.as_ref() {
+                    server.log_off();
                 }
-            } else {
-                return;
             }
+            self.release_server();
+            self.reset_session();
 
-            if let Some(sockets) = self.server_sockets.as_ref() {
-                self.net_poll_group = Some(sockets.create_poll_group());
+            self.lifecycle.set(ServerState::Idle);
+            true
+        }
 
-                #[cfg(feature = "dev")]
-                dbg!("server create_poll_group success");
-            } else {
-                return;
+        /// 清空上一局的数据,重新打开时从头开始
+        fn reset_session(&mut self) {
+            self.setp = false;
+            self.dt_total = 0.0;
+            self.frame_id = 0;
+            self.player_count = 0;
+            self.connected_success_count = 0;
+            self.game_state = EServerGameState::KEserverExiting;
+            self.frame_history.clear();
+            self.input_buffer.clear();
+            self.checksums.clear();
+            self.decode_errors.clear();
+            self.chunks.clear();
+            self.blobs.clear();
+            self.slots.clear();
+            self.paused_by = None;
+            self.game_start_data = Some(MsgServerGameStart {
+                game_data: vec![],
+                buffer_size: 0,
+            });
+            self.game_start_sent = None;
+        }
+
+        /// 释放 steam 游戏服务器相关的资源,最后一个引用释放时关闭游戏服务器
+        fn release_server(&mut self) {
+            self.can_close = false;
+            self.is_connected_to_steam = false;
+            self.policy_response_callback = false;
+            self.net_poll_group = None;
+            if let Some(handle) = self.handle.as_mut() {
+                handle.clear();
             }
-            self.can_close = true;
+            self.room = None;
+            self.routed_packets.clear();
+            self.utils = None;
+            self.server_sockets = None;
+            self.server_raw = None;
+            self.server_single = None;
+        }
+
+        /// 获取服务器的生命周期状态
+        #[napi]
+        pub fn get_state(&self) -> EServerState {
+            self.lifecycle.state().into()
+        }
+
+        /// 根据已经打开、是否登录 steam、VAC 策略和游戏状态推导生命周期状态
+        fn refresh_state(&mut self) {
+            let policy_ready = self.server_mode != EServerMode::EServerModeAuthenticationAndSecure
+                || self.policy_response_callback;
+            let state = self.lifecycle.state().refresh(
+                self.can_close,
+                self.is_connected_to_steam,
+                policy_ready,
+                self.game_state == EServerGameState::KEserverActive,
+            );
+            self.lifecycle.set(state);
         }
 
         #[napi(ts_args_type = "callback: () => void")]
@@ -1175,73 +3228,176 @@ pub mod steamp2p {
             } else {
                 Handle { handle: None }
             }
-        }
-    }
+        }
+    }
+
+    impl JsSteamServer {
+        pub fn on_client_broadcast(&mut self, mut msg: MsgClientDataBroadcast, remote: SteamId) {
+            // 发送者由服务器标记,不信任客户端自己填写的身份
+            msg.local_steam_id = remote.raw();
+            let server: MsgServerDataBroadcast = msg.into();
+            self.rg_client_data.iter().for_each(|f| {
+                if let Some(conn) = f.hsteam_net_connection.as_ref() {
+                    self.send_message_ref(&server, conn)
+                }
+            });
+            self.send_to_spectators(&server);
+
+            let frame_id = self.frame_id;
+            self.record(|r| r.record_broadcast(frame_id, &server));
+        }
+
+        fn send_to_spectators<T>(&self, msg: &T)
+        where
+            T: INetMessage + serde::Serialize,
+        {
+            self.rg_spectator_data.iter().for_each(|f| {
+                if let Some(conn) = f.hsteam_net_connection.as_ref() {
+                    self.send_message_ref(msg, conn)
+                }
+            });
+        }
+
+        fn is_spectator(&self, remote: SteamId) -> bool {
+            self.rg_spectator_data
+                .iter()
+                .chain(self.rg_pending_client_data.iter().filter(|f| f.spectator))
+                .any(|f| f.steam_iduser.steam_id().unwrap() == remote)
+        }
+
+        /// 写入一条录像记录,写入失败时停止录像
+        fn record<F>(&mut self, f: F)
+        where
+            F: FnOnce(&mut ReplayRecorder) -> std::result::Result<(), ReplayError>,
+        {
+            if let Some(recorder) = self.recorder.as_mut() {
+                if let Err(e) = f(recorder) {
+                    println!("replay recording stopped: {}", e);
+                    self.recorder = None;
+                }
+            }
+        }
+
+        pub fn on_client_frame_data(&mut self, msg: MsgClientFrameData, remote: SteamId) {
+            let data = self
+                .rg_client_data
+                .iter_mut()
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote);
+
+            if let Some(data) = data {
+                let next_frame = self.frame_id + 1;
+                let target = msg.frame_id;
+                let msg = MsgServerFrameData::from_client(msg, remote.raw(), data.player_position);
+                let result = self.input_buffer.insert(next_frame, target, msg);
+
+                #[cfg(feature = "dev")]
+                if result != InputResult::Accepted {
+                    dbg!("input for frame not accepted", target, next_frame, result);
+                }
+
+                data.input_stats.record(next_frame, target, result);
+            }
+        }
+
+        pub fn on_client_games_data(&mut self, msg: MsgClientFrameData, remote: SteamId) {
+            let Some(player) = self
+                .rg_client_data
+                .iter()
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote)
+            else {
+                return;
+            };
+            let Some(data) = self.game_start_data.as_mut() else {
+                return;
+            };
+
+            let values = data
+                .game_data
+                .iter_mut()
+                .find(|f| f.local_steam_id == remote.raw());
+
+            // 一个逻辑帧只接受同一个类型的同一个指令
+            if let Some(d) = values {
+                // 如果有了旧的数据
+                // 就用新数据覆盖
+                data.buffer_size -= d.data.len() as u32;
+                data.buffer_size += msg.data.len() as u32;
+                d.data = msg.data;
+            } else {
+                // 如果没有旧数据
+                // 直接追加
+                data.buffer_size = data.buffer_size + msg.data.len() as u32;
+                data.game_data.push(MsgServerFrameData::from_client(
+                    msg,
+                    remote.raw(),
+                    player.player_position,
+                ));
+            }
+
+            if let Some(conn) = player.hsteam_net_connection.as_ref() {
+                self.send_message(MsgSetGameStartDataComplete, conn);
+            }
+        }
+
+        /// 版本不一致时断开等待验证的客户端
+        pub fn check_client_version(
+            &mut self,
+            remote: SteamId,
+            protocol_version: u32,
+            build: &str,
+            codec: u32,
+        ) -> Option<SteamVersionMismatch> {
+            if is_version_compatible(protocol_version, build, &self.build)
+                && codec == self.codec as u32
+            {
+                return None;
+            }
 
-    impl JsSteamServer {
-        pub fn on_client_broadcast(&mut self, msg: MsgClientDataBroadcast) {
-            let server: MsgServerDataBroadcast = msg.into();
-            self.rg_client_data.iter().for_each(|f| {
-                self.send_message_ref(&server, f.hsteam_net_connection.as_ref().unwrap())
-            });
-        }
+            println!(
+                "client {} version mismatch: protocol {} (local {}), build {:?} (local {:?}), codec {} (local {})",
+                remote.raw(),
+                protocol_version,
+                PROTOCOL_VERSION,
+                build,
+                self.build,
+                codec,
+                self.codec as u32
+            );
 
-        pub fn on_client_frame_data(&mut self, msg: MsgClientFrameData, remote: SteamId) {
-            let data = self
-                .rg_client_data
-                .iter_mut()
-                .find(|f| f.steam_iduser.steam_id().unwrap() == remote);
+            self.rg_pending_client_data.retain_mut(|f| {
+                if f.steam_iduser.steam_id().unwrap() != remote {
+                    return true;
+                }
 
-            if let Some(_) = data.as_ref() {
-                let values = self
-                    .frame_messages
-                    .entry(remote.raw())
-                    .or_insert_with(|| vec![]);
-
-                // 一个逻辑帧只接受同一个类型的同一个指令
-                if let Some(d) = values.iter_mut().find(|v| v.types == msg.types) {
-                    // 如果有了旧的数据
-                    // 就用新数据覆盖
-                    d.data = msg.data;
-                } else {
-                    // 如果没有旧数据
-                    // 直接追加
-                    values.push(msg.into());
-                    self.frame_messages_size += 1;
+                if let Some(conn) = f.hsteam_net_connection.take() {
+                    conn.close(
+                        EDisconnectReason::EDRVersionMismatch.into(),
+                        Some("Version mismatch"),
+                        false,
+                    );
                 }
-            }
+                false
+            });
+
+            Some(SteamVersionMismatch {
+                steam_id: BigInt::from(remote.raw()),
+                protocol_version,
+                build: build.to_string(),
+                codec,
+            })
         }
 
-        pub fn on_client_games_data(&mut self, msg: MsgClientFrameData, remote: SteamId) {
-            self.rg_client_data
+        /// 记录客户端支持的可选功能,保存在连接的用户数据中
+        pub fn set_client_features(&self, remote: SteamId, features: u32) {
+            let conn = self
+                .rg_pending_client_data
                 .iter()
                 .find(|f| f.steam_iduser.steam_id().unwrap() == remote)
-                .and_then(|f| {
-                    let data = self.game_start_data.as_mut().unwrap();
-                    let values = data
-                        .game_data
-                        .iter_mut()
-                        .find(|f| f.local_steam_id == remote.raw());
-
-                    // 一个逻辑帧只接受同一个类型的同一个指令
-                    if let Some(d) = values {
-                        // 如果有了旧的数据
-                        // 就用新数据覆盖
-                        data.buffer_size -= d.data.len() as u32;
-                        data.buffer_size += msg.data.len() as u32;
-                        d.data = msg.data;
-                    } else {
-                        // 如果没有旧数据
-                        // 直接追加
-                        data.buffer_size = data.buffer_size + msg.data.len() as u32;
-                        data.game_data.push(msg.into());
-                    }
+                .and_then(|f| f.hsteam_net_connection.as_ref());
 
-                    Some(f.hsteam_net_connection.as_ref().unwrap())
-                })
-                .map(|conn| {
-                    self.send_message(MsgSetGameStartDataComplete, conn);
-                });
+            if let Some(conn) = conn {
+                let _ = conn.set_connection_user_data((features & SUPPORTED_FEATURES) as i64);
+            }
         }
 
         pub fn on_client_begin_authentication(
@@ -1252,14 +3408,55 @@ pub mod steamp2p {
             if self
                 .rg_client_data
                 .iter()
-                .find(|f| f.steam_iduser.steam_id().unwrap() == remote)
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote && !f.is_disconnected())
                 .is_some()
             {
                 return;
             }
 
-            if self.rg_client_data.len() + self.rg_pending_client_data.len()
-                >= self.max_players.into()
+            // 重连的玩家仍然占用着自己原来的槽位
+            let reconnect = self
+                .rg_client_data
+                .iter()
+                .any(|f| f.steam_iduser.steam_id().unwrap() == remote && f.is_disconnected());
+
+            if auth.spectator {
+                let spectators = self.rg_spectator_data.len()
+                    + self
+                        .rg_pending_client_data
+                        .iter()
+                        .filter(|f| f.spectator && f.active)
+                        .count();
+
+                if spectators >= self.max_spectators.into() {
+                    self.rg_pending_client_data.retain_mut(|f| {
+                        if f.steam_iduser.steam_id().unwrap() != remote {
+                            return true;
+                        }
+
+                        if let Some(conn) = f.hsteam_net_connection.take() {
+                            conn.close(
+                                NetConnectionEndReason::NetConnectionEnd(
+                                    networking_types::NetConnectionEnd::AppException,
+                                ),
+                                Some("Spectator slots full"),
+                                false,
+                            );
+                        }
+
+                        false
+                    });
+
+                    return;
+                }
+            } else if !reconnect
+                && self.rg_client_data.len()
+                    + self
+                        .rg_pending_client_data
+                        .iter()
+                        .filter(|f| !f.spectator)
+                        .count()
+                    >= self.max_players.into()
             {
                 let mut br = false;
                 self.rg_pending_client_data.retain_mut(|f| {
@@ -1306,13 +3503,276 @@ pub mod steamp2p {
 
                     f.ul_tick_count_last_data = now();
                     f.active = true;
+                    f.spectator = auth.spectator;
                 }
 
                 return true;
             });
         }
 
-        pub fn remove_player_from_server(&mut self) {}
+        /// 暂停游戏,`steam_id` 为0时表示服务器,游戏没有在运行时返回 `None`
+        fn pause(&mut self, steam_id: u64) -> Option<MsgServerPaused> {
+            if !self.setp || self.paused_by.is_some() {
+                return None;
+            }
+
+            self.setp = false;
+            self.paused_by = Some(steam_id);
+            self.pause_time = now();
+
+            let msg = MsgServerPaused {
+                frame_id: self.frame_id,
+                steam_id,
+            };
+            self.send_to_all(&msg);
+
+            Some(msg)
+        }
+
+        /// 恢复游戏,没有暂停时返回 `None`
+        fn resume(&mut self, steam_id: u64) -> Option<MsgServerResumed> {
+            self.paused_by.take()?;
+
+            self.setp = true;
+            self.dt_total = 0.0;
+
+            let msg = MsgServerResumed {
+                frame_id: self.frame_id,
+                steam_id,
+            };
+            self.send_to_all(&msg);
+
+            Some(msg)
+        }
+
+        /// 玩家请求暂停,超出暂停次数时忽略
+        pub fn on_client_request_pause(&mut self, remote: SteamId) -> Option<MsgServerPaused> {
+            if !self.setp || self.paused_by.is_some() {
+                return None;
+            }
+
+            let budget = self.pause_budget;
+            let data = self
+                .rg_client_data
+                .iter_mut()
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote)?;
+
+            if data.pauses_used >= budget {
+                #[cfg(feature = "dev")]
+                dbg!("pause request rejected, budget used up");
+
+                return None;
+            }
+            data.pauses_used += 1;
+
+            self.pause(remote.raw())
+        }
+
+        /// 只有发起暂停的玩家可以请求恢复
+        pub fn on_client_request_resume(&mut self, remote: SteamId) -> Option<MsgServerResumed> {
+            if self.paused_by != Some(remote.raw()) {
+                return None;
+            }
+
+            self.resume(remote.raw())
+        }
+
+        fn send_to_all<T>(&self, msg: &T)
+        where
+            T: INetMessage + serde::Serialize,
+        {
+            self.rg_client_data.iter().for_each(|f| {
+                if let Some(conn) = f.hsteam_net_connection.as_ref() {
+                    self.send_message_ref(msg, conn)
+                }
+            });
+            self.send_to_spectators(msg);
+        }
+
+        /// 记录收到客户端数据的时间
+        fn touch(&mut self, remote: SteamId) {
+            if let Some(data) = self
+                .rg_client_data
+                .iter_mut()
+                .chain(self.rg_spectator_data.iter_mut())
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote)
+            {
+                data.ul_tick_count_last_data = now();
+            }
+        }
+
+        /// 已经完成认证的玩家或观战者的连接
+        fn client_connection(&self, steam_id: u64) -> Option<&NetConnection<ServerManager>> {
+            self.rg_client_data
+                .iter()
+                .chain(self.rg_spectator_data.iter())
+                .find(|f| f.steam_iduser.steam_id().unwrap().raw() == steam_id)
+                .and_then(|f| f.hsteam_net_connection.as_ref())
+        }
+
+        /// 转发客户端之间的大数据消息,双方都需要完成认证,对方不在线时返回false
+        fn relay_blob<T>(&self, remote: SteamId, target: u64, msg: &T) -> bool
+        where
+            T: INetMessage + serde::Serialize,
+        {
+            if self.client_connection(remote.raw()).is_none() {
+                return true;
+            }
+
+            match self.client_connection(target) {
+                Some(conn) => {
+                    self.send_message_ref(msg, conn);
+                    true
+                }
+                None => false,
+            }
+        }
+
+        /// 按带宽限制发送大数据,玩家断线时暂停,重连后从断点继续
+        fn send_blobs(&mut self) {
+            self.blobs.poll(now());
+            for msg in self.blobs.take_messages() {
+                let Some(conn) = self.client_connection(msg.target()) else {
+                    continue;
+                };
+
+                match msg {
+                    BlobMessage::Start(msg) => self.send_message(msg, conn),
+                    BlobMessage::Chunk(msg) => self.send_message(msg, conn),
+                    BlobMessage::Ack(msg) => self.send_message(msg, conn),
+                    BlobMessage::Complete(msg) => self.send_message(msg, conn),
+                }
+            }
+        }
+
+        /// 定时向所有客户端发送心跳,让客户端可以检测服务器超时
+        fn send_heartbeat(&mut self) {
+            let time = now();
+            if time - self.last_heartbeat_time < self.heartbeat_interval {
+                return;
+            }
+            self.last_heartbeat_time = time;
+
+            self.rg_client_data
+                .iter()
+                .chain(self.rg_spectator_data.iter())
+                .chain(self.rg_pending_client_data.iter())
+                .for_each(|f| {
+                    if let Some(conn) = f.hsteam_net_connection.as_ref() {
+                        self.send_message(MsgServerHeartbeat, conn);
+                    }
+                });
+        }
+
+        /// 玩家连接断开,游戏中保留槽位等待重连,否则移出服务器
+        fn on_player_connection_lost(
+            &mut self,
+            index: usize,
+            reason: NetConnectionEndReason,
+            debug: Option<&str>,
+        ) -> ConnectionLost {
+            let can_reconnect =
+                self.game_state == EServerGameState::KEserverActive && self.reconnect_timeout > 0;
+            let frame_id = self.frame_id;
+
+            let data = self.rg_client_data.get_mut(index).unwrap();
+            let remote = data.steam_iduser.steam_id().unwrap();
+            if let Some(conn) = data.hsteam_net_connection.take() {
+                conn.close(reason, debug, false);
+            }
+
+            if let Some(raw) = self.server_raw.as_ref() {
+                raw.end_authentication_session(remote);
+            }
+
+            let event = data.replay_event(frame_id);
+            let lost = if can_reconnect {
+                #[cfg(feature = "dev")]
+                dbg!("player disconnected, waiting for reconnect");
+
+                data.disconnected_time = Some(now());
+                data.disconnected_frame_id = frame_id;
+
+                ConnectionLost::Reconnecting(index)
+            } else {
+                self.release_player_state(remote.raw());
+                ConnectionLost::Removed(Box::new(self.rg_client_data.remove(index)))
+            };
+
+            self.record(|r| r.record_player_left(&event));
+            lost
+        }
+
+        /// 按规则检查玩家提交的操作
+        pub fn check_input(
+            &mut self,
+            msg: &MsgClientFrameData,
+            remote: SteamId,
+        ) -> std::result::Result<(), InputViolation> {
+            let time = now();
+            let rules = &self.input_rules;
+
+            match self
+                .rg_client_data
+                .iter_mut()
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote)
+            {
+                Some(data) => rules.check(msg.types, msg.data.len(), &mut data.input_rate, time),
+                None => Ok(()),
+            }
+        }
+
+        /// 断开玩家(或观战者)的连接并移出服务器,不保留重连槽位
+        fn remove_player_from_server(
+            &mut self,
+            remote: SteamId,
+            reason: EDisconnectReason,
+            debug: Option<&str>,
+        ) -> Option<ClientConnectionData> {
+            let mut data = if let Some(index) = self
+                .rg_client_data
+                .iter()
+                .position(|f| f.steam_iduser.steam_id().unwrap() == remote)
+            {
+                self.rg_client_data.remove(index)
+            } else if let Some(index) = self
+                .rg_spectator_data
+                .iter()
+                .position(|f| f.steam_iduser.steam_id().unwrap() == remote)
+            {
+                self.rg_spectator_data.remove(index)
+            } else {
+                let index = self
+                    .rg_pending_client_data
+                    .iter()
+                    .position(|f| f.steam_iduser.steam_id().unwrap() == remote)?;
+                self.rg_pending_client_data.remove(index)
+            };
+
+            if let Some(conn) = data.hsteam_net_connection.take() {
+                conn.close(reason.into(), debug, false);
+            }
+            self.release_player_state(remote.raw());
+
+            if let Some(raw) = self.server_raw.as_ref() {
+                raw.end_authentication_session(remote);
+            }
+
+            if !data.spectator && data.active && !data.is_disconnected() {
+                let event = data.replay_event(self.frame_id);
+                self.record(|r| r.record_player_left(&event));
+            }
+
+            Some(data)
+        }
+
+        /// 清理玩家离开后留下的解码错误、分片、服务器浏览器和大数据传输状态
+        fn release_player_state(&mut self, steam_id: u64) {
+            self.decode_errors.remove(steam_id);
+            self.chunks.remove(steam_id);
+            self.browser.remove_player(steam_id);
+            self.blobs.remove_peer(steam_id);
+        }
 
         pub fn on_auth_completed(
             &mut self,
@@ -1347,14 +3807,19 @@ pub mod steamp2p {
 
             let mut data = self.rg_pending_client_data.remove(pending_auth_index);
             data.ul_tick_count_last_data = now();
+            data.player_position = self.assign_slot(data.steam_iduser.steam_id().unwrap());
 
             self.send_message(
                 MsgServerPassAuthentication {
-                    player_position: pending_auth_index as u32,
+                    player_position: data.player_position,
                 },
                 data.hsteam_net_connection.as_ref().unwrap(),
             );
+
+            let event = data.replay_event(self.frame_id);
+            self.record(|r| r.record_player_joined(&event));
             self.rg_client_data.push(data);
+            self.rg_client_data.sort_by_key(|f| f.player_position);
 
             if self.rg_client_data.len() < self.max_players.into() {
                 #[cfg(feature = "dev")]
@@ -1366,8 +3831,11 @@ pub mod steamp2p {
             if self.rg_client_data.iter().all(|f| f.active) {
                 let ready = MsgServerAllReadyToGo;
                 self.rg_client_data.iter().for_each(|f| {
-                    self.send_message_ref(&ready, f.hsteam_net_connection.as_ref().unwrap());
+                    if let Some(conn) = f.hsteam_net_connection.as_ref() {
+                        self.send_message_ref(&ready, conn);
+                    }
                 });
+                self.send_to_spectators(&ready);
 
                 return true;
             }
@@ -1375,6 +3843,220 @@ pub mod steamp2p {
             false
         }
 
+        /// 为验证成功的玩家分配槽位,大厅成员数据中指定的槽位优先
+        fn assign_slot(&mut self, remote: SteamId) -> u32 {
+            let preferred = self.slot_lobby_key.as_ref().and_then(|key| {
+                if self.lobby_id == 0 || !crate::client::has_client() {
+                    return None;
+                }
+
+                crate::client::get_client()
+                    .matchmaking()
+                    .lobby_member_data(
+                        LobbyId::from_raw(self.lobby_id),
+                        LobbyId::from_raw(remote.raw()),
+                        key,
+                    )
+                    .and_then(|value| value.parse::<u32>().ok())
+            });
+            let occupied: Vec<u32> = self
+                .rg_client_data
+                .iter()
+                .map(|f| f.player_position)
+                .collect();
+
+            self.slots
+                .assign(remote.raw(), preferred, &occupied, self.max_players.into())
+        }
+
+        /// 观战者验证成功,游戏已经开始时补发开局数据
+        ///
+        /// 返回观战者在 `rg_spectator_data` 中的索引
+        pub fn on_spectator_auth_completed(&mut self, pending_auth_index: usize) -> usize {
+            let mut data = self.rg_pending_client_data.remove(pending_auth_index);
+            data.ul_tick_count_last_data = now();
+            // 观战者离开后编号会空出来,取最小的空闲编号
+            data.player_position = (0..)
+                .find(|position| {
+                    !self
+                        .rg_spectator_data
+                        .iter()
+                        .any(|f| f.player_position == *position)
+                })
+                .unwrap();
+
+            let conn = data.hsteam_net_connection.as_ref().unwrap();
+            self.send_message(
+                MsgServerPassAuthentication {
+                    player_position: data.player_position,
+                },
+                conn,
+            );
+
+            if let Some(start) = self.game_start_sent.as_ref() {
+                self.send_message(MsgServerAllReadyToGo, conn);
+                self.send_message_ref(start, conn);
+            }
+
+            #[cfg(feature = "dev")]
+            dbg!("spectator joined", data.player_position);
+
+            self.rg_spectator_data.push(data);
+            self.rg_spectator_data.len() - 1
+        }
+
+        /// 掉线玩家重新验证成功,恢复原来的槽位并补发错过的帧
+        ///
+        /// 返回玩家在 `rg_client_data` 中的索引
+        pub fn on_reconnect_completed(&mut self, pending_auth_index: usize) -> Option<usize> {
+            let mut pending = self.rg_pending_client_data.remove(pending_auth_index);
+            let remote = pending.steam_iduser.steam_id().unwrap();
+
+            let index = self.rg_client_data.iter().position(|f| {
+                f.is_disconnected() && f.steam_iduser.steam_id().unwrap() == remote
+            })?;
+
+            let from_frame_id = self.rg_client_data[index].disconnected_frame_id + 1;
+
+            if !self.frame_history.contains(from_frame_id, self.frame_id) {
+                // 错过的帧已经不在缓存中,无法恢复
+                pending.hsteam_net_connection.take().unwrap().close(
+                    NetConnectionEndReason::NetConnectionEnd(
+                        networking_types::NetConnectionEnd::AppException,
+                    ),
+                    Some("Missed frames are no longer available"),
+                    false,
+                );
+                self.server_raw
+                    .as_ref()
+                    .unwrap()
+                    .end_authentication_session(remote);
+                return None;
+            }
+
+            let conn = pending.hsteam_net_connection.take().unwrap();
+
+            self.send_message(
+                MsgServerPassAuthentication {
+                    player_position: self.rg_client_data[index].player_position,
+                },
+                &conn,
+            );
+            self.send_message(
+                MsgServerResumeGame {
+                    player_position: self.rg_client_data[index].player_position,
+                    from_frame_id,
+                    to_frame_id: self.frame_id,
+                },
+                &conn,
+            );
+
+            self.resend_frames(from_frame_id, self.frame_id, &conn);
+
+            if let Some(steam_id) = self.paused_by {
+                self.send_message(
+                    MsgServerPaused {
+                        frame_id: self.frame_id,
+                        steam_id,
+                    },
+                    &conn,
+                );
+            }
+
+            let data = self.rg_client_data.get_mut(index).unwrap();
+            data.hsteam_net_connection = Some(conn);
+            data.disconnected_time = None;
+            data.ul_tick_count_last_data = now();
+            // 重连后的客户端可能重新从1开始编号
+            data.input_seq = SeqWindow::default();
+
+            let event = data.replay_event(self.frame_id);
+            self.record(|r| r.record_player_joined(&event));
+
+            #[cfg(feature = "dev")]
+            dbg!("player reconnected", from_frame_id, self.frame_id);
+
+            Some(index)
+        }
+
+        /// 比较玩家上报的校验值,不一致时通知所有玩家
+        pub fn on_client_checksum(
+            &mut self,
+            msg: MsgClientChecksum,
+            remote: SteamId,
+        ) -> Option<MsgServerDesync> {
+            if !self
+                .rg_client_data
+                .iter()
+                .any(|f| f.steam_iduser.steam_id().unwrap() == remote)
+            {
+                return None;
+            }
+
+            // 还没有广播的帧不可能有校验值,避免占用校验窗口
+            if msg.frame_id > self.frame_id {
+                return None;
+            }
+
+            let players = self
+                .rg_client_data
+                .iter()
+                .filter(|f| !f.is_disconnected())
+                .count();
+
+            let desync =
+                self.checksums
+                    .insert(msg.frame_id, remote.raw(), msg.checksum, players)?;
+
+            #[cfg(feature = "dev")]
+            dbg!("desync detected at frame", desync.frame_id);
+
+            self.rg_client_data.iter().for_each(|f| {
+                if let Some(conn) = f.hsteam_net_connection.as_ref() {
+                    self.send_message_ref(&desync, conn);
+                }
+            });
+
+            Some(desync)
+        }
+
+        /// 客户端请求补发 `from..=to` 之间的帧,只发送给请求者
+        pub fn on_client_request_frames(&mut self, msg: MsgClientRequestFrames, remote: SteamId) {
+            let first = self.frame_history.first_frame_id().unwrap_or(0);
+            let frame_id = self.frame_id;
+            let spectator_delay = self.spectator_delay;
+
+            let Some(data) = self
+                .rg_client_data
+                .iter_mut()
+                .chain(self.rg_spectator_data.iter_mut())
+                .find(|f| f.steam_iduser.steam_id().unwrap() == remote)
+            else {
+                return;
+            };
+
+            // 观战者不能提前拿到延迟之内的帧
+            let latest = if data.spectator {
+                frame_id.saturating_sub(spectator_delay)
+            } else {
+                frame_id
+            };
+            // 只补发缓存中存在的帧,并限制每秒补发的数量
+            let Some((from, to)) =
+                data.frame_requests
+                    .allow(msg.from.max(first), msg.to.min(latest), now())
+            else {
+                return;
+            };
+
+            #[cfg(feature = "dev")]
+            dbg!("on_client_request_frames", from, to);
+
+            if let Some(conn) = self.client_connection(remote.raw()) {
+                self.resend_frames(from, to, conn);
+            }
+        }
+
         pub fn send_message<T>(&self, msg: T, conn: &NetConnection<ServerManager>)
         where
             T: INetMessage + serde::Serialize,
@@ -1386,26 +4068,51 @@ pub mod steamp2p {
         pub fn send_message_ref<T>(&self, msg: &T, conn: &NetConnection<ServerManager>)
         where
             T: INetMessage + serde::Serialize,
+        {
+            self.send_message_with(msg, conn, T::SEND_FLAGS, T::LANE);
+        }
+
+        /// 使用指定的发送方式和通道发送
+        pub fn send_message_with<T>(
+            &self,
+            msg: &T,
+            conn: &NetConnection<ServerManager>,
+            send_flags: SendFlags,
+            lane: u16,
+        ) where
+            T: INetMessage + serde::Serialize,
         {
             //TODO u8 pool alloc and free
 
-            let mut bytes = Vec::new();
-            msg.serialize(&mut rmps::Serializer::new(&mut bytes))
-                .unwrap();
+            let bytes = self.codec.encode(msg);
 
-            let mut header: Vec<u8> = T::ID.into();
-            header.append(&mut bytes);
+            // 客户端完成认证前还不知道对方支持的功能
+            let features = conn.connection_user_data().unwrap_or(0) as u32;
+            let packets = encode_packet(T::ID, &bytes, features, self.compression_threshold);
+            // 丢失一个分片整条消息都无法还原
+            let send_flags = if packets.len() > 1 {
+                send_flags | SendFlags::RELIABLE
+            } else {
+                send_flags
+            };
 
-            let mut message = self.utils.as_ref().unwrap().allocate_message(0);
-            message.set_connection(conn);
-            message.set_send_flags(SendFlags::RELIABLE_NO_NAGLE);
-            message.set_data(header).unwrap();
+            let messages = packets
+                .into_iter()
+                .map(|packet| {
+                    let mut message = self.utils.as_ref().unwrap().allocate_message(0);
+                    message.set_connection(conn);
+                    message.set_send_flags(send_flags);
+                    message.set_lane(lane);
+                    message.set_data(packet).unwrap();
+                    message
+                })
+                .collect::<Vec<_>>();
 
             let results = self
-                .listen_socket
+                .server_sockets
                 .as_ref()
                 .unwrap()
-                .send_messages(vec![message]);
+                .send_messages(messages);
 
             results.iter().for_each(|result| {
                 let _ = result
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+/// 玩家槽位分配
+///
+/// 同一个玩家重新加入时优先使用原来的槽位,不受其他玩家进出的影响
+#[derive(Default)]
+pub struct SlotTable {
+    /// steam id -> 槽位,包括已经离开的玩家和预先指定的槽位
+    assigned: HashMap<u64, u32>,
+}
+
+impl SlotTable {
+    /// 预先指定玩家的槽位,例如来自大厅成员数据
+    pub fn reserve(&mut self, steam_id: u64, slot: u32) {
+        self.assigned.retain(|_, s| *s != slot);
+        self.assigned.insert(steam_id, slot);
+    }
+
+    pub fn get(&self, steam_id: u64) -> Option<u32> {
+        self.assigned.get(&steam_id).copied()
+    }
+
+    /// 为玩家分配槽位
+    ///
+    /// 依次尝试没有被其他玩家保留的 `preferred`、之前分配过的槽位、没有被其他玩家保留的最小槽位,
+    /// 都不可用时使用最小的空闲槽位。`occupied` 是在线(包括等待重连)玩家占用的槽位
+    pub fn assign(
+        &mut self,
+        steam_id: u64,
+        preferred: Option<u32>,
+        occupied: &[u32],
+        max_slots: u32,
+    ) -> u32 {
+        let free = |slot: &u32| *slot < max_slots && !occupied.contains(slot);
+        let reserved = |slot: &u32| {
+            self.assigned
+                .iter()
+                .any(|(id, s)| s == slot && *id != steam_id)
+        };
+
+        let slot = preferred
+            .filter(|slot| free(slot) && !reserved(slot))
+            .or_else(|| self.get(steam_id).filter(free))
+            .or_else(|| (0..max_slots).find(|slot| free(slot) && !reserved(slot)))
+            .unwrap_or_else(|| (0..).find(|slot| !occupied.contains(slot)).unwrap());
+
+        self.reserve(steam_id, slot);
+        slot
+    }
+
+    pub fn clear(&mut self) {
+        self.assigned.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SlotTable;
+
+    #[test]
+    fn keeps_slots_stable() {
+        let mut slots = SlotTable::default();
+
+        assert_eq!(slots.assign(10, None, &[], 4), 0);
+        assert_eq!(slots.assign(11, None, &[0], 4), 1);
+        assert_eq!(slots.assign(12, None, &[0, 1], 4), 2);
+
+        // 11 离开后,新玩家不会占用它的槽位
+        assert_eq!(slots.assign(13, None, &[0, 2], 4), 3);
+        // 11 重新加入时回到原来的槽位
+        assert_eq!(slots.assign(11, None, &[0, 2, 3], 4), 1);
+    }
+
+    #[test]
+    fn prefers_reserved_slots() {
+        let mut slots = SlotTable::default();
+        slots.reserve(20, 2);
+
+        assert_eq!(slots.assign(21, None, &[], 3), 0);
+        assert_eq!(slots.assign(20, None, &[0], 3), 2);
+        // 指定的槽位已经被占用
+        assert_eq!(slots.assign(22, Some(2), &[0, 2], 3), 1);
+        // 指定的槽位被离开的玩家保留
+        assert_eq!(slots.assign(23, Some(1), &[0, 2], 4), 3);
+
+        // 所有槽位都被保留时使用空闲的槽位
+        let mut slots = SlotTable::default();
+        slots.reserve(30, 0);
+        assert_eq!(slots.assign(31, None, &[], 1), 0);
+        assert_eq!(slots.get(30), None);
+    }
+}
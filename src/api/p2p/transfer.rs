@@ -0,0 +1,603 @@
+use crate::api::p2p::message::{MsgBlobAck, MsgBlobChunk, MsgBlobComplete, MsgBlobStart};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+
+/// 每个分片的字节数
+pub const BLOB_CHUNK_SIZE: usize = 64 * 1024;
+/// 默认每秒最多发送的字节数
+pub const DEFAULT_BLOB_BANDWIDTH: u32 = 512 * 1024;
+/// 最多同时发出但还没有确认的分片数
+const BLOB_WINDOW: usize = 8;
+/// 超过多久(毫秒)没有收到确认就重新发送开始消息,从断点继续
+pub const BLOB_ACK_TIMEOUT: i64 = 3000;
+/// 单个数据的上限
+pub const MAX_BLOB_SIZE: usize = 256 * 1024 * 1024;
+/// 每个连接同时接收的数据上限
+pub const MAX_INCOMING_PER_PEER: usize = 2;
+/// 保留的已结束接收的记录数
+const MAX_FINISHED: usize = 64;
+
+/// 需要发送给对方的消息
+#[derive(Debug, PartialEq)]
+pub enum BlobMessage {
+    Start(MsgBlobStart),
+    Chunk(MsgBlobChunk),
+    Ack(MsgBlobAck),
+    Complete(MsgBlobComplete),
+}
+
+impl BlobMessage {
+    pub fn target(&self) -> u64 {
+        match self {
+            BlobMessage::Start(msg) => msg.target,
+            BlobMessage::Chunk(msg) => msg.target,
+            BlobMessage::Ack(msg) => msg.target,
+            BlobMessage::Complete(msg) => msg.target,
+        }
+    }
+}
+
+/// 需要通知游戏的事件
+#[derive(Debug, PartialEq)]
+pub enum BlobEvent {
+    Progress {
+        steam_id: u64,
+        transfer_id: u32,
+        transferred: u32,
+        total: u32,
+        /// 由我们发送
+        outgoing: bool,
+    },
+    Received {
+        steam_id: u64,
+        transfer_id: u32,
+        meta: String,
+        data: Vec<u8>,
+    },
+    Failed {
+        steam_id: u64,
+        transfer_id: u32,
+        error: String,
+        outgoing: bool,
+    },
+}
+
+struct OutgoingBlob {
+    steam_id: u64,
+    transfer_id: u32,
+    data: Vec<u8>,
+    sha256: Vec<u8>,
+    meta: String,
+    /// 已经发出的字节数
+    sent: usize,
+    /// 对方确认收到的字节数
+    acked: usize,
+    /// 对方已经回复了开始消息
+    started: bool,
+    /// 最后一次发送开始消息或者收到确认的时间,为0时还没有开始
+    last_ack: i64,
+}
+
+struct IncomingBlob {
+    size: usize,
+    sha256: Vec<u8>,
+    meta: String,
+    data: Vec<u8>,
+}
+
+/// 在现有连接上分片传输大数据,按带宽限制发送,断线重连后从断点继续
+pub struct BlobTransfers {
+    local_id: u64,
+    outgoing: Vec<OutgoingBlob>,
+    incoming: HashMap<(u64, u32), IncomingBlob>,
+    /// 最近结束的接收的校验值和结果,对方没有收到结束消息时重新回复
+    finished: VecDeque<((u64, u32), Vec<u8>, String)>,
+    /// 每秒最多发送的字节数,为0时不限制
+    bandwidth: u32,
+    budget: f64,
+    last_poll: i64,
+    next_id: u32,
+    messages: Vec<BlobMessage>,
+    events: Vec<BlobEvent>,
+}
+
+impl Default for BlobTransfers {
+    fn default() -> Self {
+        BlobTransfers {
+            local_id: 0,
+            outgoing: Vec::new(),
+            incoming: HashMap::new(),
+            finished: VecDeque::new(),
+            bandwidth: DEFAULT_BLOB_BANDWIDTH,
+            budget: 0.0,
+            last_poll: 0,
+            next_id: 1,
+            messages: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl BlobTransfers {
+    /// 设置发出的消息中的发送方
+    pub fn set_local_id(&mut self, local_id: u64) {
+        self.local_id = local_id;
+    }
+
+    pub fn set_bandwidth(&mut self, bytes_per_second: u32) {
+        self.bandwidth = bytes_per_second;
+    }
+
+    /// 开始向 `steam_id` 发送数据,返回传输ID
+    pub fn send(&mut self, steam_id: u64, data: Vec<u8>, meta: String) -> u32 {
+        let transfer_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+
+        self.outgoing.push(OutgoingBlob {
+            steam_id,
+            transfer_id,
+            sha256: Sha256::digest(&data).to_vec(),
+            data,
+            meta,
+            sent: 0,
+            acked: 0,
+            started: false,
+            last_ack: 0,
+        });
+
+        transfer_id
+    }
+
+    /// 按带宽限制生成要发送的开始消息和分片
+    pub fn poll(&mut self, now: i64) {
+        if self.bandwidth > 0 {
+            let elapsed = if self.last_poll == 0 {
+                0
+            } else {
+                now - self.last_poll
+            };
+            // 最多积累一秒的额度
+            self.budget = (self.budget + self.bandwidth as f64 * elapsed as f64 / 1000.0)
+                .min(self.bandwidth.max(BLOB_CHUNK_SIZE as u32) as f64);
+        }
+        self.last_poll = now;
+
+        for blob in self.outgoing.iter_mut() {
+            if blob.last_ack == 0 || now - blob.last_ack >= BLOB_ACK_TIMEOUT {
+                blob.last_ack = now;
+                blob.started = false;
+                blob.sent = blob.acked;
+                self.messages.push(BlobMessage::Start(MsgBlobStart {
+                    transfer_id: blob.transfer_id,
+                    target: blob.steam_id,
+                    source: self.local_id,
+                    size: blob.data.len() as u32,
+                    sha256: blob.sha256.clone(),
+                    meta: blob.meta.clone(),
+                }));
+                continue;
+            }
+
+            if !blob.started {
+                continue;
+            }
+
+            while blob.sent < blob.data.len()
+                && blob.sent - blob.acked < BLOB_WINDOW * BLOB_CHUNK_SIZE
+            {
+                let len = BLOB_CHUNK_SIZE.min(blob.data.len() - blob.sent);
+                if self.bandwidth > 0 {
+                    if self.budget < len as f64 {
+                        return;
+                    }
+                    self.budget -= len as f64;
+                }
+
+                self.messages.push(BlobMessage::Chunk(MsgBlobChunk {
+                    transfer_id: blob.transfer_id,
+                    target: blob.steam_id,
+                    source: self.local_id,
+                    offset: blob.sent as u32,
+                    data: blob.data[blob.sent..blob.sent + len].to_vec(),
+                }));
+                blob.sent += len;
+            }
+        }
+    }
+
+    pub fn on_start(&mut self, msg: MsgBlobStart) {
+        let key = (msg.source, msg.transfer_id);
+
+        if let Some(index) = self.finished.iter().position(|(k, _, _)| *k == key) {
+            if self.finished[index].1 == msg.sha256 {
+                let error = self.finished[index].2.clone();
+                self.complete(key, error);
+                return;
+            }
+            self.finished.remove(index);
+        }
+
+        if msg.size as usize > MAX_BLOB_SIZE {
+            self.complete(key, format!("blob too large ({} bytes)", msg.size));
+            return;
+        }
+
+        if !self.incoming.contains_key(&key)
+            && self
+                .incoming
+                .keys()
+                .filter(|(id, _)| *id == msg.source)
+                .count()
+                >= MAX_INCOMING_PER_PEER
+        {
+            self.complete(key, "too many transfers".to_string());
+            return;
+        }
+
+        let incoming = self.incoming.entry(key).or_insert_with(|| IncomingBlob {
+            size: msg.size as usize,
+            sha256: msg.sha256.clone(),
+            meta: msg.meta.clone(),
+            data: Vec::new(),
+        });
+
+        // 同一个ID开始了不同的数据,从头接收
+        if incoming.size != msg.size as usize || incoming.sha256 != msg.sha256 {
+            *incoming = IncomingBlob {
+                size: msg.size as usize,
+                sha256: msg.sha256,
+                meta: msg.meta,
+                data: Vec::new(),
+            };
+        }
+
+        let received = incoming.data.len() as u32;
+        if incoming.size == 0 {
+            self.finish(key);
+        } else {
+            self.ack(key, received);
+        }
+    }
+
+    pub fn on_chunk(&mut self, msg: MsgBlobChunk) {
+        let key = (msg.source, msg.transfer_id);
+        let Some(incoming) = self.incoming.get_mut(&key) else {
+            return;
+        };
+
+        // 重新开始之前发出的分片
+        if msg.offset as usize != incoming.data.len()
+            || incoming.data.len() + msg.data.len() > incoming.size
+        {
+            return;
+        }
+
+        incoming.data.extend_from_slice(&msg.data);
+        let received = incoming.data.len() as u32;
+        let total = incoming.size as u32;
+
+        self.events.push(BlobEvent::Progress {
+            steam_id: msg.source,
+            transfer_id: msg.transfer_id,
+            transferred: received,
+            total,
+            outgoing: false,
+        });
+
+        if received == total {
+            self.finish(key);
+        } else {
+            self.ack(key, received);
+        }
+    }
+
+    pub fn on_ack(&mut self, msg: MsgBlobAck, now: i64) {
+        let Some(blob) = self
+            .outgoing
+            .iter_mut()
+            .find(|f| f.steam_id == msg.source && f.transfer_id == msg.transfer_id)
+        else {
+            return;
+        };
+
+        let received = (msg.received as usize).min(blob.data.len());
+        blob.last_ack = now;
+
+        // 回复开始消息,从对方已经收到的位置继续
+        if !blob.started {
+            blob.started = true;
+            blob.sent = received;
+            blob.acked = received;
+        } else if received > blob.acked {
+            blob.acked = received;
+        } else {
+            return;
+        }
+
+        self.events.push(BlobEvent::Progress {
+            steam_id: blob.steam_id,
+            transfer_id: blob.transfer_id,
+            transferred: received as u32,
+            total: blob.data.len() as u32,
+            outgoing: true,
+        });
+    }
+
+    pub fn on_complete(&mut self, msg: MsgBlobComplete) {
+        let Some(index) = self
+            .outgoing
+            .iter()
+            .position(|f| f.steam_id == msg.source && f.transfer_id == msg.transfer_id)
+        else {
+            return;
+        };
+
+        let blob = self.outgoing.remove(index);
+        self.events.push(if msg.error.is_empty() {
+            BlobEvent::Progress {
+                steam_id: blob.steam_id,
+                transfer_id: blob.transfer_id,
+                transferred: blob.data.len() as u32,
+                total: blob.data.len() as u32,
+                outgoing: true,
+            }
+        } else {
+            BlobEvent::Failed {
+                steam_id: blob.steam_id,
+                transfer_id: blob.transfer_id,
+                error: msg.error,
+                outgoing: true,
+            }
+        });
+    }
+
+    /// 放弃和 `steam_id` 之间的所有传输
+    pub fn remove_peer(&mut self, steam_id: u64) {
+        self.outgoing.retain(|f| f.steam_id != steam_id);
+        self.incoming.retain(|(id, _), _| *id != steam_id);
+        self.finished.retain(|((id, _), _, _)| *id != steam_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.outgoing.clear();
+        self.incoming.clear();
+        self.finished.clear();
+        self.messages.clear();
+        self.events.clear();
+    }
+
+    pub fn take_messages(&mut self) -> Vec<BlobMessage> {
+        std::mem::take(&mut self.messages)
+    }
+
+    pub fn take_events(&mut self) -> Vec<BlobEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn ack(&mut self, (steam_id, transfer_id): (u64, u32), received: u32) {
+        self.messages.push(BlobMessage::Ack(MsgBlobAck {
+            transfer_id,
+            target: steam_id,
+            source: self.local_id,
+            received,
+        }));
+    }
+
+    fn complete(&mut self, (steam_id, transfer_id): (u64, u32), error: String) {
+        self.messages.push(BlobMessage::Complete(MsgBlobComplete {
+            transfer_id,
+            target: steam_id,
+            source: self.local_id,
+            error,
+        }));
+    }
+
+    /// 收齐后校验数据
+    fn finish(&mut self, key: (u64, u32)) {
+        let Some(incoming) = self.incoming.remove(&key) else {
+            return;
+        };
+
+        let error = if Sha256::digest(&incoming.data).as_slice() == incoming.sha256.as_slice() {
+            self.events.push(BlobEvent::Received {
+                steam_id: key.0,
+                transfer_id: key.1,
+                meta: incoming.meta,
+                data: incoming.data,
+            });
+            String::new()
+        } else {
+            let error = "sha256 mismatch".to_string();
+            self.events.push(BlobEvent::Failed {
+                steam_id: key.0,
+                transfer_id: key.1,
+                error: error.clone(),
+                outgoing: false,
+            });
+            error
+        };
+
+        if self.finished.len() >= MAX_FINISHED {
+            self.finished.pop_front();
+        }
+        self.finished
+            .push_back((key, incoming.sha256, error.clone()));
+        self.complete(key, error);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        BlobEvent, BlobMessage, BlobTransfers, BLOB_ACK_TIMEOUT, BLOB_CHUNK_SIZE, MAX_FINISHED,
+        MAX_INCOMING_PER_PEER,
+    };
+    use crate::api::p2p::message::MsgBlobStart;
+
+    /// 把一方发出的消息交给另一方,`drop` 返回true的消息丢失
+    fn deliver<F>(from: &mut BlobTransfers, to: &mut BlobTransfers, now: i64, mut drop: F) -> usize
+    where
+        F: FnMut(&BlobMessage) -> bool,
+    {
+        let messages = from.take_messages();
+        let count = messages.len();
+        for msg in messages {
+            if drop(&msg) {
+                continue;
+            }
+            match msg {
+                BlobMessage::Start(msg) => to.on_start(msg),
+                BlobMessage::Chunk(msg) => to.on_chunk(msg),
+                BlobMessage::Ack(msg) => to.on_ack(msg, now),
+                BlobMessage::Complete(msg) => to.on_complete(msg),
+            }
+        }
+        count
+    }
+
+    fn peers() -> (BlobTransfers, BlobTransfers) {
+        let mut sender = BlobTransfers::default();
+        sender.set_local_id(1);
+        let mut receiver = BlobTransfers::default();
+        receiver.set_local_id(2);
+        (sender, receiver)
+    }
+
+    #[test]
+    fn transfers_and_verifies_blob() {
+        let (mut sender, mut receiver) = peers();
+        sender.set_bandwidth(0);
+
+        let data: Vec<u8> = (0..BLOB_CHUNK_SIZE * 3 + 100).map(|i| i as u8).collect();
+        let transfer_id = sender.send(2, data.clone(), "map".to_string());
+
+        let mut now = 1;
+        sender.poll(now);
+        while deliver(&mut sender, &mut receiver, now, |_| false)
+            + deliver(&mut receiver, &mut sender, now, |_| false)
+            > 0
+        {
+            now += 10;
+            sender.poll(now);
+        }
+
+        let received = receiver.take_events().pop();
+        assert_eq!(
+            received,
+            Some(BlobEvent::Received {
+                steam_id: 1,
+                transfer_id,
+                meta: "map".to_string(),
+                data,
+            })
+        );
+        assert_eq!(
+            sender.take_events().pop(),
+            Some(BlobEvent::Progress {
+                steam_id: 2,
+                transfer_id,
+                transferred: BLOB_CHUNK_SIZE as u32 * 3 + 100,
+                total: BLOB_CHUNK_SIZE as u32 * 3 + 100,
+                outgoing: true,
+            })
+        );
+        assert!(sender.outgoing.is_empty());
+    }
+
+    #[test]
+    fn resumes_after_loss_within_bandwidth() {
+        let (mut sender, mut receiver) = peers();
+        sender.set_bandwidth(BLOB_CHUNK_SIZE as u32);
+
+        let data = vec![7u8; BLOB_CHUNK_SIZE * 4];
+        sender.send(2, data, String::new());
+
+        sender.poll(1);
+        deliver(&mut sender, &mut receiver, 1, |_| false);
+        deliver(&mut receiver, &mut sender, 1, |_| false);
+
+        // 每秒只能发送一个分片
+        sender.poll(1001);
+        sender.poll(1001);
+        let mut chunks = 0;
+        deliver(&mut sender, &mut receiver, 1001, |msg| {
+            chunks += 1;
+            !matches!(msg, BlobMessage::Chunk(_))
+        });
+        assert_eq!(chunks, 1);
+        deliver(&mut receiver, &mut sender, 1001, |_| false);
+
+        // 第二个分片丢失,超时后从断点继续
+        sender.poll(2001);
+        deliver(&mut sender, &mut receiver, 2001, |_| true);
+        sender.poll(2001 + BLOB_ACK_TIMEOUT);
+        deliver(&mut sender, &mut receiver, 5001, |_| false);
+        let ack = receiver.take_messages();
+        assert!(
+            matches!(&ack[..], [BlobMessage::Ack(ack)] if ack.received == BLOB_CHUNK_SIZE as u32)
+        );
+
+        // 篡改的数据校验失败
+        for msg in ack {
+            if let BlobMessage::Ack(msg) = msg {
+                sender.on_ack(msg, 5001);
+            }
+        }
+        for f in sender.outgoing.iter_mut() {
+            f.data[BLOB_CHUNK_SIZE * 3] = 0;
+        }
+        let mut now = 5001;
+        for _ in 0..3 {
+            now += 1000;
+            sender.poll(now);
+            deliver(&mut sender, &mut receiver, now, |_| false);
+            deliver(&mut receiver, &mut sender, now, |_| false);
+        }
+
+        assert!(matches!(
+            receiver.take_events().pop(),
+            Some(BlobEvent::Failed {
+                outgoing: false,
+                ..
+            })
+        ));
+        assert!(matches!(
+            sender.take_events().pop(),
+            Some(BlobEvent::Failed { outgoing: true, .. })
+        ));
+    }
+
+    #[test]
+    fn limits_incoming_per_peer() {
+        let mut receiver = BlobTransfers::default();
+        receiver.set_local_id(2);
+
+        let start = |source: u64, transfer_id: u32| MsgBlobStart {
+            transfer_id,
+            target: 2,
+            source,
+            size: 10,
+            sha256: vec![0; 32],
+            meta: String::new(),
+        };
+
+        for transfer_id in 0..=MAX_INCOMING_PER_PEER as u32 {
+            receiver.on_start(start(1, transfer_id));
+        }
+        receiver.on_start(start(3, 0));
+        assert_eq!(receiver.incoming.len(), MAX_INCOMING_PER_PEER + 1);
+        assert!(matches!(
+            receiver.take_messages().get(MAX_INCOMING_PER_PEER),
+            Some(BlobMessage::Complete(msg)) if msg.error == "too many transfers"
+        ));
+
+        // 空数据直接结束,只保留最近的记录
+        for transfer_id in 0..MAX_FINISHED as u32 * 2 {
+            receiver.on_start(MsgBlobStart {
+                size: 0,
+                ..start(4, transfer_id)
+            });
+        }
+        assert_eq!(receiver.finished.len(), MAX_FINISHED);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+/// 某一类操作的限制,为0时不限制
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct InputRule {
+    /// 单条操作数据的最大字节数
+    pub max_size: u32,
+    /// 每秒最多提交的次数
+    pub max_per_second: u32,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum InputViolation {
+    /// 严格模式下没有注册规则的操作类型
+    UnknownType,
+    TooLarge,
+    RateLimited,
+    /// 被JS校验函数拒绝
+    Rejected,
+}
+
+/// 按操作类型注册的校验规则
+#[derive(Default)]
+pub struct InputRules {
+    rules: HashMap<u32, InputRule>,
+    /// 为true时拒绝没有注册规则的操作类型
+    strict: bool,
+}
+
+impl InputRules {
+    pub fn set_rule(&mut self, types: u32, rule: InputRule) {
+        self.rules.insert(types, rule);
+    }
+
+    pub fn remove_rule(&mut self, types: u32) {
+        self.rules.remove(&types);
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// 检查一条操作,通过时计入该玩家的提交次数
+    pub fn check(
+        &self,
+        types: u32,
+        size: usize,
+        rate: &mut InputRate,
+        time: i64,
+    ) -> Result<(), InputViolation> {
+        let rule = match self.rules.get(&types) {
+            Some(rule) => rule,
+            None if self.strict => return Err(InputViolation::UnknownType),
+            None => return Ok(()),
+        };
+
+        if rule.max_size > 0 && size > rule.max_size as usize {
+            return Err(InputViolation::TooLarge);
+        }
+
+        if rule.max_per_second > 0 && rate.hit(types, time) > rule.max_per_second {
+            return Err(InputViolation::RateLimited);
+        }
+
+        Ok(())
+    }
+}
+
+/// 玩家每种操作在当前一秒内的提交次数
+#[derive(Default)]
+pub struct InputRate {
+    window_start: i64,
+    counts: HashMap<u32, u32>,
+}
+
+impl InputRate {
+    /// 记录一次提交,返回当前一秒内的次数
+    pub fn hit(&mut self, types: u32, time: i64) -> u32 {
+        if time - self.window_start >= 1000 {
+            self.window_start = time;
+            self.counts.clear();
+        }
+
+        let count = self.counts.entry(types).or_insert(0);
+        *count += 1;
+        *count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InputRate, InputRule, InputRules, InputViolation};
+
+    #[test]
+    fn checks_size_and_rate_per_type() {
+        let mut rules = InputRules::default();
+        rules.set_rule(
+            1,
+            InputRule {
+                max_size: 4,
+                max_per_second: 2,
+            },
+        );
+
+        let mut rate = InputRate::default();
+        assert_eq!(
+            rules.check(1, 5, &mut rate, 0),
+            Err(InputViolation::TooLarge)
+        );
+        assert_eq!(rules.check(1, 4, &mut rate, 0), Ok(()));
+        assert_eq!(rules.check(1, 4, &mut rate, 500), Ok(()));
+        assert_eq!(
+            rules.check(1, 4, &mut rate, 999),
+            Err(InputViolation::RateLimited)
+        );
+        assert_eq!(rules.check(1, 4, &mut rate, 1000), Ok(()));
+
+        assert_eq!(rules.check(2, 1024, &mut rate, 1000), Ok(()));
+        rules.set_strict(true);
+        assert_eq!(
+            rules.check(2, 1, &mut rate, 1000),
+            Err(InputViolation::UnknownType)
+        );
+    }
+}
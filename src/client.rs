@@ -5,7 +5,6 @@ use steamworks::SingleClient;
 
 lazy_static! {
     static ref STEAM_CLIENT: Mutex<Option<Client>> = Mutex::new(None);
-    static ref START_TIME: SystemTime = SystemTime::now();
 }
 
 static mut STEAM_SINGLE: Option<SingleClient> = None;
@@ -34,7 +33,7 @@ pub fn get_single() -> &'static SingleClient {
 }
 
 pub fn now() -> i64 {
-    let since_the_epoch = START_TIME
+    let since_the_epoch = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards");
     let ms = since_the_epoch.as_secs() as i64 * 1000i64
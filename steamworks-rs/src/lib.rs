@@ -27,6 +27,7 @@ pub use crate::error::*;
 pub use crate::friends::*;
 pub use crate::input::*;
 pub use crate::matchmaking::*;
+pub use crate::matchmaking_servers::*;
 pub use crate::networking::*;
 pub use crate::remote_play::*;
 pub use crate::remote_storage::*;
@@ -42,6 +43,7 @@ mod error;
 mod friends;
 mod input;
 mod matchmaking;
+mod matchmaking_servers;
 mod networking;
 pub mod networking_messages;
 pub mod networking_sockets;
@@ -298,6 +300,18 @@ impl<Manager> Client<Manager> {
         }
     }
 
+    /// Returns an accessor to the steam game server browser interface
+    pub fn matchmaking_servers(&self) -> MatchmakingServers<Manager> {
+        unsafe {
+            let mms = sys::SteamAPI_SteamMatchmakingServers_v002();
+            debug_assert!(!mms.is_null());
+            MatchmakingServers {
+                mms,
+                inner: self.inner.clone(),
+            }
+        }
+    }
+
     /// Returns an accessor to the steam networking interface
     pub fn networking(&self) -> Networking<Manager> {
         unsafe {
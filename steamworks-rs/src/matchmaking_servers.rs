@@ -0,0 +1,563 @@
+use super::*;
+use std::net::Ipv4Addr;
+use std::os::raw::{c_char, c_int};
+
+/// Access to the steam game server browser interface
+pub struct MatchmakingServers<Manager> {
+    pub(crate) mms: *mut sys::ISteamMatchmakingServers,
+    pub(crate) inner: Arc<Inner<Manager>>,
+}
+
+/// Which list of game servers to request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ServerListKind {
+    Internet,
+    Lan,
+    Friends,
+    Favorites,
+    History,
+}
+
+/// How a server list request finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ServerListResponse {
+    ServerResponded,
+    ServerFailedToRespond,
+    NoServersListedOnMasterServer,
+}
+
+/// A game server as reported by the server browser
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameServerItem {
+    pub addr: Ipv4Addr,
+    pub connection_port: u16,
+    pub query_port: u16,
+    /// Current ping time in milliseconds
+    pub ping: i32,
+    pub had_successful_response: bool,
+    pub do_not_refresh: bool,
+    pub game_dir: String,
+    pub map: String,
+    pub game_description: String,
+    pub app_id: AppId,
+    /// Number of players currently on the server, including bots
+    pub players: i32,
+    pub max_players: i32,
+    pub bot_players: i32,
+    pub password: bool,
+    pub secure: bool,
+    /// Unix time when this server was last played on (favorite and history lists only)
+    pub last_played: u32,
+    pub server_version: i32,
+    pub server_name: String,
+    pub game_tags: String,
+    /// Invalid if the server is not logged on to Steam
+    pub steam_id: SteamId,
+}
+
+impl GameServerItem {
+    unsafe fn from_raw(raw: &sys::gameserveritem_t) -> GameServerItem {
+        GameServerItem {
+            addr: Ipv4Addr::from(raw.m_NetAdr.m_unIP),
+            connection_port: raw.m_NetAdr.m_usConnectionPort,
+            query_port: raw.m_NetAdr.m_usQueryPort,
+            ping: raw.m_nPing,
+            had_successful_response: raw.m_bHadSuccessfulResponse,
+            do_not_refresh: raw.m_bDoNotRefresh,
+            game_dir: string_from_chars(&raw.m_szGameDir),
+            map: string_from_chars(&raw.m_szMap),
+            game_description: string_from_chars(&raw.m_szGameDescription),
+            app_id: AppId(raw.m_nAppID),
+            players: raw.m_nPlayers,
+            max_players: raw.m_nMaxPlayers,
+            bot_players: raw.m_nBotPlayers,
+            password: raw.m_bPassword,
+            secure: raw.m_bSecure,
+            last_played: raw.m_ulTimeLastPlayed,
+            server_version: raw.m_nServerVersion,
+            server_name: string_from_chars(&raw.m_szServerName),
+            game_tags: string_from_chars(&raw.m_szGameTags),
+            steam_id: SteamId(raw.m_steamID.m_steamid.m_unAll64Bits),
+        }
+    }
+}
+
+/// A player on a game server, from `MatchmakingServers::player_details`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ServerPlayer {
+    pub name: String,
+    pub score: i32,
+    /// Seconds the player has been connected
+    pub time_played: f32,
+}
+
+fn string_from_chars(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+unsafe fn string_from_ptr(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
+
+fn filter_pair(key: &str, value: &str) -> sys::MatchMakingKeyValuePair_t {
+    let mut pair = sys::MatchMakingKeyValuePair_t {
+        m_szKey: [0; 256],
+        m_szValue: [0; 256],
+    };
+    // Keep the trailing nul, longer strings are cut off
+    for (dst, src) in pair.m_szKey.iter_mut().zip(key.bytes().take(255)) {
+        *dst = src as c_char;
+    }
+    for (dst, src) in pair.m_szValue.iter_mut().zip(value.bytes().take(255)) {
+        *dst = src as c_char;
+    }
+    pair
+}
+
+// The response interfaces are C++ classes with only pure virtual methods, so
+// a struct starting with a pointer to a table of function pointers in
+// declaration order has the same layout. On the 64 bit targets we support the
+// `this` call convention is the C one with `this` as the first argument.
+// 32 bit MSVC passes `this` in ecx with `thiscall` instead, which these
+// tables don't implement.
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
+compile_error!("server browser responders assume the 64 bit C++ calling convention");
+
+#[repr(C)]
+struct ServerListVTable {
+    server_responded:
+        unsafe extern "C" fn(*mut ServerListResponder, sys::HServerListRequest, c_int),
+    server_failed_to_respond:
+        unsafe extern "C" fn(*mut ServerListResponder, sys::HServerListRequest, c_int),
+    refresh_complete: unsafe extern "C" fn(
+        *mut ServerListResponder,
+        sys::HServerListRequest,
+        sys::EMatchMakingServerResponse,
+    ),
+}
+
+#[repr(C)]
+struct ServerListResponder {
+    vtable: *const ServerListVTable,
+    mms: *mut sys::ISteamMatchmakingServers,
+    on_server: Box<dyn FnMut(GameServerItem)>,
+    on_complete: Option<Box<dyn FnOnce(ServerListResponse)>>,
+}
+
+static SERVER_LIST_VTABLE: ServerListVTable = ServerListVTable {
+    server_responded: server_list_responded,
+    server_failed_to_respond: server_list_failed_to_respond,
+    refresh_complete: server_list_refresh_complete,
+};
+
+unsafe extern "C" fn server_list_responded(
+    this: *mut ServerListResponder,
+    request: sys::HServerListRequest,
+    server: c_int,
+) {
+    let this = &mut *this;
+    let item = sys::SteamAPI_ISteamMatchmakingServers_GetServerDetails(this.mms, request, server);
+    if !item.is_null() {
+        (this.on_server)(GameServerItem::from_raw(&*item));
+    }
+}
+
+unsafe extern "C" fn server_list_failed_to_respond(
+    _this: *mut ServerListResponder,
+    _request: sys::HServerListRequest,
+    _server: c_int,
+) {
+}
+
+unsafe extern "C" fn server_list_refresh_complete(
+    this: *mut ServerListResponder,
+    _request: sys::HServerListRequest,
+    response: sys::EMatchMakingServerResponse,
+) {
+    let this = &mut *this;
+    if let Some(on_complete) = this.on_complete.take() {
+        on_complete(match response {
+            sys::EMatchMakingServerResponse::eServerResponded => {
+                ServerListResponse::ServerResponded
+            }
+            sys::EMatchMakingServerResponse::eServerFailedToRespond => {
+                ServerListResponse::ServerFailedToRespond
+            }
+            _ => ServerListResponse::NoServersListedOnMasterServer,
+        });
+    }
+}
+
+/// A running server list request, dropping it cancels and releases the request
+pub struct ServerListRequest<Manager> {
+    handle: sys::HServerListRequest,
+    responder: *mut ServerListResponder,
+    mms: *mut sys::ISteamMatchmakingServers,
+    _filters: Vec<sys::MatchMakingKeyValuePair_t>,
+    _inner: Arc<Inner<Manager>>,
+}
+
+impl<Manager> ServerListRequest<Manager> {
+    /// Whether the list is still being refreshed
+    pub fn is_refreshing(&self) -> bool {
+        unsafe { sys::SteamAPI_ISteamMatchmakingServers_IsRefreshing(self.mms, self.handle) }
+    }
+
+    /// Number of servers in the list so far
+    pub fn server_count(&self) -> i32 {
+        unsafe { sys::SteamAPI_ISteamMatchmakingServers_GetServerCount(self.mms, self.handle) }
+    }
+
+    /// Stops the query, no more servers will be reported
+    pub fn cancel(&self) {
+        unsafe { sys::SteamAPI_ISteamMatchmakingServers_CancelQuery(self.mms, self.handle) }
+    }
+}
+
+impl<Manager> Drop for ServerListRequest<Manager> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::SteamAPI_ISteamMatchmakingServers_ReleaseRequest(self.mms, self.handle);
+            drop(Box::from_raw(self.responder));
+        }
+    }
+}
+
+/// The responders of single server queries, owned by their `ServerQuery`
+trait QueryResponder {
+    /// Whether steam already made the last callback for this query
+    fn is_finished(&self) -> bool;
+}
+
+/// A running query of a single server, dropping it cancels the query if it
+/// has not finished yet and releases the callback
+pub struct ServerQuery<Manager> {
+    handle: sys::HServerQuery,
+    responder: *mut dyn QueryResponder,
+    mms: *mut sys::ISteamMatchmakingServers,
+    _inner: Arc<Inner<Manager>>,
+}
+
+impl<Manager> ServerQuery<Manager> {
+    /// Whether the callback has been called
+    pub fn is_finished(&self) -> bool {
+        unsafe { (*self.responder).is_finished() }
+    }
+}
+
+impl<Manager> Drop for ServerQuery<Manager> {
+    fn drop(&mut self) {
+        unsafe {
+            // Steam does not use the responder after its last callback or
+            // after the query is cancelled
+            if !(*self.responder).is_finished() {
+                sys::SteamAPI_ISteamMatchmakingServers_CancelServerQuery(self.mms, self.handle);
+            }
+            drop(Box::from_raw(self.responder));
+        }
+    }
+}
+
+#[repr(C)]
+struct PingVTable {
+    server_responded: unsafe extern "C" fn(*mut PingResponder, *mut sys::gameserveritem_t),
+    server_failed_to_respond: unsafe extern "C" fn(*mut PingResponder),
+}
+
+#[repr(C)]
+struct PingResponder {
+    vtable: *const PingVTable,
+    cb: Option<Box<dyn FnOnce(Option<GameServerItem>)>>,
+}
+
+impl QueryResponder for PingResponder {
+    fn is_finished(&self) -> bool {
+        self.cb.is_none()
+    }
+}
+
+static PING_VTABLE: PingVTable = PingVTable {
+    server_responded: ping_responded,
+    server_failed_to_respond: ping_failed_to_respond,
+};
+
+unsafe extern "C" fn ping_responded(this: *mut PingResponder, server: *mut sys::gameserveritem_t) {
+    if let Some(cb) = (*this).cb.take() {
+        cb(Some(GameServerItem::from_raw(&*server)));
+    }
+}
+
+unsafe extern "C" fn ping_failed_to_respond(this: *mut PingResponder) {
+    if let Some(cb) = (*this).cb.take() {
+        cb(None);
+    }
+}
+
+#[repr(C)]
+struct PlayersVTable {
+    add_player_to_list: unsafe extern "C" fn(*mut PlayersResponder, *const c_char, c_int, f32),
+    players_failed_to_respond: unsafe extern "C" fn(*mut PlayersResponder),
+    players_refresh_complete: unsafe extern "C" fn(*mut PlayersResponder),
+}
+
+#[repr(C)]
+struct PlayersResponder {
+    vtable: *const PlayersVTable,
+    players: Vec<ServerPlayer>,
+    cb: Option<Box<dyn FnOnce(Option<Vec<ServerPlayer>>)>>,
+}
+
+impl QueryResponder for PlayersResponder {
+    fn is_finished(&self) -> bool {
+        self.cb.is_none()
+    }
+}
+
+static PLAYERS_VTABLE: PlayersVTable = PlayersVTable {
+    add_player_to_list: players_add,
+    players_failed_to_respond: players_failed,
+    players_refresh_complete: players_complete,
+};
+
+unsafe extern "C" fn players_add(
+    this: *mut PlayersResponder,
+    name: *const c_char,
+    score: c_int,
+    time_played: f32,
+) {
+    (*this).players.push(ServerPlayer {
+        name: string_from_ptr(name),
+        score,
+        time_played,
+    });
+}
+
+unsafe extern "C" fn players_failed(this: *mut PlayersResponder) {
+    if let Some(cb) = (*this).cb.take() {
+        cb(None);
+    }
+}
+
+unsafe extern "C" fn players_complete(this: *mut PlayersResponder) {
+    let this = &mut *this;
+    if let Some(cb) = this.cb.take() {
+        cb(Some(std::mem::take(&mut this.players)));
+    }
+}
+
+#[repr(C)]
+struct RulesVTable {
+    rules_responded: unsafe extern "C" fn(*mut RulesResponder, *const c_char, *const c_char),
+    rules_failed_to_respond: unsafe extern "C" fn(*mut RulesResponder),
+    rules_refresh_complete: unsafe extern "C" fn(*mut RulesResponder),
+}
+
+#[repr(C)]
+struct RulesResponder {
+    vtable: *const RulesVTable,
+    rules: HashMap<String, String>,
+    cb: Option<Box<dyn FnOnce(Option<HashMap<String, String>>)>>,
+}
+
+impl QueryResponder for RulesResponder {
+    fn is_finished(&self) -> bool {
+        self.cb.is_none()
+    }
+}
+
+static RULES_VTABLE: RulesVTable = RulesVTable {
+    rules_responded: rules_add,
+    rules_failed_to_respond: rules_failed,
+    rules_refresh_complete: rules_complete,
+};
+
+unsafe extern "C" fn rules_add(
+    this: *mut RulesResponder,
+    rule: *const c_char,
+    value: *const c_char,
+) {
+    (*this)
+        .rules
+        .insert(string_from_ptr(rule), string_from_ptr(value));
+}
+
+unsafe extern "C" fn rules_failed(this: *mut RulesResponder) {
+    if let Some(cb) = (*this).cb.take() {
+        cb(None);
+    }
+}
+
+unsafe extern "C" fn rules_complete(this: *mut RulesResponder) {
+    let this = &mut *this;
+    if let Some(cb) = this.cb.take() {
+        cb(Some(std::mem::take(&mut this.rules)));
+    }
+}
+
+impl<Manager> MatchmakingServers<Manager> {
+    /// Requests a list of game servers for the app.
+    ///
+    /// `on_server` is called for every server that responds and `on_complete`
+    /// once the list has been refreshed. `filters` are the master server
+    /// filters, e.g. `("gametagsand", "ranked")`, and are ignored for LAN lists.
+    ///
+    /// Callbacks run during `run_callbacks`. The request is released when the
+    /// returned handle is dropped.
+    pub fn request_server_list<S, C>(
+        &self,
+        kind: ServerListKind,
+        app_id: AppId,
+        filters: &[(&str, &str)],
+        on_server: S,
+        on_complete: C,
+    ) -> ServerListRequest<Manager>
+    where
+        S: FnMut(GameServerItem) + 'static,
+        C: FnOnce(ServerListResponse) + 'static,
+    {
+        let responder = Box::into_raw(Box::new(ServerListResponder {
+            vtable: &SERVER_LIST_VTABLE,
+            mms: self.mms,
+            on_server: Box::new(on_server),
+            on_complete: Some(Box::new(on_complete)),
+        }));
+        let mut filters: Vec<_> = filters
+            .iter()
+            .map(|(key, value)| filter_pair(key, value))
+            .collect();
+
+        unsafe {
+            let response = responder as *mut sys::ISteamMatchmakingServerListResponse;
+            let mut ptr = filters.as_mut_ptr();
+            let count = filters.len() as u32;
+            let handle = match kind {
+                ServerListKind::Internet => {
+                    sys::SteamAPI_ISteamMatchmakingServers_RequestInternetServerList(
+                        self.mms, app_id.0, &mut ptr, count, response,
+                    )
+                }
+                ServerListKind::Lan => sys::SteamAPI_ISteamMatchmakingServers_RequestLANServerList(
+                    self.mms, app_id.0, response,
+                ),
+                ServerListKind::Friends => {
+                    sys::SteamAPI_ISteamMatchmakingServers_RequestFriendsServerList(
+                        self.mms, app_id.0, &mut ptr, count, response,
+                    )
+                }
+                ServerListKind::Favorites => {
+                    sys::SteamAPI_ISteamMatchmakingServers_RequestFavoritesServerList(
+                        self.mms, app_id.0, &mut ptr, count, response,
+                    )
+                }
+                ServerListKind::History => {
+                    sys::SteamAPI_ISteamMatchmakingServers_RequestHistoryServerList(
+                        self.mms, app_id.0, &mut ptr, count, response,
+                    )
+                }
+            };
+
+            ServerListRequest {
+                handle,
+                responder,
+                mms: self.mms,
+                _filters: filters,
+                _inner: self.inner.clone(),
+            }
+        }
+    }
+
+    /// Queries a single server for its details. The callback receives `None`
+    /// if the server did not respond.
+    ///
+    /// The query is cancelled when the returned handle is dropped.
+    pub fn ping_server<F>(&self, addr: Ipv4Addr, query_port: u16, cb: F) -> ServerQuery<Manager>
+    where
+        F: FnOnce(Option<GameServerItem>) + 'static,
+    {
+        let responder = Box::into_raw(Box::new(PingResponder {
+            vtable: &PING_VTABLE,
+            cb: Some(Box::new(cb)),
+        }));
+        unsafe {
+            let handle = sys::SteamAPI_ISteamMatchmakingServers_PingServer(
+                self.mms,
+                u32::from(addr),
+                query_port,
+                responder as *mut sys::ISteamMatchmakingPingResponse,
+            );
+            self.server_query(handle, responder)
+        }
+    }
+
+    /// Queries the players on a single server. The callback receives `None`
+    /// if the server did not respond.
+    ///
+    /// The query is cancelled when the returned handle is dropped.
+    pub fn player_details<F>(&self, addr: Ipv4Addr, query_port: u16, cb: F) -> ServerQuery<Manager>
+    where
+        F: FnOnce(Option<Vec<ServerPlayer>>) + 'static,
+    {
+        let responder = Box::into_raw(Box::new(PlayersResponder {
+            vtable: &PLAYERS_VTABLE,
+            players: Vec::new(),
+            cb: Some(Box::new(cb)),
+        }));
+        unsafe {
+            let handle = sys::SteamAPI_ISteamMatchmakingServers_PlayerDetails(
+                self.mms,
+                u32::from(addr),
+                query_port,
+                responder as *mut sys::ISteamMatchmakingPlayersResponse,
+            );
+            self.server_query(handle, responder)
+        }
+    }
+
+    /// Queries the rules (key/values set with `Server::set_key_value`) of a
+    /// single server. The callback receives `None` if the server did not respond.
+    ///
+    /// The query is cancelled when the returned handle is dropped.
+    pub fn server_rules<F>(&self, addr: Ipv4Addr, query_port: u16, cb: F) -> ServerQuery<Manager>
+    where
+        F: FnOnce(Option<HashMap<String, String>>) + 'static,
+    {
+        let responder = Box::into_raw(Box::new(RulesResponder {
+            vtable: &RULES_VTABLE,
+            rules: HashMap::new(),
+            cb: Some(Box::new(cb)),
+        }));
+        unsafe {
+            let handle = sys::SteamAPI_ISteamMatchmakingServers_ServerRules(
+                self.mms,
+                u32::from(addr),
+                query_port,
+                responder as *mut sys::ISteamMatchmakingRulesResponse,
+            );
+            self.server_query(handle, responder)
+        }
+    }
+
+    fn server_query<R>(&self, handle: sys::HServerQuery, responder: *mut R) -> ServerQuery<Manager>
+    where
+        R: QueryResponder + 'static,
+    {
+        ServerQuery {
+            handle,
+            responder,
+            mms: self.mms,
+            _inner: self.inner.clone(),
+        }
+    }
+}
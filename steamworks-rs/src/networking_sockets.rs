@@ -268,6 +268,38 @@ impl<Manager: 'static> NetworkingSockets<Manager> {
             inner: self.inner.clone(),
         }
     }
+
+    /// Send one or more messages without copying the message payload.
+    ///
+    /// Same as `ListenSocket::send_messages`, for connections that were not
+    /// accepted through a listen socket (e.g. the client side of `connect_p2p`).
+    ///
+    /// Returns the message number or Steam error for each sent message.
+    pub fn send_messages(
+        &self,
+        messages: impl IntoIterator<Item = NetworkingMessage<Manager>>,
+    ) -> Vec<SResult<MessageNumber>> {
+        let messages: Vec<_> = messages.into_iter().map(|x| x.take_message()).collect();
+        let mut results = vec![0; messages.len()];
+        unsafe {
+            sys::SteamAPI_ISteamNetworkingSockets_SendMessages(
+                self.sockets,
+                messages.len() as _,
+                messages.as_ptr(),
+                results.as_mut_ptr(),
+            );
+            results
+                .into_iter()
+                .map(|x| {
+                    if x >= 0 {
+                        Ok(MessageNumber(x as u64))
+                    } else {
+                        Err((-x).try_into().expect("invalid error code"))
+                    }
+                })
+                .collect()
+        }
+    }
 }
 
 /// A socket that will continually listen for client connections.
@@ -809,6 +841,34 @@ impl<Manager: 'static> NetConnection<Manager> {
         self.is_handled = true
     }
 
+    /// Configure multiple outbound message streams ("lanes") on the connection,
+    /// and control head-of-line blocking between them.
+    ///
+    /// Messages within a given lane are always sent in the order they are
+    /// queued, but messages from different lanes may be sent out of order.
+    /// Lanes with a lower priority number are always sent first, lanes with
+    /// the same priority share the bandwidth according to their weights.
+    ///
+    /// Both slices must have the same length. Messages are sent on lane 0
+    /// unless `NetworkingMessage::set_lane` is used.
+    pub fn configure_lanes(&self, priorities: &[i32], weights: &[u16]) -> SResult<()> {
+        assert_eq!(priorities.len(), weights.len());
+
+        let result = unsafe {
+            sys::SteamAPI_ISteamNetworkingSockets_ConfigureConnectionLanes(
+                self.sockets,
+                self.handle,
+                priorities.len() as _,
+                priorities.as_ptr(),
+                weights.as_ptr(),
+            )
+        };
+        match result {
+            sys::EResult::k_EResultOK => Ok(()),
+            error => Err(error.into()),
+        }
+    }
+
     pub fn get_connection_info(&self) -> Option<NetConnectionInfo> {
         unsafe {
             let mut info: sys::SteamNetConnectionInfo_t = std::mem::zeroed();
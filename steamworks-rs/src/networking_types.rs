@@ -1653,6 +1653,18 @@ impl<Manager> NetworkingMessage<Manager> {
         }
     }
 
+    /// For outbound messages, which lane to use. See `NetConnection::configure_lanes`.
+    /// For inbound messages, what lane was the message received on?
+    pub fn lane(&self) -> u16 {
+        unsafe { (*self.message).m_idxLane }
+    }
+
+    pub fn set_lane(&mut self, lane: u16) {
+        unsafe {
+            (*self.message).m_idxLane = lane;
+        }
+    }
+
     /// Message payload
     pub fn data(&self) -> &[u8] {
         unsafe {
@@ -1,6 +1,7 @@
 use super::*;
 #[cfg(test)]
 use serial_test::serial;
+use std::ffi::NulError;
 use std::net::Ipv4Addr;
 
 /// The main entry point into the steam client for servers.
@@ -259,6 +260,13 @@ impl Server {
         }
     }
 
+    /// Begin the process of logging the game server out of Steam
+    pub fn log_off(&self) {
+        unsafe {
+            sys::SteamAPI_ISteamGameServer_LogOff(self.server);
+        }
+    }
+
     /// If active, updates the master server with this server's presence so players can find it via
     /// the steam matchmaking/server browser interfaces.
     pub fn enable_heartbeats(&self, active: bool) {
@@ -313,10 +321,109 @@ impl Server {
         }
     }
 
+    /// Sets the spectator port for the server browser, 0 means no spectator port
+    pub fn set_spectator_port(&self, port: u16) {
+        unsafe {
+            sys::SteamAPI_ISteamGameServer_SetSpectatorPort(self.server, port);
+        }
+    }
+
+    /// Sets the name of the spectator server. (Only used if spectator port is nonzero.)
+    ///
+    /// Returns an error if the name contains a nul byte.
+    pub fn set_spectator_server_name(&self, name: &str) -> Result<(), NulError> {
+        let name = CString::new(name)?;
+        unsafe {
+            sys::SteamAPI_ISteamGameServer_SetSpectatorServerName(self.server, name.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Clears the whole list of key/values that are sent in rules queries.
+    pub fn clear_all_key_values(&self) {
+        unsafe {
+            sys::SteamAPI_ISteamGameServer_ClearAllKeyValues(self.server);
+        }
+    }
+
+    /// Adds or updates a key/value pair that is sent in rules queries.
+    ///
+    /// Returns an error if the key or value contains a nul byte.
+    pub fn set_key_value(&self, key: &str, value: &str) -> Result<(), NulError> {
+        let key = CString::new(key)?;
+        let value = CString::new(value)?;
+        unsafe {
+            sys::SteamAPI_ISteamGameServer_SetKeyValue(self.server, key.as_ptr(), value.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Sets a string defining the "gametags" for this server, this is optional, but if set it
+    /// allows users to filter in the matchmaking/server-browser interfaces based on the value.
+    ///
+    /// Tags are comma separated and limited to 128 bytes. Returns an error if the tags contain a
+    /// nul byte.
+    pub fn set_game_tags(&self, tags: &str) -> Result<(), NulError> {
+        let tags = CString::new(tags)?;
+        unsafe {
+            sys::SteamAPI_ISteamGameServer_SetGameTags(self.server, tags.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Sets a string defining the "gamedata" for this server, this is optional, but if set it
+    /// allows users to filter in the matchmaking/server-browser interfaces based on the value.
+    ///
+    /// Limited to 2048 bytes. Returns an error if the data contains a nul byte.
+    pub fn set_game_data(&self, data: &str) -> Result<(), NulError> {
+        let data = CString::new(data)?;
+        unsafe {
+            sys::SteamAPI_ISteamGameServer_SetGameData(self.server, data.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Sets the region identifier for the master server updater.
+    ///
+    /// Returns an error if the region contains a nul byte.
+    pub fn set_region(&self, region: &str) -> Result<(), NulError> {
+        let region = CString::new(region)?;
+        unsafe {
+            sys::SteamAPI_ISteamGameServer_SetRegion(self.server, region.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Updates the name and score of a connected user as shown in the server browser.
+    ///
+    /// Returns `false` if the user is not connected to this server, or an error if the name
+    /// contains a nul byte.
+    pub fn update_user_data(
+        &self,
+        user: SteamId,
+        name: &str,
+        score: u32,
+    ) -> Result<bool, NulError> {
+        let name = CString::new(name)?;
+        unsafe {
+            Ok(sys::SteamAPI_ISteamGameServer_BUpdateUserData(
+                self.server,
+                user.0,
+                name.as_ptr(),
+                score,
+            ))
+        }
+    }
+
     pub fn secure(&self) -> bool {
         unsafe { sys::SteamAPI_ISteamGameServer_BSecure(self.server) }
     }
 
+    /// Whether the server is currently logged on to Steam
+    pub fn logged_on(&self) -> bool {
+        unsafe { sys::SteamAPI_ISteamGameServer_BLoggedOn(self.server) }
+    }
+
     /// Returns an accessor to the steam UGC interface (steam workshop)
     ///
     /// **For this to work properly, you need to call `UGC::init_for_game_server()`!**